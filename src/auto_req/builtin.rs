@@ -1,8 +1,14 @@
 use crate::error::AutoReqError;
-use elf::abi::{EM_ALPHA, SHT_GNU_HASH, SHT_HASH};
+use elf::abi::{
+    DT_NEEDED, DT_SONAME, EM_ALPHA, ET_DYN, SHT_DYNAMIC, SHT_GNU_HASH, SHT_GNU_VERDEF,
+    SHT_GNU_VERNEED, SHT_HASH, VER_FLG_BASE,
+};
 use elf::endian::AnyEndian;
 use elf::file::{Class, FileHeader};
-use elf::{ElfStream, ParseError};
+use elf::gnu_symver::{VerDefIterator, VerNeedIterator};
+use elf::section::{SectionHeader, SectionHeaderTable};
+use elf::string_table::StringTable;
+use elf::{ElfBytes, ElfStream, ParseError};
 use std::collections::BTreeSet;
 use std::ffi::OsString;
 use std::fs::File;
@@ -44,24 +50,40 @@ impl ElfInfo {
     }
 }
 
+/// Returns whether the file is an ELF file, as the files to be scanned are probed.
+pub(crate) fn is_elf<P: AsRef<Path>>(path: P) -> bool {
+    ElfInfo::new(path).is_ok()
+}
+
 #[test]
 fn test_elf_info_new() {
     ElfInfo::new("/bin/sh").unwrap();
+    assert!(is_elf("/bin/sh"));
+    assert!(!is_elf(file!()));
+}
+
+/// The method to resolve the shared libraries required by an ELF file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SharedLibraryResolver {
+    /// Run `ldd -v` on the file
+    Ldd,
+    /// Read `DT_NEEDED` entries and `.gnu.version_r` section of the file
+    DynamicSection,
+}
+
+fn skip_so_name(so_name: &str) -> bool {
+    so_name.contains(".so")
+        && (so_name.starts_with("ld.")
+            || so_name.starts_with("ld-")
+            || so_name.starts_with("ld64.")
+            || so_name.starts_with("ld64-")
+            || so_name.starts_with("lib"))
 }
 
 fn find_requires_by_ldd(
     path: &Path,
     marker: Option<&str>,
 ) -> Result<BTreeSet<String>, AutoReqError> {
-    fn skip_so_name(so_name: &str) -> bool {
-        so_name.contains(".so")
-            && (so_name.starts_with("ld.")
-                || so_name.starts_with("ld-")
-                || so_name.starts_with("ld64.")
-                || so_name.starts_with("ld64-")
-                || so_name.starts_with("lib"))
-    }
-
     let process = Command::new("ldd")
         .arg("-v")
         .arg(path.as_os_str())
@@ -93,7 +115,7 @@ fn find_requires_by_ldd(
     let mut requires = BTreeSet::new();
     for name in unversioned_libraries
         .into_iter()
-        .chain(versioned_libraries.into_iter())
+        .chain(versioned_libraries)
         .filter(|&name| skip_so_name(name))
     {
         if name.contains(" (") {
@@ -107,9 +129,93 @@ fn find_requires_by_ldd(
     Ok(requires)
 }
 
-fn find_requires_of_elf(path: &Path) -> Result<Option<BTreeSet<String>>, AutoReqError> {
+/// Returns the string table linked to the section `shdr` via `sh_link`.
+fn linked_strtab<'data>(
+    elf: &ElfBytes<'data, AnyEndian>,
+    shdrs: &SectionHeaderTable<'data, AnyEndian>,
+    shdr: &SectionHeader,
+) -> Result<StringTable<'data>, ParseError> {
+    let strtab_shdr = shdrs.get(shdr.sh_link as usize)?;
+    elf.section_data_as_strtab(&strtab_shdr)
+}
+
+fn find_requires_by_dynamic_section(
+    path: &Path,
+    marker: Option<&str>,
+) -> Result<BTreeSet<String>, AutoReqError> {
+    let data = std::fs::read(path)?;
+    let parse_error = |e| AutoReqError::ElfParse(path.to_path_buf(), e);
+    let elf = ElfBytes::<AnyEndian>::minimal_parse(&data).map_err(parse_error)?;
+
+    let marker = marker.unwrap_or_default();
+    let mut requires = BTreeSet::new();
+    let Some(shdrs) = elf.section_headers() else {
+        return Ok(requires);
+    };
+
+    if let Some(dynamic_shdr) = shdrs.iter().find(|s| s.sh_type == SHT_DYNAMIC) {
+        let strtab = linked_strtab(&elf, &shdrs, &dynamic_shdr).map_err(parse_error)?;
+        let dynamic = elf.dynamic().map_err(parse_error)?.into_iter().flatten();
+        for entry in dynamic.filter(|d| d.d_tag == DT_NEEDED) {
+            let name = strtab.get(entry.d_val() as usize).map_err(parse_error)?;
+            if skip_so_name(name) {
+                requires.insert(format!("{name}(){marker}"));
+            }
+        }
+    }
+
+    if let Some(verneed_shdr) = shdrs.iter().find(|s| s.sh_type == SHT_GNU_VERNEED) {
+        let strtab = linked_strtab(&elf, &shdrs, &verneed_shdr).map_err(parse_error)?;
+        let (verneed_data, _) = elf.section_data(&verneed_shdr).map_err(parse_error)?;
+        let verneeds = VerNeedIterator::new(
+            elf.ehdr.endianness,
+            elf.ehdr.class,
+            verneed_shdr.sh_info as u64,
+            0,
+            verneed_data,
+        );
+        for (verneed, auxs) in verneeds {
+            let name = strtab.get(verneed.vn_file as usize).map_err(parse_error)?;
+            if !skip_so_name(name) {
+                continue;
+            }
+            for aux in auxs {
+                let version = strtab.get(aux.vna_name as usize).map_err(parse_error)?;
+                requires.insert(format!("{name}({version}){marker}"));
+            }
+        }
+    }
+
+    Ok(requires)
+}
+
+#[test]
+fn test_find_requires_by_dynamic_section() {
+    let info = ElfInfo::new("/bin/sh").unwrap();
+    let requires = find_requires_by_dynamic_section(Path::new("/bin/sh"), info.marker()).unwrap();
+    assert!(!requires.is_empty());
+    assert!(requires.iter().all(|v| v.contains(".so")));
+    if let Ok(ldd_requires) = find_requires_by_ldd(Path::new("/bin/sh"), info.marker()) {
+        assert!(requires.is_subset(&ldd_requires));
+    }
+
+    assert!(matches!(
+        find_requires_by_dynamic_section(Path::new(file!()), None),
+        Err(AutoReqError::ElfParse(_, _))
+    ));
+}
+
+fn find_requires_of_elf(
+    path: &Path,
+    resolver: SharedLibraryResolver,
+) -> Result<Option<BTreeSet<String>>, AutoReqError> {
     if let Ok(info) = ElfInfo::new(path) {
-        let mut requires = find_requires_by_ldd(path, info.marker())?;
+        let mut requires = match resolver {
+            SharedLibraryResolver::Ldd => find_requires_by_ldd(path, info.marker())?,
+            SharedLibraryResolver::DynamicSection => {
+                find_requires_by_dynamic_section(path, info.marker())?
+            }
+        };
         if info.got_gnu_hash && !info.got_hash {
             requires.insert("rtld(GNU_HASH)".to_string());
         }
@@ -121,13 +227,23 @@ fn find_requires_of_elf(path: &Path) -> Result<Option<BTreeSet<String>>, AutoReq
 
 #[test]
 fn test_find_requires_of_elf() {
-    let requires = find_requires_of_elf(Path::new("/bin/sh")).unwrap().unwrap();
-    assert!(
-        requires
-            .iter()
-            .all(|v| v.contains(".so") || v == "rtld(GNU_HASH)")
-    );
-    assert!(matches!(find_requires_of_elf(Path::new(file!())), Ok(None)));
+    for resolver in [
+        SharedLibraryResolver::Ldd,
+        SharedLibraryResolver::DynamicSection,
+    ] {
+        let requires = find_requires_of_elf(Path::new("/bin/sh"), resolver)
+            .unwrap()
+            .unwrap();
+        assert!(
+            requires
+                .iter()
+                .all(|v| v.contains(".so") || v == "rtld(GNU_HASH)")
+        );
+        assert!(matches!(
+            find_requires_of_elf(Path::new(file!()), resolver),
+            Ok(None)
+        ));
+    }
 }
 
 fn find_require_of_shebang(path: &Path) -> Result<Option<String>, AutoReqError> {
@@ -154,6 +270,102 @@ fn find_require_of_shebang(path: &Path) -> Result<Option<String>, AutoReqError>
     })
 }
 
+fn find_provides_by_dynamic_section(
+    path: &Path,
+    marker: Option<&str>,
+) -> Result<BTreeSet<String>, AutoReqError> {
+    let data = std::fs::read(path)?;
+    let parse_error = |e| AutoReqError::ElfParse(path.to_path_buf(), e);
+    let elf = ElfBytes::<AnyEndian>::minimal_parse(&data).map_err(parse_error)?;
+
+    let marker = marker.unwrap_or_default();
+    let mut provides = BTreeSet::new();
+    let Some(shdrs) = elf.section_headers() else {
+        return Ok(provides);
+    };
+    if elf.ehdr.e_type != ET_DYN {
+        return Ok(provides);
+    }
+
+    let mut soname = None;
+    if let Some(dynamic_shdr) = shdrs.iter().find(|s| s.sh_type == SHT_DYNAMIC) {
+        let strtab = linked_strtab(&elf, &shdrs, &dynamic_shdr).map_err(parse_error)?;
+        let mut dynamic = elf.dynamic().map_err(parse_error)?.into_iter().flatten();
+        if let Some(entry) = dynamic.find(|d| d.d_tag == DT_SONAME) {
+            soname = Some(strtab.get(entry.d_val() as usize).map_err(parse_error)?);
+        }
+    }
+
+    if let Some(verdef_shdr) = shdrs.iter().find(|s| s.sh_type == SHT_GNU_VERDEF) {
+        let strtab = linked_strtab(&elf, &shdrs, &verdef_shdr).map_err(parse_error)?;
+        let (verdef_data, _) = elf.section_data(&verdef_shdr).map_err(parse_error)?;
+        let verdefs = VerDefIterator::new(
+            elf.ehdr.endianness,
+            elf.ehdr.class,
+            verdef_shdr.sh_info as u64,
+            0,
+            verdef_data,
+        );
+        let mut versions = Vec::new();
+        for (verdef, mut auxs) in verdefs {
+            // Only the first auxiliary entry names the version; the rest are its parents.
+            let Some(aux) = auxs.next() else {
+                continue;
+            };
+            let version = strtab.get(aux.vda_name as usize).map_err(parse_error)?;
+            if verdef.vd_flags & VER_FLG_BASE != 0 {
+                soname = soname.or(Some(version));
+            } else {
+                versions.push(version);
+            }
+        }
+        if let Some(name) = soname.filter(|v| skip_so_name(v)) {
+            for version in versions {
+                provides.insert(format!("{name}({version}){marker}"));
+            }
+        }
+    }
+
+    if let Some(name) = soname.filter(|v| skip_so_name(v)) {
+        provides.insert(format!("{name}(){marker}"));
+    }
+
+    Ok(provides)
+}
+
+fn find_provides_of_elf(path: &Path) -> Result<Option<BTreeSet<String>>, AutoReqError> {
+    if let Ok(info) = ElfInfo::new(path) {
+        Ok(Some(find_provides_by_dynamic_section(path, info.marker())?))
+    } else {
+        Ok(None)
+    }
+}
+
+#[test]
+fn test_find_provides_of_elf() {
+    // executables do not provide anything
+    let provides = find_provides_of_elf(Path::new("/bin/sh")).unwrap().unwrap();
+    assert!(provides.is_empty());
+    assert!(matches!(find_provides_of_elf(Path::new(file!())), Ok(None)));
+
+    let libc = [
+        "/lib64/libc.so.6",
+        "/usr/lib64/libc.so.6",
+        "/lib/x86_64-linux-gnu/libc.so.6",
+        "/usr/lib/x86_64-linux-gnu/libc.so.6",
+        "/lib/aarch64-linux-gnu/libc.so.6",
+        "/usr/lib/aarch64-linux-gnu/libc.so.6",
+    ]
+    .into_iter()
+    .map(Path::new)
+    .find(|p| p.exists());
+    if let Some(libc) = libc {
+        let provides = find_provides_of_elf(libc).unwrap().unwrap();
+        assert!(provides.iter().any(|v| v.starts_with("libc.so.6()")));
+        assert!(provides.iter().any(|v| v.starts_with("libc.so.6(GLIBC_2.")));
+    }
+}
+
 #[test]
 fn test_find_require_of_shebang() {
     assert!(matches!(
@@ -188,11 +400,14 @@ fn is_executable(path: &Path) -> bool {
 }
 
 /// find requires.
-pub(super) fn find_requires<P: AsRef<Path>>(path: &[P]) -> Result<Vec<String>, AutoReqError> {
+pub(super) fn find_requires<P: AsRef<Path>>(
+    path: &[P],
+    resolver: SharedLibraryResolver,
+) -> Result<Vec<String>, AutoReqError> {
     let mut requires = Vec::new();
     for p in path.iter().map(|v| v.as_ref()) {
         if is_executable(p) {
-            if let Some(elf_requires) = find_requires_of_elf(p)? {
+            if let Some(elf_requires) = find_requires_of_elf(p, resolver)? {
                 requires.extend(elf_requires);
             } else if let Some(shebang_require) = find_require_of_shebang(p)? {
                 requires.push(shebang_require);
@@ -201,3 +416,16 @@ pub(super) fn find_requires<P: AsRef<Path>>(path: &[P]) -> Result<Vec<String>, A
     }
     Ok(requires)
 }
+
+/// find provides.
+pub(super) fn find_provides<P: AsRef<Path>>(path: &[P]) -> Result<Vec<String>, AutoReqError> {
+    let mut provides = Vec::new();
+    for p in path.iter().map(|v| v.as_ref()) {
+        if is_executable(p)
+            && let Some(elf_provides) = find_provides_of_elf(p)?
+        {
+            provides.extend(elf_provides);
+        }
+    }
+    Ok(provides)
+}
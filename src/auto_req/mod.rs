@@ -1,4 +1,6 @@
 use crate::{cli, error::AutoReqError};
+use builtin::SharedLibraryResolver;
+pub(crate) use builtin::is_elf;
 use std::path::{Path, PathBuf};
 
 mod builtin;
@@ -7,6 +9,9 @@ mod script;
 /// The path to the system default find-requires program
 const RPM_FIND_REQUIRES: &str = "/usr/lib/rpm/find-requires";
 
+/// The path to the system default find-provides program
+const RPM_FIND_PROVIDES: &str = "/usr/lib/rpm/find-provides";
+
 /// The method to auto-req
 #[derive(Debug, PartialEq, Eq)]
 pub enum AutoReqMode {
@@ -16,8 +21,10 @@ pub enum AutoReqMode {
     Disabled,
     /// `find-requires` script
     Script(PathBuf),
-    /// Builtin
+    /// Builtin based on `ldd`
     BuiltIn,
+    /// Builtin based on the ELF dynamic section
+    Elf,
 }
 
 impl From<cli::AutoReqMode> for AutoReqMode {
@@ -26,6 +33,7 @@ impl From<cli::AutoReqMode> for AutoReqMode {
             cli::AutoReqMode::Auto => AutoReqMode::Auto,
             cli::AutoReqMode::Disabled => AutoReqMode::Disabled,
             cli::AutoReqMode::Builtin => AutoReqMode::BuiltIn,
+            cli::AutoReqMode::Elf => AutoReqMode::Elf,
             cli::AutoReqMode::FindRequires => AutoReqMode::Script(PathBuf::from(RPM_FIND_REQUIRES)),
             cli::AutoReqMode::Script(path) => AutoReqMode::Script(path),
         }
@@ -42,16 +50,75 @@ pub fn find_requires<T: IntoIterator<Item = P>, P: AsRef<Path>>(
             if Path::new(RPM_FIND_REQUIRES).exists() {
                 find_requires(files, AutoReqMode::Script(PathBuf::from(RPM_FIND_REQUIRES)))
             } else {
-                find_requires(files, AutoReqMode::BuiltIn)
+                find_requires(files, AutoReqMode::Elf)
             }
         }
         AutoReqMode::Disabled => Ok(Vec::new()),
-        AutoReqMode::Script(script) => Ok(script::find_requires(
+        AutoReqMode::Script(script) => Ok(script::find_dependencies(
             files.into_iter().collect::<Vec<_>>().as_slice(),
             script.as_path(),
         )?),
         AutoReqMode::BuiltIn => Ok(builtin::find_requires(
             files.into_iter().collect::<Vec<_>>().as_slice(),
+            SharedLibraryResolver::Ldd,
+        )?),
+        AutoReqMode::Elf => Ok(builtin::find_requires(
+            files.into_iter().collect::<Vec<_>>().as_slice(),
+            SharedLibraryResolver::DynamicSection,
+        )?),
+    }
+}
+
+/// The method to auto-prov
+#[derive(Debug, PartialEq, Eq)]
+pub enum AutoProvMode {
+    /// Automatically selected
+    Auto,
+    /// Disable
+    Disabled,
+    /// `find-provides` script
+    Script(PathBuf),
+    /// Builtin based on the ELF dynamic section
+    BuiltIn,
+}
+
+impl From<cli::AutoProvMode> for AutoProvMode {
+    fn from(value: cli::AutoProvMode) -> Self {
+        match value {
+            cli::AutoProvMode::Auto => AutoProvMode::Auto,
+            cli::AutoProvMode::Disabled => AutoProvMode::Disabled,
+            cli::AutoProvMode::Builtin => AutoProvMode::BuiltIn,
+            cli::AutoProvMode::FindProvides => {
+                AutoProvMode::Script(PathBuf::from(RPM_FIND_PROVIDES))
+            }
+            cli::AutoProvMode::Script(path) => AutoProvMode::Script(path),
+        }
+    }
+}
+
+/// Find provides
+pub fn find_provides<T: IntoIterator<Item = P>, P: AsRef<Path>>(
+    files: T,
+    mode: AutoProvMode,
+) -> Result<Vec<String>, AutoReqError> {
+    match mode {
+        AutoProvMode::Auto => {
+            if Path::new(RPM_FIND_PROVIDES).exists() {
+                find_provides(
+                    files,
+                    AutoProvMode::Script(PathBuf::from(RPM_FIND_PROVIDES)),
+                )
+            } else {
+                find_provides(files, AutoProvMode::BuiltIn)
+            }
+        }
+        AutoProvMode::Disabled => Ok(Vec::new()),
+        AutoProvMode::Script(script) => Ok(script::find_dependencies(
+            files.into_iter().collect::<Vec<_>>().as_slice(),
+            script.as_path(),
+        )?),
+        AutoProvMode::BuiltIn => Ok(builtin::find_provides(
+            files.into_iter().collect::<Vec<_>>().as_slice(),
         )?),
     }
 }
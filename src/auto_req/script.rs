@@ -4,8 +4,8 @@ use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
 use std::process::{Command, Stdio};
 
-/// find requires using `find-requires` program located at `script_path`.
-pub(super) fn find_requires<P: AsRef<Path>, S: AsRef<OsStr>>(
+/// find requires or provides using `find-requires` or `find-provides` program located at `script_path`.
+pub(super) fn find_dependencies<P: AsRef<Path>, S: AsRef<OsStr>>(
     path: &[P],
     script_path: S,
 ) -> Result<Vec<String>, AutoReqError> {
@@ -31,7 +31,7 @@ pub(super) fn find_requires<P: AsRef<Path>, S: AsRef<OsStr>>(
 
     for line in reader.lines() {
         match line {
-            Ok(content) if content == "" => (), // ignore empty line
+            Ok(content) if content.is_empty() => (), // ignore empty line
             Ok(content) => requires.push(content),
             Err(e) => {
                 return Err(AutoReqError::ProcessError(
@@ -46,28 +46,32 @@ pub(super) fn find_requires<P: AsRef<Path>, S: AsRef<OsStr>>(
 }
 
 #[test]
-fn test_find_requires() {
+fn test_find_dependencies() {
     assert_eq!(
-        find_requires(&[file!()], "/bin/cat").unwrap(),
+        find_dependencies(&[file!()], "/bin/cat").unwrap(),
         vec![file!().to_string()]
     );
     assert!(matches!(
-        find_requires(&[file!()], "not-exist"),
+        find_dependencies(&[file!()], "not-exist"),
         Err(AutoReqError::ProcessError(_, _))
     ));
     if Path::new(super::RPM_FIND_REQUIRES).is_file() {
         assert!(
-            !find_requires(&["/bin/cat"], super::RPM_FIND_REQUIRES)
+            !find_dependencies(&["/bin/cat"], super::RPM_FIND_REQUIRES)
                 .unwrap()
                 .is_empty()
         );
     }
 
     // empty dependencies shall return empty vector
-    assert!(find_requires(&[file!()], "/bin/false").unwrap().is_empty());
+    assert!(
+        find_dependencies(&[file!()], "/bin/false")
+            .unwrap()
+            .is_empty()
+    );
     if Path::new(super::RPM_FIND_REQUIRES).is_file() {
         assert!(
-            find_requires(&["/dev/null"], super::RPM_FIND_REQUIRES)
+            find_dependencies(&["/dev/null"], super::RPM_FIND_REQUIRES)
                 .unwrap()
                 .is_empty()
         );
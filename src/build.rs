@@ -0,0 +1,234 @@
+//! Running `cargo build` before packaging.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+use serde_json::Value;
+
+use crate::build_target::BuildTarget;
+use crate::cli::Cli;
+use crate::error::{BuildError, Error};
+
+/// Files produced or verified as up to date by `cargo build`.
+#[derive(Debug, Default)]
+pub(crate) struct BuildOutput {
+    artifacts: Vec<PathBuf>,
+    out_dirs: Vec<PathBuf>,
+}
+
+/// Returns the arguments of `cargo build` for the profile, the target and the `package`.
+fn build_args(args: &Cli, build_target: &BuildTarget, package: Option<&Path>) -> Vec<OsString> {
+    let mut command = vec![
+        OsString::from("build"),
+        OsString::from("--message-format=json-render-diagnostics"),
+        OsString::from("--profile"),
+        OsString::from(build_target.profile()),
+    ];
+    if let Some(target) = build_target.target() {
+        command.push("--target".into());
+        command.push(target.into());
+    }
+    if let Some(target_dir) = build_target.target_dir() {
+        command.push("--target-dir".into());
+        command.push(target_dir.into());
+    }
+    if let Some(package) = package {
+        command.push("--manifest-path".into());
+        command.push(package.join("Cargo.toml").into());
+    }
+    command.extend(args.feature_args().into_iter().map(OsString::from));
+    if args.locked {
+        command.push("--locked".into());
+    }
+    command
+}
+
+/// Runs `cargo build` for the `package` and returns its output.
+pub(crate) fn cargo_build(
+    args: &Cli,
+    build_target: &BuildTarget,
+    package: Option<&Path>,
+) -> Result<BuildOutput, Error> {
+    let cargo = std::env::var_os("CARGO").unwrap_or("cargo".into());
+    let output = Command::new(cargo)
+        .args(build_args(args, build_target, package))
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(BuildError::BuildProcess)?;
+    if !output.status.success() {
+        Err(BuildError::BuildFailed(output.status.to_string()))?;
+    }
+    Ok(BuildOutput::parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+impl BuildOutput {
+    /// Parses the JSON messages printed by `cargo build --message-format=json`.
+    fn parse(messages: &str) -> Self {
+        let mut output = Self::default();
+        for message in messages.lines() {
+            let Ok(message) = serde_json::from_str::<Value>(message) else {
+                continue;
+            };
+            match message["reason"].as_str() {
+                Some("compiler-artifact") => {
+                    let filenames = message["filenames"].as_array().into_iter().flatten();
+                    let executable = message["executable"].as_str().map(Value::from);
+                    for path in filenames.chain(executable.as_ref()) {
+                        if let Some(path) = path.as_str() {
+                            output.artifacts.push(PathBuf::from(path));
+                        }
+                    }
+                }
+                Some("build-script-executed") => {
+                    if let Some(out_dir) = message["out_dir"].as_str() {
+                        output.out_dirs.push(PathBuf::from(out_dir));
+                    }
+                }
+                _ => {}
+            }
+        }
+        output
+    }
+
+    /// Checks that none of the `sources` in `target_dir` is left over from an older build.
+    ///
+    /// The artifacts and the outputs of the build scripts are up to date even if they were not
+    /// rebuilt, while the other files are stale if older than the newest artifact.
+    pub(crate) fn check_sources(
+        &self,
+        sources: &[(PathBuf, String)],
+        target_dir: &Path,
+    ) -> Result<(), Error> {
+        let Ok(target_dir) = fs::canonicalize(target_dir) else {
+            return Ok(());
+        };
+        let Some(newest) = self.artifacts.iter().filter_map(|v| modified(v)).max() else {
+            return Ok(());
+        };
+        let artifacts = self
+            .artifacts
+            .iter()
+            .filter_map(|v| fs::canonicalize(v).ok())
+            .collect::<Vec<_>>();
+        let out_dirs = self
+            .out_dirs
+            .iter()
+            .filter_map(|v| fs::canonicalize(v).ok())
+            .collect::<Vec<_>>();
+
+        for (source, _) in sources {
+            let Ok(path) = fs::canonicalize(source) else {
+                continue;
+            };
+            let is_stale = path.starts_with(&target_dir)
+                && !artifacts.contains(&path)
+                && !out_dirs.iter().any(|v| path.starts_with(v))
+                && modified(&path).is_some_and(|v| v < newest);
+            if is_stale {
+                Err(BuildError::StaleAsset(source.clone()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_build_args() {
+        let args = Cli::default();
+        let build_target = BuildTarget::from_args(&args);
+        assert_eq!(
+            build_args(&args, &build_target, None),
+            [
+                "build",
+                "--message-format=json-render-diagnostics",
+                "--profile",
+                "release"
+            ]
+        );
+
+        let args = Cli {
+            profile: "dev".to_string(),
+            target: Some("aarch64-unknown-linux-gnu".to_string()),
+            features: vec!["bar,baz".to_string()],
+            no_default_features: true,
+            locked: true,
+            ..Default::default()
+        };
+        let build_target = BuildTarget::from_args(&args);
+        assert_eq!(
+            build_args(&args, &build_target, Some(Path::new("foo")))[2..],
+            [
+                "--profile",
+                "dev",
+                "--target",
+                "aarch64-unknown-linux-gnu",
+                "--manifest-path",
+                "foo/Cargo.toml",
+                "--features",
+                "bar,baz",
+                "--no-default-features",
+                "--locked",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_sources() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let target_dir = tempdir.path().join("target");
+        let out_dir = target_dir.join("release/build/foo-0123/out");
+        fs::create_dir_all(&out_dir).unwrap();
+        let foo = target_dir.join("release/foo");
+        let old_bar = target_dir.join("release/bar");
+        let completion = out_dir.join("foo.bash");
+        let readme = tempdir.path().join("README.md");
+        for path in [&foo, &old_bar, &completion, &readme] {
+            fs::write(path, "").unwrap();
+        }
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        for path in [&old_bar, &completion, &readme] {
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+        }
+
+        let messages = [
+            r#"{"reason":"build-script-executed","out_dir":"OUT_DIR"}"#,
+            r#"{"reason":"compiler-artifact","filenames":["FOO"],"executable":"FOO"}"#,
+            r#"{"reason":"build-finished","success":true}"#,
+        ]
+        .join("\n")
+        .replace("OUT_DIR", out_dir.to_str().unwrap())
+        .replace("FOO", foo.to_str().unwrap());
+        let output = BuildOutput::parse(&messages);
+        assert_eq!(output.artifacts, [foo.clone(), foo.clone()]);
+        assert_eq!(output.out_dirs, [out_dir]);
+
+        let sources = [&foo, &completion, &readme]
+            .into_iter()
+            .map(|v| (v.clone(), String::new()))
+            .collect::<Vec<_>>();
+        assert!(output.check_sources(&sources, &target_dir).is_ok());
+
+        let sources = [(old_bar.clone(), String::new())];
+        assert!(matches!(
+            output.check_sources(&sources, &target_dir),
+            Err(Error::Build(BuildError::StaleAsset(v))) if v == old_bar
+        ));
+    }
+}
@@ -1,44 +1,118 @@
 use std::env::consts::ARCH;
+use std::ffi::OsString;
+use std::fs;
 use std::path::{Path, PathBuf};
 
+use toml::{Table, Value};
+
 use crate::cli::Cli;
+use crate::error::{ConfigError, Error, FileAnnotatedError};
 
 #[derive(Debug, Clone)]
 pub struct BuildTarget {
-    target_dir: Option<String>,
+    target_dir: Option<PathBuf>,
     target: Option<String>,
     profile: String,
     arch: Option<String>,
 }
 
 impl BuildTarget {
-    pub fn new(args: &Cli) -> Self {
+    /// Determines the target and the target directory in the same precedence as cargo: the
+    /// command line options, the environment variables and then the cargo configuration files
+    /// of the package in `dir` and of `$CARGO_HOME`.
+    pub fn new(args: &Cli, dir: &Path) -> Result<Self, Error> {
+        let cargo_config =
+            CargoConfig::load(&std::env::current_dir()?.join(dir), cargo_home().as_deref())?;
+        Ok(Self::from_args(args).with_defaults(cargo_config, |name| std::env::var_os(name)))
+    }
+
+    /// Determines the target and the target directory from the command line options only.
+    pub fn from_args(args: &Cli) -> Self {
         Self {
-            target_dir: args.target_dir.clone(),
+            target_dir: args.target_dir.as_ref().map(PathBuf::from),
             target: args.target.clone(),
             profile: args.profile.clone(),
             arch: args.arch.clone(),
         }
     }
 
+    /// Fills the target and the target directory not given on the command line with the
+    /// environment variables looked up by `env`, then with `cargo_config`.
+    fn with_defaults(
+        self,
+        cargo_config: CargoConfig,
+        env: impl Fn(&str) -> Option<OsString>,
+    ) -> Self {
+        let target = self
+            .target
+            .or_else(|| env("CARGO_BUILD_TARGET").and_then(|v| v.into_string().ok()))
+            .or(cargo_config.target);
+        let target_dir = self
+            .target_dir
+            .or_else(|| env("CARGO_BUILD_TARGET_DIR").map(PathBuf::from))
+            .or_else(|| env("CARGO_TARGET_DIR").map(PathBuf::from))
+            .or(cargo_config.target_dir);
+        Self {
+            target_dir,
+            target,
+            ..self
+        }
+    }
+
+    /// Uses `target_dir`, such as the one reported by `cargo metadata`, unless the target
+    /// directory is specified explicitly.
+    pub fn with_default_target_dir(self, target_dir: PathBuf) -> Self {
+        Self {
+            target_dir: self.target_dir.or(Some(target_dir)),
+            ..self
+        }
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    pub fn target_dir(&self) -> Option<&Path> {
+        self.target_dir.as_deref()
+    }
+
     pub fn profile(&self) -> &str {
         self.profile.as_str()
     }
 
-    pub fn build_target_path(&self) -> PathBuf {
-        if let Some(target_dir) = &self.target_dir {
-            PathBuf::from(&target_dir)
-        } else {
-            let target_build_dir = std::env::var("CARGO_BUILD_TARGET_DIR")
-                .or_else(|_| std::env::var("CARGO_TARGET_DIR"))
-                .unwrap_or("target".to_string());
-            PathBuf::from(&target_build_dir)
+    /// Returns the name of the directory the artifacts of the profile are placed in.
+    pub fn profile_dir_name(&self) -> &str {
+        match self.profile() {
+            "dev" | "test" => "debug",
+            "bench" => "release",
+            p => p,
         }
     }
 
+    pub fn build_target_path(&self) -> PathBuf {
+        self.target_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("target"))
+    }
+
+    /// Returns the target name, which is the file stem for the path to a target specification
+    /// JSON file.
+    fn target_name(&self) -> Option<&str> {
+        self.target.as_deref().map(|target| {
+            if target.ends_with(".json") {
+                Path::new(target)
+                    .file_stem()
+                    .and_then(|v| v.to_str())
+                    .unwrap_or(target)
+            } else {
+                target
+            }
+        })
+    }
+
     pub fn target_path<P: AsRef<Path>>(&self, dir_name: P) -> PathBuf {
         let mut path = self.build_target_path();
-        if let Some(target) = &self.target {
+        if let Some(target) = self.target_name() {
             path = path.join(target)
         }
         path.join(dir_name)
@@ -48,25 +122,137 @@ impl BuildTarget {
         if let Some(arch) = &self.arch {
             arch.clone()
         } else {
-            let arch = self
-                .target
-                .as_ref()
-                .and_then(|v| v.split('-').next())
-                .unwrap_or(ARCH);
-
-            match arch {
-                "x86" => "i586",
-                "arm" => "armhfp",
-                "powerpc" => "ppc",
-                "powerpc64" => "ppc64",
-                "powerpc64le" => "ppc64le",
-                _ => arch,
-            }
-            .to_string()
+            rpm_arch(self.target_name().unwrap_or(ARCH)).to_string()
         }
     }
 }
 
+/// Returns the RPM arch for the Rust target `triple`, or for the bare arch name such as
+/// `std::env::consts::ARCH`.
+///
+/// The ABI suffix of the triple chooses the ARM float ABI, e.g. `armv7-unknown-linux-gnueabihf`
+/// to `armv7hl`. The names not listed are used as they are.
+fn rpm_arch(triple: &str) -> &str {
+    let (arch, abi) = match triple.split_once('-') {
+        Some((arch, rest)) => (arch, rest.rsplit('-').next()),
+        None => (triple, None),
+    };
+    let hard_float = abi.map(|v| v.ends_with("eabihf"));
+
+    match (arch, hard_float) {
+        ("x86", _) => "i586",
+        ("x86_64h", _) => "x86_64",
+        // ARM version is unknown without the triple
+        ("arm", None) => "armhfp",
+        ("arm" | "armv6", Some(true)) => "armv6hl",
+        ("arm" | "armv6", Some(false)) => "armv6l",
+        ("armv4t", _) => "armv4tl",
+        ("armv5te", _) => "armv5tel",
+        ("armv7" | "armv7a", Some(true)) => "armv7hl",
+        ("armv7" | "armv7a", _) => "armv7l",
+        ("thumbv7neon", _) => "armv7hnl",
+        ("powerpc", _) => "ppc",
+        ("powerpc64", _) => "ppc64",
+        ("powerpc64le", _) => "ppc64le",
+        ("riscv32gc", _) => "riscv32",
+        ("riscv64gc" | "riscv64a23", _) => "riscv64",
+        ("mipsisa32r6", _) => "mipsr6",
+        ("mipsisa32r6el", _) => "mipsr6el",
+        ("mipsisa64r6", _) => "mips64r6",
+        ("mipsisa64r6el", _) => "mips64r6el",
+        _ => arch,
+    }
+}
+
+/// Returns `$CARGO_HOME`, which defaults to `~/.cargo`.
+pub(crate) fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|v| v.join(".cargo")))
+}
+
+/// Build settings read from the cargo configuration files.
+///
+/// See <https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure>.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct CargoConfig {
+    target: Option<String>,
+    target_dir: Option<PathBuf>,
+}
+
+impl CargoConfig {
+    /// Reads the configuration files in the `.cargo` directories of `dir` and its ancestors, then
+    /// in `cargo_home`. The deeper file takes precedence over the others.
+    fn load(dir: &Path, cargo_home: Option<&Path>) -> Result<Self, Error> {
+        let mut cargo_dirs = dir
+            .ancestors()
+            .map(|v| v.join(".cargo"))
+            .collect::<Vec<_>>();
+        if let Some(cargo_home) = cargo_home
+            && !cargo_dirs.iter().any(|v| v == cargo_home)
+        {
+            cargo_dirs.push(cargo_home.to_path_buf());
+        }
+
+        let mut config = Self::default();
+        for cargo_dir in cargo_dirs {
+            // cargo reads the legacy `config` file in preference to `config.toml`
+            let Some(path) = ["config", "config.toml"]
+                .into_iter()
+                .map(|v| cargo_dir.join(v))
+                .find(|v| v.is_file())
+            else {
+                continue;
+            };
+            let text = fs::read_to_string(&path).map_err(|err| Error::FileIo(path.clone(), err))?;
+            let table = toml::from_str::<Table>(&text)
+                .map_err(|err| FileAnnotatedError(Some(path.clone()), err))?;
+            // paths are relative to the parent directory of the `.cargo` directory
+            let base = cargo_dir.parent().unwrap_or(&cargo_dir);
+            config
+                .merge(&table, base)
+                .map_err(|err| FileAnnotatedError(Some(path.clone()), err))?;
+        }
+        Ok(config)
+    }
+
+    /// Fills the settings not set yet with the values of `table`.
+    fn merge(&mut self, table: &Table, base: &Path) -> Result<(), ConfigError> {
+        let Some(build) = table.get("build") else {
+            return Ok(());
+        };
+        let build = build
+            .as_table()
+            .ok_or(ConfigError::WrongType("build".to_string(), "table"))?;
+
+        if self.target.is_none()
+            && let Some(target) = build.get("target")
+        {
+            // only the first one is packaged when multiple targets are specified
+            let target = match target {
+                Value::String(v) => Some(v.as_str()),
+                Value::Array(v) => v.first().and_then(Value::as_str),
+                _ => None,
+            };
+            let target = target.ok_or(ConfigError::WrongType(
+                "build.target".to_string(),
+                "string or array of string",
+            ))?;
+            self.target = Some(target.to_string());
+        }
+        if self.target_dir.is_none()
+            && let Some(target_dir) = build.get("target-dir")
+        {
+            let target_dir = target_dir.as_str().ok_or(ConfigError::WrongType(
+                "build.target-dir".to_string(),
+                "string",
+            ))?;
+            self.target_dir = Some(base.join(target_dir));
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -74,11 +260,11 @@ mod test {
     #[test]
     fn test_build_target_path() {
         let args = crate::cli::Cli::default();
-        let target = BuildTarget::new(&args);
+        let target = BuildTarget::from_args(&args);
         assert_eq!(target.build_target_path(), PathBuf::from("target"));
 
         let target = BuildTarget {
-            target_dir: Some("/tmp/foobar/target".to_string()),
+            target_dir: Some(PathBuf::from("/tmp/foobar/target")),
             ..target
         };
         assert_eq!(
@@ -90,7 +276,7 @@ mod test {
     #[test]
     fn test_target_path() {
         let args = crate::cli::Cli::default();
-        let default_target = BuildTarget::new(&args);
+        let default_target = BuildTarget::from_args(&args);
         assert_eq!(
             default_target.target_path("release"),
             PathBuf::from("target/release")
@@ -106,7 +292,7 @@ mod test {
         );
 
         let target = BuildTarget {
-            target_dir: Some("/tmp/foobar/target".to_string()),
+            target_dir: Some(PathBuf::from("/tmp/foobar/target")),
             ..default_target.clone()
         };
         assert_eq!(
@@ -115,7 +301,7 @@ mod test {
         );
 
         let target = BuildTarget {
-            target_dir: Some("/tmp/foobar/target".to_string()),
+            target_dir: Some(PathBuf::from("/tmp/foobar/target")),
             target: Some("x86_64-unknown-linux-gnu".to_string()),
             ..default_target
         };
@@ -124,4 +310,141 @@ mod test {
             PathBuf::from("/tmp/foobar/target/x86_64-unknown-linux-gnu/debug")
         );
     }
+
+    #[test]
+    fn test_with_defaults() {
+        let args = crate::cli::Cli::default();
+        let cargo_config = CargoConfig {
+            target: Some("aarch64-unknown-linux-gnu".to_string()),
+            target_dir: Some(PathBuf::from("/tmp/config")),
+        };
+
+        let target = BuildTarget::from_args(&args).with_defaults(CargoConfig::default(), |_| None);
+        assert_eq!(target.target(), None);
+        assert_eq!(target.target_dir(), None);
+
+        let target = BuildTarget::from_args(&args).with_defaults(cargo_config.clone(), |_| None);
+        assert_eq!(target.target(), Some("aarch64-unknown-linux-gnu"));
+        assert_eq!(target.target_dir(), Some(Path::new("/tmp/config")));
+
+        let env = |name: &str| match name {
+            "CARGO_BUILD_TARGET" => Some(OsString::from("x86_64-unknown-linux-gnu")),
+            "CARGO_TARGET_DIR" => Some(OsString::from("/tmp/env")),
+            _ => None,
+        };
+        let target = BuildTarget::from_args(&args).with_defaults(cargo_config.clone(), env);
+        assert_eq!(target.target(), Some("x86_64-unknown-linux-gnu"));
+        assert_eq!(target.target_dir(), Some(Path::new("/tmp/env")));
+
+        let args = crate::cli::Cli {
+            target: Some("i686-unknown-linux-gnu".to_string()),
+            target_dir: Some("/tmp/args".to_string()),
+            ..Default::default()
+        };
+        let target = BuildTarget::from_args(&args).with_defaults(cargo_config, env);
+        assert_eq!(target.target(), Some("i686-unknown-linux-gnu"));
+        assert_eq!(target.target_dir(), Some(Path::new("/tmp/args")));
+    }
+
+    #[test]
+    fn test_target_name() {
+        let args = crate::cli::Cli::default();
+        let target = BuildTarget {
+            target: Some("/tmp/foobar/thumbv7em-custom-none.json".to_string()),
+            arch: None,
+            ..BuildTarget::from_args(&args)
+        };
+        assert_eq!(target.target_name(), Some("thumbv7em-custom-none"));
+        assert_eq!(target.binary_arch(), "thumbv7em");
+
+        let target = BuildTarget {
+            profile: "dev".to_string(),
+            ..target
+        };
+        assert_eq!(target.profile_dir_name(), "debug");
+    }
+
+    #[test]
+    fn test_rpm_arch() {
+        for (triple, arch) in [
+            ("x86_64-unknown-linux-gnu", "x86_64"),
+            ("x86_64-unknown-linux-musl", "x86_64"),
+            ("i586-unknown-linux-gnu", "i586"),
+            ("i686-unknown-linux-gnu", "i686"),
+            ("aarch64-unknown-linux-gnu", "aarch64"),
+            ("arm-unknown-linux-gnueabi", "armv6l"),
+            ("arm-unknown-linux-gnueabihf", "armv6hl"),
+            ("armv5te-unknown-linux-gnueabi", "armv5tel"),
+            ("armv7-unknown-linux-gnueabi", "armv7l"),
+            ("armv7-unknown-linux-gnueabihf", "armv7hl"),
+            ("armv7-unknown-linux-musleabihf", "armv7hl"),
+            ("thumbv7neon-unknown-linux-gnueabihf", "armv7hnl"),
+            ("powerpc-unknown-linux-gnu", "ppc"),
+            ("powerpc64-unknown-linux-gnu", "ppc64"),
+            ("powerpc64le-unknown-linux-gnu", "ppc64le"),
+            ("riscv64gc-unknown-linux-gnu", "riscv64"),
+            ("loongarch64-unknown-linux-gnu", "loongarch64"),
+            ("s390x-unknown-linux-gnu", "s390x"),
+            ("sparc64-unknown-linux-gnu", "sparc64"),
+            ("mips64el-unknown-linux-gnuabi64", "mips64el"),
+            ("mipsisa64r6el-unknown-linux-gnuabi64", "mips64r6el"),
+            ("x86", "i586"),
+            ("arm", "armhfp"),
+        ] {
+            assert_eq!(rpm_arch(triple), arch, "{triple}");
+        }
+    }
+
+    #[test]
+    fn test_cargo_config_load() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let project = tempdir.path().join("foo/bar");
+        let cargo_home = tempdir.path().join("home/.cargo");
+        fs::create_dir_all(project.join(".cargo")).unwrap();
+        fs::create_dir_all(tempdir.path().join("foo/.cargo")).unwrap();
+        fs::create_dir_all(&cargo_home).unwrap();
+
+        assert_eq!(
+            CargoConfig::load(&project, Some(&cargo_home)).unwrap(),
+            CargoConfig::default()
+        );
+
+        fs::write(
+            cargo_home.join("config.toml"),
+            "build.target = \"aarch64-unknown-linux-gnu\"\nbuild.target-dir = \"/tmp/baz\"\n",
+        )
+        .unwrap();
+        fs::write(
+            tempdir.path().join("foo/.cargo/config.toml"),
+            "[build]\ntarget-dir = \"out\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            CargoConfig::load(&project, Some(&cargo_home)).unwrap(),
+            CargoConfig {
+                target: Some("aarch64-unknown-linux-gnu".to_string()),
+                target_dir: Some(tempdir.path().join("foo/out")),
+            }
+        );
+
+        fs::write(
+            project.join(".cargo/config.toml"),
+            "[build]\ntarget = [\"x86_64-unknown-linux-gnu\", \"i686-unknown-linux-gnu\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            CargoConfig::load(&project, Some(&cargo_home)).unwrap(),
+            CargoConfig {
+                target: Some("x86_64-unknown-linux-gnu".to_string()),
+                target_dir: Some(tempdir.path().join("foo/out")),
+            }
+        );
+
+        fs::write(project.join(".cargo/config.toml"), "build.target = 1\n").unwrap();
+        assert!(matches!(
+            CargoConfig::load(&project, Some(&cargo_home)),
+            Err(Error::ExtraConfig(FileAnnotatedError(Some(_), ConfigError::WrongType(v, _))))
+                if v == "build.target"
+        ));
+    }
 }
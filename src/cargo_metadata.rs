@@ -0,0 +1,338 @@
+//! Resolution of the packages and the workspace from the output of `cargo metadata`.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde_json::Value;
+
+use crate::error::{CargoMetadataError, Error};
+
+/// Member of the workspace.
+#[derive(Debug)]
+pub(crate) struct MetadataPackage {
+    pub(crate) name: String,
+    pub(crate) manifest_path: PathBuf,
+    has_generate_rpm: bool,
+}
+
+impl MetadataPackage {
+    /// Returns the directory of the package.
+    pub(crate) fn dir(&self) -> &Path {
+        self.manifest_path.parent().unwrap_or(Path::new(""))
+    }
+}
+
+/// Workspace described by `cargo metadata --no-deps`.
+#[derive(Debug)]
+pub(crate) struct CargoMetadata {
+    pub(crate) workspace_root: PathBuf,
+    pub(crate) target_directory: PathBuf,
+    packages: Vec<MetadataPackage>,
+}
+
+impl CargoMetadata {
+    /// Runs `cargo metadata` for the manifest at `manifest_path`, or the one of the current
+    /// directory if `None`.
+    pub(crate) fn load(manifest_path: Option<&Path>) -> Result<Self, Error> {
+        let cargo = std::env::var_os("CARGO").unwrap_or("cargo".into());
+        let mut command = Command::new(cargo);
+        command.args(["metadata", "--format-version", "1", "--no-deps"]);
+        if let Some(manifest_path) = manifest_path {
+            command.arg("--manifest-path").arg(manifest_path);
+        }
+        let output = command
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(CargoMetadataError::Process)?;
+        if !output.status.success() {
+            Err(CargoMetadataError::Failed(output.status.to_string()))?;
+        }
+        Ok(Self::parse(&output.stdout)?)
+    }
+
+    fn parse(json: &[u8]) -> Result<Self, CargoMetadataError> {
+        let metadata = serde_json::from_slice::<Value>(json).map_err(CargoMetadataError::Parse)?;
+        let path = |key: &str| {
+            metadata[key]
+                .as_str()
+                .map(PathBuf::from)
+                .ok_or(CargoMetadataError::Missing(key.to_string()))
+        };
+        let workspace_root = path("workspace_root")?;
+        let target_directory = path("target_directory")?;
+
+        let members = metadata["workspace_members"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut packages = Vec::new();
+        for package in metadata["packages"].as_array().into_iter().flatten() {
+            if !members.contains(&package["id"]) {
+                continue;
+            }
+            let (Some(name), Some(manifest_path)) =
+                (package["name"].as_str(), package["manifest_path"].as_str())
+            else {
+                return Err(CargoMetadataError::Missing("packages".to_string()));
+            };
+            packages.push(MetadataPackage {
+                name: name.to_string(),
+                manifest_path: PathBuf::from(manifest_path),
+                has_generate_rpm: !package["metadata"]["generate-rpm"].is_null(),
+            });
+        }
+
+        Ok(Self {
+            workspace_root,
+            target_directory,
+            packages,
+        })
+    }
+
+    /// Returns the member named `name`.
+    pub(crate) fn package(&self, name: &str) -> Result<&MetadataPackage, CargoMetadataError> {
+        self.packages
+            .iter()
+            .find(|v| v.name == name)
+            .ok_or(CargoMetadataError::PackageNotFound(name.to_string()))
+    }
+
+    /// Returns the members having `[package.metadata.generate-rpm]`.
+    pub(crate) fn packages_with_metadata(&self) -> Vec<&MetadataPackage> {
+        self.packages
+            .iter()
+            .filter(|v| v.has_generate_rpm)
+            .collect()
+    }
+}
+
+/// Returns the name and the version of the packages linked into the binaries of the package at
+/// `manifest_path` when built for the `target` with the features selected by `feature_args`,
+/// resolved by `cargo metadata`.
+///
+/// The build and dev dependencies and the proc-macro crates with their dependencies are not
+/// linked, and neither are the dependencies for the other platforms.
+pub(crate) fn linked_packages(
+    manifest_path: &Path,
+    target: Option<&str>,
+    feature_args: &[String],
+    locked: bool,
+) -> Result<Vec<(String, String)>, Error> {
+    let cargo = std::env::var_os("CARGO").unwrap_or("cargo".into());
+    let target = match target {
+        Some(target) => target.to_string(),
+        None => host_triple(&cargo)?,
+    };
+    let mut command = Command::new(cargo);
+    command
+        .args(["metadata", "--format-version", "1", "--filter-platform"])
+        .arg(target)
+        .arg("--manifest-path")
+        .arg(manifest_path)
+        .args(feature_args);
+    if locked {
+        command.arg("--locked");
+    }
+    let output = command
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(CargoMetadataError::Process)?;
+    if !output.status.success() {
+        Err(CargoMetadataError::Failed(output.status.to_string()))?;
+    }
+    Ok(parse_linked_packages(&output.stdout)?)
+}
+
+/// Returns the host triple reported by `cargo -vV`.
+fn host_triple(cargo: &std::ffi::OsStr) -> Result<String, CargoMetadataError> {
+    let output = Command::new(cargo)
+        .arg("-vV")
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(CargoMetadataError::Process)?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|v| v.strip_prefix("host:"))
+        .map(|v| v.trim().to_string())
+        .ok_or(CargoMetadataError::Missing("host".to_string()))
+}
+
+/// Returns the elements of the array `values` by their `id`.
+fn index_by_id(values: &Value) -> HashMap<&str, &Value> {
+    values
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|v| Some((v["id"].as_str()?, v)))
+        .collect()
+}
+
+fn parse_linked_packages(json: &[u8]) -> Result<Vec<(String, String)>, CargoMetadataError> {
+    let metadata = serde_json::from_slice::<Value>(json).map_err(CargoMetadataError::Parse)?;
+    let root = metadata["resolve"]["root"]
+        .as_str()
+        .ok_or(CargoMetadataError::Missing("resolve.root".to_string()))?;
+    let packages = index_by_id(&metadata["packages"]);
+    let nodes = index_by_id(&metadata["resolve"]["nodes"]);
+    let is_proc_macro = |id: &str| {
+        packages.get(id).is_some_and(|v| {
+            v["targets"].as_array().into_iter().flatten().any(|v| {
+                v["kind"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .any(|v| v == "proc-macro")
+            })
+        })
+    };
+
+    let mut visited = HashSet::from([root]);
+    let mut queue = vec![root];
+    while let Some(id) = queue.pop() {
+        for dep in nodes
+            .get(id)
+            .and_then(|v| v["deps"].as_array())
+            .into_iter()
+            .flatten()
+        {
+            // `kind` is null for the normal dependencies
+            let is_normal = dep["dep_kinds"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|v| v["kind"].is_null());
+            let Some(dep_id) = dep["pkg"].as_str() else {
+                continue;
+            };
+            if is_normal && !is_proc_macro(dep_id) && visited.insert(dep_id) {
+                queue.push(dep_id);
+            }
+        }
+    }
+
+    let mut linked = visited
+        .into_iter()
+        .filter(|id| *id != root)
+        .map(|id| {
+            let package = packages
+                .get(id)
+                .ok_or(CargoMetadataError::Missing(id.to_string()))?;
+            match (package["name"].as_str(), package["version"].as_str()) {
+                (Some(name), Some(version)) => Ok((name.to_string(), version.to_string())),
+                _ => Err(CargoMetadataError::Missing("packages".to_string())),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    linked.sort();
+    Ok(linked)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let json = r#"{
+            "packages": [
+                {
+                    "name": "foo",
+                    "id": "path+file:///ws/foo#0.1.0",
+                    "manifest_path": "/ws/foo/Cargo.toml",
+                    "metadata": { "generate-rpm": { "assets": [] } }
+                },
+                {
+                    "name": "bar",
+                    "id": "path+file:///ws/crates/bar#0.1.0",
+                    "manifest_path": "/ws/crates/bar/Cargo.toml",
+                    "metadata": null
+                }
+            ],
+            "workspace_members": ["path+file:///ws/foo#0.1.0", "path+file:///ws/crates/bar#0.1.0"],
+            "workspace_root": "/ws",
+            "target_directory": "/ws/target",
+            "metadata": null,
+            "version": 1
+        }"#;
+        let metadata = CargoMetadata::parse(json.as_bytes()).unwrap();
+        assert_eq!(metadata.workspace_root, Path::new("/ws"));
+        assert_eq!(metadata.target_directory, Path::new("/ws/target"));
+        assert_eq!(
+            metadata.package("bar").unwrap().dir(),
+            Path::new("/ws/crates/bar")
+        );
+        assert!(matches!(
+            metadata.package("baz"),
+            Err(CargoMetadataError::PackageNotFound(v)) if v == "baz"
+        ));
+        let packages = metadata.packages_with_metadata();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name, "foo");
+
+        assert!(matches!(
+            CargoMetadata::parse(b"{}"),
+            Err(CargoMetadataError::Missing(v)) if v == "workspace_root"
+        ));
+    }
+
+    #[test]
+    fn test_parse_linked_packages() {
+        let json = r#"{
+            "packages": [
+                { "id": "foo", "name": "foo", "version": "0.1.0", "targets": [{ "kind": ["bin"] }] },
+                { "id": "serde", "name": "serde", "version": "1.0.200", "targets": [{ "kind": ["lib"] }] },
+                { "id": "serde_derive", "name": "serde_derive", "version": "1.0.200", "targets": [{ "kind": ["proc-macro"] }] },
+                { "id": "syn", "name": "syn", "version": "2.0.60", "targets": [{ "kind": ["lib"] }] },
+                { "id": "cc", "name": "cc", "version": "1.0.90", "targets": [{ "kind": ["lib"] }] },
+                { "id": "tempfile", "name": "tempfile", "version": "3.10.1", "targets": [{ "kind": ["lib"] }] },
+                { "id": "libc", "name": "libc", "version": "0.2.150", "targets": [{ "kind": ["lib"] }] }
+            ],
+            "resolve": {
+                "root": "foo",
+                "nodes": [
+                    { "id": "foo", "deps": [
+                        { "pkg": "serde", "dep_kinds": [{ "kind": null, "target": null }] },
+                        { "pkg": "cc", "dep_kinds": [{ "kind": "build", "target": null }] },
+                        { "pkg": "tempfile", "dep_kinds": [{ "kind": "dev", "target": null }] }
+                    ] },
+                    { "id": "serde", "deps": [
+                        { "pkg": "serde_derive", "dep_kinds": [{ "kind": null, "target": null }] }
+                    ] },
+                    { "id": "serde_derive", "deps": [
+                        { "pkg": "syn", "dep_kinds": [{ "kind": null, "target": null }] }
+                    ] },
+                    { "id": "tempfile", "deps": [
+                        { "pkg": "libc", "dep_kinds": [{ "kind": null, "target": null }] }
+                    ] },
+                    { "id": "cc", "deps": [
+                        { "pkg": "libc", "dep_kinds": [{ "kind": null, "target": null }] }
+                    ] }
+                ]
+            }
+        }"#;
+        assert_eq!(
+            parse_linked_packages(json.as_bytes()).unwrap(),
+            vec![("serde".to_string(), "1.0.200".to_string())]
+        );
+
+        assert!(matches!(
+            parse_linked_packages(br#"{ "resolve": null }"#),
+            Err(CargoMetadataError::Missing(v)) if v == "resolve.root"
+        ));
+    }
+
+    #[test]
+    fn test_load() {
+        let metadata = CargoMetadata::load(None).unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(metadata.workspace_root, cwd);
+        assert_eq!(
+            metadata
+                .package("cargo-generate-rpm")
+                .unwrap()
+                .manifest_path,
+            cwd.join("Cargo.toml")
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use clap::{
-    Arg, ArgMatches, Command, CommandFactory, FromArgMatches, Parser, ValueEnum,
+    Arg, ArgMatches, Args, Command, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
     builder::{PathBufValueParser, PossibleValuesParser, TypedValueParser, ValueParserFactory},
 };
 use std::ffi::{OsStr, OsString};
@@ -14,11 +14,14 @@ enum CargoWrapper {
 }
 
 /// Arguments of the command line interface
-#[derive(Debug, Parser)]
+#[derive(Clone, Debug, Parser)]
 #[command(name = "cargo-generate-rpm")]
 #[command(bin_name = "cargo-generate-rpm")]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+
     /// Target arch of generated package.
     #[arg(short, long)]
     pub arch: Option<String>,
@@ -28,32 +31,53 @@ pub struct Cli {
     pub output: Option<PathBuf>,
 
     /// Name of a crate in the workspace for which
-    /// RPM package will be generated.
+    /// RPM package will be generated. May be specified
+    /// multiple times.
     #[arg(short, long)]
-    pub package: Option<String>,
+    pub package: Vec<String>,
+
+    /// Generate the RPM packages of all the members of the
+    /// workspace having [package.metadata.generate-rpm].
+    #[arg(long, conflicts_with = "package")]
+    pub workspace: bool,
 
     /// Automatic dependency processing mode.
     #[arg(long, default_value = "auto",
         help = "Automatic dependency processing mode. \
-        [possible values: auto, disabled, builtin, find-requires, /path/to/find-requires]",
+        [possible values: auto, disabled, builtin, elf, find-requires, /path/to/find-requires]",
         long_help = color_print::cstr!("Automatic dependency processing mode.\n\n\
         Possible values:\n\
         - <bold>auto</bold>:                   Use the preferred automatic dependency process.\n\
         - <bold>disabled</bold>:               Disable the discovery of dependencies. [alias: no]\n\
         - <bold>builtin</bold>:                Use the builtin procedure based on ldd.\n\
+        - <bold>elf</bold>:                    Use the builtin procedure reading the ELF dynamic section.\n\
         - <bold>find-requires</bold>:          Use /usr/lib/rpm/find-requires.\n\
         - <bold>/path/to/find-requires</bold>: Use the specified external program."))]
     pub auto_req: AutoReqMode,
 
+    /// Automatic provides processing mode.
+    #[arg(long, default_value = "auto",
+        help = "Automatic provides processing mode. \
+        [possible values: auto, disabled, builtin, find-provides, /path/to/find-provides]",
+        long_help = color_print::cstr!("Automatic provides processing mode.\n\n\
+        Possible values:\n\
+        - <bold>auto</bold>:                   Use the preferred automatic provides process.\n\
+        - <bold>disabled</bold>:               Disable the discovery of provides. [alias: no]\n\
+        - <bold>builtin</bold>:                Use the builtin procedure reading the ELF dynamic section.\n\
+        - <bold>find-provides</bold>:          Use /usr/lib/rpm/find-provides.\n\
+        - <bold>/path/to/find-provides</bold>: Use the specified external program."))]
+    pub auto_prov: AutoProvMode,
+
     /// Sub-directory name for all generated artifacts. May be
     /// specified with CARGO_BUILD_TARGET environment
-    /// variable.
+    /// variable or `build.target` of .cargo/config.toml.
     #[arg(long)]
     pub target: Option<String>,
 
     /// Directory for all generated artifacts. May be
     /// specified with CARGO_BUILD_TARGET_DIR or
-    /// CARGO_TARGET_DIR environment variables.
+    /// CARGO_TARGET_DIR environment variables or
+    /// `build.target-dir` of .cargo/config.toml.
     #[arg(long)]
     pub target_dir: Option<String>,
 
@@ -90,6 +114,55 @@ pub struct Cli {
     /// Path to a PGP private key file for signing the built RPM package
     #[arg(long)]
     pub signing_key: Option<PathBuf>,
+
+    /// Print the resolved package plan instead of writing the RPM package file.
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "text")]
+    pub dry_run: Option<OutputFormat>,
+
+    /// Generate a source RPM package containing the spec file and the vendored source tarball
+    /// instead of the binary RPM packages.
+    #[arg(long, conflicts_with = "dry_run")]
+    pub srpm: bool,
+
+    /// Run `cargo build` with the profile, the target and the package before packaging,
+    /// and fail if an asset in the target directory is older than the build output.
+    #[arg(long, conflicts_with = "srpm")]
+    pub build: bool,
+
+    /// Space or comma separated list of features the package is built with, passed to
+    /// `cargo build` and used to resolve the crates linked into the binaries.
+    #[arg(long)]
+    pub features: Vec<String>,
+
+    /// Activate all the features of the package.
+    #[arg(long)]
+    pub all_features: bool,
+
+    /// Do not activate the `default` feature of the package.
+    #[arg(long)]
+    pub no_default_features: bool,
+
+    /// Pass `--locked` to cargo.
+    #[arg(long)]
+    pub locked: bool,
+}
+
+/// Subcommands of the command line interface
+#[derive(Clone, Debug, Subcommand)]
+pub enum Commands {
+    /// Print the header tags, dependencies, scriptlets and files of an existing RPM package.
+    Inspect(InspectArgs),
+}
+
+/// Arguments of the `inspect` subcommand
+#[derive(Clone, Debug, Args)]
+pub struct InspectArgs {
+    /// RPM package file to inspect.
+    pub file: PathBuf,
+
+    /// Print in JSON format.
+    #[arg(long)]
+    pub json: bool,
 }
 
 impl Cli {
@@ -159,6 +232,22 @@ impl Cli {
         extra_metadata_args.sort_by_key(|v| v.0);
         extra_metadata_args.drain(..).map(|v| v.1).collect()
     }
+
+    /// Returns the options of cargo selecting the features of the package.
+    pub fn feature_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for features in &self.features {
+            args.push("--features".to_string());
+            args.push(features.clone());
+        }
+        if self.all_features {
+            args.push("--all-features".to_string());
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".to_string());
+        }
+        args
+    }
 }
 
 impl Default for Cli {
@@ -167,6 +256,12 @@ impl Default for Cli {
     }
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
 #[derive(ValueEnum, Clone, Copy, Debug, Default)]
 pub enum Compression {
     None,
@@ -193,6 +288,7 @@ pub enum AutoReqMode {
     Auto,
     Disabled,
     Builtin,
+    Elf,
     FindRequires,
     Script(PathBuf),
 }
@@ -216,11 +312,12 @@ impl TypedValueParser for AutoReqModeParser {
         arg: Option<&Arg>,
         value: &OsStr,
     ) -> Result<Self::Value, clap::Error> {
-        const VALUES: [(&str, AutoReqMode); 5] = [
+        const VALUES: [(&str, AutoReqMode); 6] = [
             ("auto", AutoReqMode::Auto),
             ("disabled", AutoReqMode::Disabled),
             ("no", AutoReqMode::Disabled),
             ("builtin", AutoReqMode::Builtin),
+            ("elf", AutoReqMode::Elf),
             ("find-requires", AutoReqMode::FindRequires),
         ];
 
@@ -239,6 +336,57 @@ impl TypedValueParser for AutoReqModeParser {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AutoProvMode {
+    Auto,
+    Disabled,
+    Builtin,
+    FindProvides,
+    Script(PathBuf),
+}
+
+impl ValueParserFactory for AutoProvMode {
+    type Parser = AutoProvModeParser;
+
+    fn value_parser() -> Self::Parser {
+        AutoProvModeParser
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AutoProvModeParser;
+
+impl TypedValueParser for AutoProvModeParser {
+    type Value = AutoProvMode;
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        const VALUES: [(&str, AutoProvMode); 5] = [
+            ("auto", AutoProvMode::Auto),
+            ("disabled", AutoProvMode::Disabled),
+            ("no", AutoProvMode::Disabled),
+            ("builtin", AutoProvMode::Builtin),
+            ("find-provides", AutoProvMode::FindProvides),
+        ];
+
+        let inner = PossibleValuesParser::new(VALUES.iter().map(|(k, _v)| k));
+        match inner.parse_ref(cmd, arg, value) {
+            Ok(name) => Ok(VALUES.iter().find(|(k, _v)| name.eq(k)).unwrap().1.clone()),
+            Err(e) if e.kind() == clap::error::ErrorKind::InvalidValue => {
+                let inner = PathBufValueParser::new();
+                match inner.parse_ref(cmd, arg, value) {
+                    Ok(v) => Ok(AutoProvMode::Script(v)),
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ExtraMetadataSource {
     File(PathBuf, Option<String>),
@@ -249,6 +397,7 @@ pub enum ExtraMetadataSource {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
     #[test]
     fn verify_cli() {
         <Cli as CommandFactory>::command().debug_assert()
@@ -374,13 +523,96 @@ mod tests {
         assert_eq!(args.auto_req, AutoReqMode::Auto);
         let args = Cli::try_parse_from(["", "--auto-req", "builtin"]).unwrap();
         assert_eq!(args.auto_req, AutoReqMode::Builtin);
+        let args = Cli::try_parse_from(["", "--auto-req", "elf"]).unwrap();
+        assert_eq!(args.auto_req, AutoReqMode::Elf);
         let args = Cli::try_parse_from(["", "--auto-req", "find-requires"]).unwrap();
         assert_eq!(args.auto_req, AutoReqMode::FindRequires);
         let args = Cli::try_parse_from(["", "--auto-req", "/usr/lib/rpm/find-requires"]).unwrap();
         assert!(
-            matches!(args.auto_req, AutoReqMode::Script(v) if v == PathBuf::from("/usr/lib/rpm/find-requires"))
+            matches!(args.auto_req, AutoReqMode::Script(v) if v == Path::new("/usr/lib/rpm/find-requires"))
         );
         let args = Cli::try_parse_from(["", "--auto-req", "no"]).unwrap();
         assert_eq!(args.auto_req, AutoReqMode::Disabled);
     }
+
+    #[test]
+    fn test_auto_prov() {
+        let args = Cli::try_parse_from([""]).unwrap();
+        assert_eq!(args.auto_prov, AutoProvMode::Auto);
+        let args = Cli::try_parse_from(["", "--auto-prov", "auto"]).unwrap();
+        assert_eq!(args.auto_prov, AutoProvMode::Auto);
+        let args = Cli::try_parse_from(["", "--auto-prov", "builtin"]).unwrap();
+        assert_eq!(args.auto_prov, AutoProvMode::Builtin);
+        let args = Cli::try_parse_from(["", "--auto-prov", "find-provides"]).unwrap();
+        assert_eq!(args.auto_prov, AutoProvMode::FindProvides);
+        let args = Cli::try_parse_from(["", "--auto-prov", "/usr/lib/rpm/find-provides"]).unwrap();
+        assert!(
+            matches!(args.auto_prov, AutoProvMode::Script(v) if v == Path::new("/usr/lib/rpm/find-provides"))
+        );
+        let args = Cli::try_parse_from(["", "--auto-prov", "no"]).unwrap();
+        assert_eq!(args.auto_prov, AutoProvMode::Disabled);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let args = Cli::try_parse_from([""]).unwrap();
+        assert!(args.command.is_none());
+
+        let args = Cli::try_parse_from(["", "inspect", "foo.rpm"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Commands::Inspect(InspectArgs { file, json: false })) if file == Path::new("foo.rpm")
+        ));
+
+        let (args, _) = Cli::get_matches_and_try_parse_from(|| {
+            ["cargo", "generate-rpm", "inspect", "--json", "foo.rpm"]
+                .map(&OsString::from)
+                .into_iter()
+        })
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Commands::Inspect(InspectArgs { file, json: true })) if file == Path::new("foo.rpm")
+        ));
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let args = Cli::try_parse_from([""]).unwrap();
+        assert_eq!(args.dry_run, None);
+        let args = Cli::try_parse_from(["", "--dry-run"]).unwrap();
+        assert_eq!(args.dry_run, Some(OutputFormat::Text));
+        let args = Cli::try_parse_from(["", "--dry-run=json"]).unwrap();
+        assert_eq!(args.dry_run, Some(OutputFormat::Json));
+        assert!(Cli::try_parse_from(["", "--dry-run=yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_feature_args() {
+        let args = Cli::try_parse_from([""]).unwrap();
+        assert!(args.feature_args().is_empty());
+        let args = Cli::try_parse_from([
+            "",
+            "--features",
+            "foo,bar",
+            "--features",
+            "baz",
+            "--no-default-features",
+            "--locked",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.feature_args(),
+            [
+                "--features",
+                "foo,bar",
+                "--features",
+                "baz",
+                "--no-default-features"
+            ]
+        );
+        assert!(args.locked);
+        let args = Cli::try_parse_from(["", "--all-features"]).unwrap();
+        assert_eq!(args.feature_args(), ["--all-features"]);
+    }
 }
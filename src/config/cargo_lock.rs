@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use rpm::Dependency;
+use toml::value::Table;
+
+use crate::error::{ConfigError, Error};
+
+use super::version::VersionScheme;
+
+/// Package locked in `Cargo.lock`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct LockedPackage {
+    pub(super) name: String,
+    pub(super) version: String,
+    /// `None` for the local packages such as the workspace members
+    pub(super) source: Option<String>,
+}
+
+/// Returns the packages in the `Cargo.lock` found in `dir` or its parents.
+pub(super) fn locked_packages(dir: &Path) -> Result<Vec<LockedPackage>, Error> {
+    let lock_path = dir
+        .ancestors()
+        .map(|v| v.join("Cargo.lock"))
+        .find(|v| v.is_file())
+        .ok_or(ConfigError::Missing("Cargo.lock".to_string()))?;
+    let content =
+        std::fs::read_to_string(&lock_path).map_err(|err| Error::FileIo(lock_path.clone(), err))?;
+    let lock = content
+        .parse::<Table>()
+        .map_err(|_| ConfigError::WrongType(lock_path.display().to_string(), "TOML"))?;
+
+    let packages = lock
+        .get("package")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|package| {
+            let name = package.get("name")?.as_str()?;
+            let version = package.get("version")?.as_str()?;
+            Some(LockedPackage {
+                name: name.to_string(),
+                version: version.to_string(),
+                source: package
+                    .get("source")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string),
+            })
+        })
+        .collect();
+    Ok(packages)
+}
+
+/// Returns the non-local `packages` that are `linked` into the binaries, given as pairs of the
+/// name and the version.
+pub(super) fn bundled_packages(
+    packages: Vec<LockedPackage>,
+    linked: &[(String, String)],
+) -> Vec<LockedPackage> {
+    packages
+        .into_iter()
+        .filter(|v| v.source.is_some())
+        .filter(|v| {
+            linked
+                .iter()
+                .any(|(name, version)| *name == v.name && *version == v.version)
+        })
+        .collect()
+}
+
+/// Returns `bundled(crate(<name>)) = <version>` of the `packages`.
+pub(super) fn bundled_provides(packages: &[LockedPackage]) -> Vec<Dependency> {
+    packages
+        .iter()
+        .map(|v| {
+            let (version, _) = VersionScheme::Semver.translate(&v.version);
+            Dependency::eq(format!("bundled(crate({}))", v.name), version)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bundled_provides() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tempdir.path().join("crates/foo")).unwrap();
+        std::fs::write(
+            tempdir.path().join("Cargo.lock"),
+            r#"
+version = 4
+
+[[package]]
+name = "foo"
+version = "0.1.0"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "syn"
+version = "1.0.109"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "syn"
+version = "2.0.0-rc.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "tempfile"
+version = "3.10.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+
+        let packages = locked_packages(&tempdir.path().join("crates/foo")).unwrap();
+        assert_eq!(packages.len(), 5);
+        assert_eq!(packages[0].source, None);
+
+        let linked = [
+            ("foo", "0.1.0"),
+            ("serde", "1.0.200"),
+            ("syn", "2.0.0-rc.1"),
+        ]
+        .map(|(name, version)| (name.to_string(), version.to_string()));
+        let packages = bundled_packages(packages, &linked);
+        assert_eq!(packages.len(), 2);
+        assert_eq!(
+            bundled_provides(&packages),
+            vec![
+                Dependency::eq("bundled(crate(serde))", "1.0.200"),
+                Dependency::eq("bundled(crate(syn))", "2.0.0~rc.1"),
+            ]
+        );
+
+        let tempdir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            locked_packages(tempdir.path()),
+            Err(Error::Config(ConfigError::Missing(v))) if v == "Cargo.lock"
+        ));
+    }
+}
@@ -0,0 +1,421 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use toml::Value;
+
+use super::metadata::TomlValueHelper;
+use crate::error::{ChangelogError, ConfigError, Error};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Entry of `%changelog`, passed to `PackageBuilder::add_changelog_entry`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ChangelogEntry {
+    pub(super) name: String,
+    pub(super) text: String,
+    pub(super) time: u32,
+}
+
+/// Where the changelog entries are read from, configured with the `changelog` key.
+#[derive(Debug, PartialEq)]
+enum ChangelogSource<'a> {
+    /// Inline array of `{ date, author, text }` tables
+    Inline(&'a [Value]),
+    /// Tags of the local git repository
+    Git,
+    /// Keep-a-Changelog style markdown file
+    Markdown(&'a str),
+}
+
+impl<'a> ChangelogSource<'a> {
+    fn new<M: TomlValueHelper<'a>>(metadata: &M) -> Result<Option<Self>, ConfigError> {
+        match metadata.get_str("changelog") {
+            Ok(Some("git")) => Ok(Some(Self::Git)),
+            Ok(v) => Ok(v.map(Self::Markdown)),
+            Err(_) => metadata
+                .get_array("changelog")
+                .map(|v| v.map(Self::Inline))
+                .map_err(|_| ConfigError::WrongType("changelog".to_string(), "array or string")),
+        }
+    }
+}
+
+/// Returns the changelog entries, newest first.
+///
+/// `authors` are the authors of the package, the first of which is used as the name of the
+/// entries read from the markdown file unless `changelog-author` is specified.
+pub(super) fn changelog_entries<'a, M: TomlValueHelper<'a>>(
+    metadata: &M,
+    parent: &Path,
+    authors: &[String],
+) -> Result<Vec<ChangelogEntry>, Error> {
+    match ChangelogSource::new(metadata)? {
+        None => Ok(Vec::new()),
+        Some(ChangelogSource::Inline(entries)) => inline_entries(entries),
+        Some(ChangelogSource::Git) => {
+            let prefix = metadata
+                .get_str("changelog-tag-prefix")?
+                .unwrap_or_default();
+            git_entries(parent, prefix)
+        }
+        Some(ChangelogSource::Markdown(path)) => {
+            let author = match (metadata.get_str("changelog-author")?, authors.first()) {
+                (Some(v), _) => v,
+                (None, Some(v)) => v.as_str(),
+                (None, None) => Err(ConfigError::Missing("package.authors".to_string()))?,
+            };
+            let path = if Path::new(path).exists() {
+                PathBuf::from(path)
+            } else {
+                parent.join(path)
+            };
+            let content =
+                std::fs::read_to_string(&path).map_err(|err| Error::FileIo(path.clone(), err))?;
+            Ok(markdown_entries(&content, author)?)
+        }
+    }
+}
+
+fn inline_entries(entries: &[Value]) -> Result<Vec<ChangelogEntry>, Error> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(idx, entry)| {
+            let table = entry
+                .as_table()
+                .ok_or(ConfigError::WrongType(format!("changelog[{idx}]"), "table"))?;
+            let get_str = |key: &'static str| match table.get(key) {
+                Some(Value::String(v)) => Ok(v.as_str()),
+                Some(_) => Err(ConfigError::WrongType(
+                    format!("changelog[{idx}].{key}"),
+                    "string",
+                )),
+                None => Err(ConfigError::Missing(format!("changelog[{idx}].{key}"))),
+            };
+            let date = match table.get("date") {
+                Some(Value::Datetime(v)) => v.date.map(|v| v.to_string()).unwrap_or_default(),
+                _ => get_str("date")?.to_string(),
+            };
+            Ok::<_, Error>(ChangelogEntry {
+                name: get_str("author")?.to_string(),
+                text: get_str("text")?.trim_end().to_string(),
+                time: parse_date(&date)?,
+            })
+        })
+        .collect()
+}
+
+/// Parses the release sections of a Keep-a-Changelog style markdown such as
+/// `## [1.0.0] - 2024-01-02`. Sections without date such as `## [Unreleased]` are skipped.
+fn markdown_entries(content: &str, author: &str) -> Result<Vec<ChangelogEntry>, ChangelogError> {
+    let heading =
+        Regex::new(r"^##\s+\[?([^\]\s]+)\]?(?:\([^)]*\))?(?:\s+-\s+(\d{4}-\d{2}-\d{2}))?").unwrap();
+    let link_reference = Regex::new(r"^\[[^\]]+\]:\s").unwrap();
+
+    let mut entries = Vec::new();
+    let mut current: Option<(ChangelogEntry, Vec<String>)> = None;
+    for line in content.lines() {
+        if line.starts_with("## ") {
+            entries.extend(current.take().map(finish_markdown_entry));
+            if let Some(caps) = heading.captures(line)
+                && let Some(date) = caps.get(2)
+            {
+                let entry = ChangelogEntry {
+                    name: format!("{author} - {}", caps[1].trim_start_matches('v')),
+                    text: String::new(),
+                    time: parse_date(date.as_str())?,
+                };
+                current = Some((entry, Vec::new()));
+            }
+        } else if let Some((_, lines)) = current.as_mut() {
+            let line = line.trim_end();
+            if let Some(item) = line.strip_prefix("* ").or(line.strip_prefix("- ")) {
+                lines.push(format!("- {item}"));
+            } else if line.starts_with(' ') && !lines.is_empty() {
+                lines.push(line.to_string());
+            } else if link_reference.is_match(line) {
+                entries.extend(current.take().map(finish_markdown_entry));
+            }
+        }
+    }
+    entries.extend(current.map(finish_markdown_entry));
+    Ok(entries)
+}
+
+fn finish_markdown_entry((mut entry, lines): (ChangelogEntry, Vec<String>)) -> ChangelogEntry {
+    entry.text = lines.join("\n");
+    entry
+}
+
+/// Reads the tags starting with `prefix` of the git repository at `dir`, each of which has the
+/// subjects of the commits since the previous one. The version is the tag without `prefix`.
+fn git_entries(dir: &Path, prefix: &str) -> Result<Vec<ChangelogEntry>, Error> {
+    let tags = git(
+        dir,
+        &[
+            "for-each-ref",
+            "--sort=-v:refname",
+            "--sort=-creatordate",
+            "--format=%(refname:short)%09%(creatordate:unix)%09%(if)%(taggername)%(then)%(taggername) %(taggeremail)%(else)%(*authorname)%(authorname) %(*authoremail)%(authoremail)%(end)",
+            "refs/tags",
+        ],
+    )?;
+    let tags = tags
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            Some((fields.next()?, fields.next()?, fields.next()?))
+        })
+        .filter(|(tag, _, _)| tag.starts_with(prefix))
+        .collect::<Vec<_>>();
+
+    let mut entries = Vec::with_capacity(tags.len());
+    for (idx, (tag, time, author)) in tags.iter().enumerate() {
+        let range = match tags.get(idx + 1) {
+            Some((previous, _, _)) => format!("{previous}..{tag}"),
+            None => tag.to_string(),
+        };
+        let text = git(dir, &["log", "--no-merges", "--format=- %s", &range])?;
+        let version = tag.strip_prefix(prefix).unwrap_or(tag);
+        entries.push(ChangelogEntry {
+            name: format!("{author} - {}", version.trim_start_matches('v')),
+            text: text.trim_end().to_string(),
+            time: time.parse().unwrap_or_default(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Runs git in `dir` and returns its output.
+pub(super) fn git(dir: &Path, args: &[&str]) -> Result<String, ChangelogError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(ChangelogError::GitProcess)?;
+    if !output.status.success() {
+        return Err(ChangelogError::GitFailed(
+            args[0].to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Converts the date `YYYY-MM-DD` into the timestamp at noon UTC of the day, as rpmbuild does.
+fn parse_date(date: &str) -> Result<u32, ChangelogError> {
+    let invalid = || ChangelogError::InvalidDate(date.to_string());
+    let fields = date
+        .split('-')
+        .map(|v| v.parse::<i64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let [year, month, day] = fields.as_slice() else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(month) || !(1..=31).contains(day) {
+        return Err(invalid());
+    }
+    let time = days_from_civil(*year, *month, *day) * SECONDS_PER_DAY + SECONDS_PER_DAY / 2;
+    u32::try_from(time).map_err(|_| invalid())
+}
+
+/// Returns the number of days since 1970-01-01 of the date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Formats the timestamp in the form of `%changelog` such as `Tue Jan 02 2024`.
+pub(crate) fn format_date(time: u64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let days = time as i64 / SECONDS_PER_DAY;
+    // inverse of `days_from_civil`
+    let days_shifted = days + 719468;
+    let era = days_shifted.div_euclid(146097);
+    let day_of_era = days_shifted - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{} {} {day:02} {year}",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        MONTHS[month as usize - 1]
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::metadata::MetadataConfig;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-01").unwrap(), 43200);
+        assert_eq!(parse_date("2024-01-02").unwrap(), 1704196800);
+        assert_eq!(parse_date("2024-02-29").unwrap(), 1709208000);
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("2024/01/02").is_err());
+        assert!(parse_date("1969-12-31").is_err());
+
+        assert_eq!(format_date(1704196800), "Tue Jan 02 2024");
+        assert_eq!(format_date(1709208000), "Thu Feb 29 2024");
+        assert_eq!(format_date(0), "Thu Jan 01 1970");
+    }
+
+    #[test]
+    fn test_changelog_entries() {
+        let metadata = toml::toml! {
+            changelog = [
+                { date = "2024-01-02", author = "Foo <foo@example.com> - 1.0.0", text = "- Initial release\n" },
+                { date = 2023-12-01, author = "Foo <foo@example.com> - 0.9.0", text = "- Beta" },
+            ]
+        };
+        let metadata = MetadataConfig::new(&metadata, None);
+        let entries = changelog_entries(&metadata, Path::new(""), &[]).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ChangelogEntry {
+                    name: "Foo <foo@example.com> - 1.0.0".to_string(),
+                    text: "- Initial release".to_string(),
+                    time: 1704196800,
+                },
+                ChangelogEntry {
+                    name: "Foo <foo@example.com> - 0.9.0".to_string(),
+                    text: "- Beta".to_string(),
+                    time: 1701432000,
+                },
+            ]
+        );
+
+        let metadata = toml::toml! {
+            changelog = [{ date = "2024-01-02", text = "- Initial release" }]
+        };
+        let metadata = MetadataConfig::new(&metadata, None);
+        assert!(matches!(
+            changelog_entries(&metadata, Path::new(""), &[]),
+            Err(Error::Config(ConfigError::Missing(v))) if v == "changelog[0].author"
+        ));
+
+        let metadata = toml::toml! {
+            changelog = 1
+        };
+        let metadata = MetadataConfig::new(&metadata, None);
+        assert!(matches!(
+            changelog_entries(&metadata, Path::new(""), &[]),
+            Err(Error::Config(ConfigError::WrongType(v, _))) if v == "changelog"
+        ));
+
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tempdir.path().join("CHANGELOG.md"),
+            "## [1.0.0] - 2024-01-02\n- Initial release\n",
+        )
+        .unwrap();
+        let metadata = toml::toml! {
+            changelog = "CHANGELOG.md"
+        };
+        let metadata = MetadataConfig::new(&metadata, None);
+        let entries = changelog_entries(&metadata, tempdir.path(), &["Foo".to_string()]).unwrap();
+        assert_eq!(entries[0].name, "Foo - 1.0.0");
+        assert!(matches!(
+            changelog_entries(&metadata, tempdir.path(), &[]),
+            Err(Error::Config(ConfigError::Missing(v))) if v == "package.authors"
+        ));
+    }
+
+    #[test]
+    fn test_git_entries() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path();
+        let commit = |message: &str, tag: &str| {
+            for args in [
+                vec!["commit", "--allow-empty", "-m", message],
+                vec!["tag", "-a", "-m", tag, tag],
+            ] {
+                git(
+                    dir,
+                    &[
+                        &["-c", "user.name=Foo", "-c", "user.email=foo@example.com"],
+                        &["-c", "commit.gpgsign=false", "-c", "tag.gpgsign=false"],
+                        args.as_slice(),
+                    ]
+                    .concat(),
+                )
+                .unwrap();
+            }
+        };
+        git(dir, &["init", "-q"]).unwrap();
+        commit("Add foo", "foo-v1.0.0");
+        commit("Add bar", "bar-v0.1.0");
+        commit("Fix foo", "foo-v1.0.1");
+
+        let entries = git_entries(dir, "foo-").unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|v| (v.name.as_str(), v.text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Foo <foo@example.com> - 1.0.1", "- Fix foo\n- Add bar"),
+                ("Foo <foo@example.com> - 1.0.0", "- Add foo"),
+            ]
+        );
+        assert_eq!(git_entries(dir, "").unwrap().len(), 3);
+        assert!(git_entries(dir, "baz-").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_markdown_entries() {
+        let content = r#"# Changelog
+
+All notable changes to this project will be documented in this file.
+
+## [Unreleased]
+- Work in progress
+
+## [1.1.0](https://example.com/v1.1.0) - 2024-02-29
+### Added
+- Feature A
+  spanning two lines
+* Feature B
+
+### Fixed
+- Bug C
+
+## v1.0.0 - 2024-01-02
+- Initial release
+
+[1.1.0]: https://example.com/compare/v1.0.0...v1.1.0
+"#;
+        assert_eq!(
+            markdown_entries(content, "Foo <foo@example.com>").unwrap(),
+            vec![
+                ChangelogEntry {
+                    name: "Foo <foo@example.com> - 1.1.0".to_string(),
+                    text: "- Feature A\n  spanning two lines\n- Feature B\n- Bug C".to_string(),
+                    time: 1709208000,
+                },
+                ChangelogEntry {
+                    name: "Foo <foo@example.com> - 1.0.0".to_string(),
+                    text: "- Initial release".to_string(),
+                    time: 1704196800,
+                },
+            ]
+        );
+    }
+}
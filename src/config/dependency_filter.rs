@@ -0,0 +1,117 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+use super::metadata::TomlValueHelper;
+use crate::error::ConfigError;
+
+/// Filter of the automatically discovered dependencies.
+///
+/// It is configured with `{prefix}-include`, `{prefix}-exclude` and `{prefix}-exclude-from`
+/// keys, similar to `%__requires_exclude` and `%__requires_exclude_from` macros of rpmbuild.
+#[derive(Debug, Default)]
+pub(super) struct DependencyFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    exclude_from: Vec<Regex>,
+}
+
+impl DependencyFilter {
+    pub(super) fn new<'a, M: TomlValueHelper<'a>>(
+        metadata: &M,
+        prefix: &str,
+    ) -> Result<Self, ConfigError> {
+        Ok(Self {
+            include: Self::get_regex_array(metadata, format!("{prefix}-include"))?,
+            exclude: Self::get_regex_array(metadata, format!("{prefix}-exclude"))?,
+            exclude_from: Self::get_regex_array(metadata, format!("{prefix}-exclude-from"))?,
+        })
+    }
+
+    fn get_regex_array<'a, M: TomlValueHelper<'a>>(
+        metadata: &M,
+        name: String,
+    ) -> Result<Vec<Regex>, ConfigError> {
+        metadata
+            .get_array(name.as_str())?
+            .unwrap_or_default()
+            .iter()
+            .map(|v| match v {
+                Value::String(v) => {
+                    Regex::new(v).map_err(|e| ConfigError::InvalidRegex(name.clone(), e))
+                }
+                _ => Err(ConfigError::WrongType(name.clone(), "array of string")),
+            })
+            .collect()
+    }
+
+    /// Returns the source paths of the files whose destination is not excluded from the scan.
+    pub(super) fn scanned_files<'b>(&self, files: &'b [(PathBuf, String)]) -> Vec<&'b Path> {
+        files
+            .iter()
+            .filter(|(_, dest)| !self.exclude_from.iter().any(|r| r.is_match(dest)))
+            .map(|(source, _)| source.as_path())
+            .collect()
+    }
+
+    /// Returns `true` if the discovered dependency shall be added to the package.
+    pub(super) fn is_accepted(&self, dependency: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|r| r.is_match(dependency)))
+            && !self.exclude.iter().any(|r| r.is_match(dependency))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::metadata::MetadataConfig;
+    use super::*;
+    use toml::toml;
+    use toml::value::Table;
+
+    #[test]
+    fn test_dependency_filter() {
+        let metadata = Table::new();
+        let metadata_config = MetadataConfig::new(&metadata, None);
+        let filter = DependencyFilter::new(&metadata_config, "auto-req").unwrap();
+        assert!(filter.is_accepted("libfoo.so.1()(64bit)"));
+
+        let metadata = toml! {
+            auto-req-include = ["^lib"]
+            auto-req-exclude = ["^libfoo\\.so"]
+            auto-req-exclude-from = ["^/usr/lib/private/"]
+        };
+        let metadata_config = MetadataConfig::new(&metadata, None);
+        let filter = DependencyFilter::new(&metadata_config, "auto-req").unwrap();
+        assert!(filter.is_accepted("libc.so.6()(64bit)"));
+        assert!(!filter.is_accepted("libfoo.so.1()(64bit)"));
+        assert!(!filter.is_accepted("/bin/bash"));
+
+        let files = [
+            (PathBuf::from("a"), "/usr/bin/a".to_string()),
+            (PathBuf::from("b"), "/usr/lib/private/libb.so".to_string()),
+        ];
+        assert_eq!(filter.scanned_files(&files), vec![Path::new("a")]);
+
+        // other prefix is not affected
+        let filter = DependencyFilter::new(&metadata_config, "auto-prov").unwrap();
+        assert!(filter.is_accepted("libfoo.so.1()(64bit)"));
+
+        let metadata = toml! {
+            auto-req-exclude = ["("]
+        };
+        let metadata_config = MetadataConfig::new(&metadata, None);
+        assert!(matches!(
+            DependencyFilter::new(&metadata_config, "auto-req"),
+            Err(ConfigError::InvalidRegex(v, _)) if v == "auto-req-exclude"
+        ));
+
+        let metadata = toml! {
+            auto-req-exclude = [1]
+        };
+        let metadata_config = MetadataConfig::new(&metadata, None);
+        assert!(matches!(
+            DependencyFilter::new(&metadata_config, "auto-req"),
+            Err(ConfigError::WrongType(v, "array of string")) if v == "auto-req-exclude"
+        ));
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+/// Returns the description given in the metadata, read from the file if `value` is its path.
+pub(super) fn load_description_if_path(value: &str, parent: &Path) -> Result<String, Error> {
+    let path = [PathBuf::from(value), parent.join(value)]
+        .into_iter()
+        .find(|v| v.is_file());
+    match path {
+        Some(path) => std::fs::read_to_string(&path).map_err(|err| Error::FileIo(path, err)),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// Returns the first section of the README at `path`, used as the description unless given.
+///
+/// The README is optional, so it is ignored if missing or unreadable.
+pub(super) fn readme_description(path: &Path, parent: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(parent.join(path)).ok()?;
+    readme_section(&content)
+}
+
+/// Returns the first line of the `description`, used as the summary unless given.
+pub(super) fn first_line(description: &str) -> Option<&str> {
+    description.lines().map(str::trim).find(|v| !v.is_empty())
+}
+
+/// Returns the text of the markdown `content` before the first heading other than the title,
+/// omitting the badges and the images.
+fn readme_section(content: &str) -> Option<String> {
+    let mut lines = Vec::<&str>::new();
+    let mut has_title = false;
+    let mut in_code_block = false;
+    for line in content.lines().map(str::trim_end) {
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block {
+            lines.push(line);
+            continue;
+        }
+        // `Title` followed by `===` or `---` is a heading as well as `# Title`
+        let is_underline = (line.len() >= 3 && line.bytes().all(|v| v == b'='))
+            || (line.len() >= 3 && line.bytes().all(|v| v == b'-'));
+        let is_setext_heading = is_underline && lines.last().is_some_and(|v| !v.is_empty());
+        if line.starts_with('#') || is_setext_heading {
+            if is_setext_heading {
+                lines.pop();
+            }
+            if has_title || lines.iter().any(|v| !v.is_empty()) {
+                break;
+            }
+            has_title = true;
+            lines.clear();
+        } else if !(line.starts_with("[![") || line.starts_with("![")) {
+            lines.push(line);
+        }
+    }
+
+    let text = lines
+        .into_iter()
+        .skip_while(|v| v.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let text = text.trim_end();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_readme_section() {
+        let readme = r#"# foo
+
+[![CI](https://example.com/badge.svg)](https://example.com/)
+
+Foo is a tool
+to do foo.
+
+```sh
+# not a heading
+foo --bar
+```
+
+## Install
+
+cargo install foo
+"#;
+        assert_eq!(
+            readme_section(readme).unwrap(),
+            "Foo is a tool\nto do foo.\n\n```sh\n# not a heading\nfoo --bar\n```"
+        );
+
+        let readme = "foo\n===\n\nFoo is a tool.\n\nUsage\n-----\n\nfoo --bar\n";
+        assert_eq!(readme_section(readme).unwrap(), "Foo is a tool.");
+
+        assert_eq!(
+            readme_section("Foo is a tool.\n\n# Usage\n").unwrap(),
+            "Foo is a tool."
+        );
+        assert_eq!(readme_section("# foo\n\n## Usage\n\nfoo --bar\n"), None);
+    }
+
+    #[test]
+    fn test_load_description_if_path() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("DESCRIPTION"), "Foo is a tool.\n").unwrap();
+        assert_eq!(
+            load_description_if_path("DESCRIPTION", tempdir.path()).unwrap(),
+            "Foo is a tool.\n"
+        );
+        assert_eq!(
+            load_description_if_path("Foo is a tool.", tempdir.path()).unwrap(),
+            "Foo is a tool."
+        );
+        assert_eq!(
+            first_line("\n  Foo is a tool.\n  It does foo.\n"),
+            Some("Foo is a tool.")
+        );
+        assert_eq!(first_line("\n"), None);
+    }
+
+    #[test]
+    fn test_readme_description() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tempdir.path().join("README.md"),
+            "# foo\n\nFoo is a tool.\n",
+        )
+        .unwrap();
+        assert_eq!(
+            readme_description(Path::new("README.md"), tempdir.path()),
+            Some("Foo is a tool.".to_string())
+        );
+        assert_eq!(
+            readme_description(Path::new("MISSING.md"), tempdir.path()),
+            None
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use glob::glob;
+use glob::{Pattern, glob};
+use regex::Regex;
+use rpm::{FileFlags, FileVerifyFlags};
 use toml::value::Table;
 
 use crate::build_target::BuildTarget;
@@ -6,31 +8,76 @@ use crate::error::ConfigError;
 use std::path::{Path, PathBuf};
 use toml::Value;
 
+/// Type of the packaged file specified with `type` of the asset
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
+pub enum FileType<'a> {
+    /// Regular file read from `source`
+    #[default]
+    File,
+    /// Empty directory owned by the package
+    Dir,
+    /// Symbolic link pointing to `target`
+    Symlink(&'a str),
+    /// `%ghost` file, which is not included in the payload
+    Ghost,
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct FileInfo<'a, 'b, 'c, 'd, 'e> {
-    pub source: &'a str,
+    /// Source location of the file, which is `None` unless `file_type` is `FileType::File`
+    pub source: Option<&'a str>,
     pub dest: &'b str,
+    pub file_type: FileType<'a>,
     pub user: Option<&'c str>,
     pub group: Option<&'d str>,
     pub mode: Option<usize>,
     pub config: bool,
     pub config_noreplace: bool,
+    pub config_missingok: bool,
     pub doc: bool,
+    pub license: bool,
+    pub readme: bool,
+    pub artifact: bool,
     pub caps: Option<&'e str>,
+    pub verify: Option<FileVerifyFlags>,
+    /// Patterns of the paths relative to the base directory of the glob to be excluded
+    pub exclude: Vec<Pattern>,
+    /// Whether the subdirectories created by the glob are owned by the package
+    pub include_dirs: bool,
 }
 
+const VERIFY_ATTRIBUTES: [(&str, FileVerifyFlags); 10] = [
+    ("md5", FileVerifyFlags::FILEDIGEST),
+    ("filedigest", FileVerifyFlags::FILEDIGEST),
+    ("size", FileVerifyFlags::FILESIZE),
+    ("link", FileVerifyFlags::LINKTO),
+    ("user", FileVerifyFlags::USER),
+    ("group", FileVerifyFlags::GROUP),
+    ("mtime", FileVerifyFlags::MTIME),
+    ("mode", FileVerifyFlags::MODE),
+    ("rdev", FileVerifyFlags::RDEV),
+    ("caps", FileVerifyFlags::CAPS),
+];
+
 impl FileInfo<'_, '_, '_, '_, '_> {
-    pub fn new(assets: &[Value]) -> Result<Vec<FileInfo>, ConfigError> {
+    pub fn new(assets: &[Value]) -> Result<Vec<FileInfo<'_, '_, '_, '_, '_>>, ConfigError> {
         let mut files = Vec::with_capacity(assets.len());
         for (idx, value) in assets.iter().enumerate() {
             let table = value
                 .as_table()
                 .ok_or(ConfigError::AssetFileUndefined(idx, "source"))?;
-            let source = table
-                .get("source")
-                .ok_or(ConfigError::AssetFileUndefined(idx, "source"))?
-                .as_str()
-                .ok_or(ConfigError::AssetFileWrongType(idx, "source", "string"))?;
+            let file_type = Self::get_file_type(table, idx)?;
+            let source = if file_type == FileType::File {
+                Some(
+                    table
+                        .get("source")
+                        .ok_or(ConfigError::AssetFileUndefined(idx, "source"))?
+                        .as_str()
+                        .ok_or(ConfigError::AssetFileWrongType(idx, "source", "string"))?,
+                )
+            } else {
+                None
+            };
             let dest = table
                 .get("dest")
                 .ok_or(ConfigError::AssetFileUndefined(idx, "dest"))?
@@ -54,7 +101,7 @@ impl FileInfo<'_, '_, '_, '_, '_> {
             } else {
                 None
             };
-            let mode = Self::get_mode(table, source, idx)?;
+            let mode = Self::get_mode(table, source.unwrap_or_default(), file_type, idx)?;
             let caps = if let Some(caps) = table.get("caps") {
                 Some(
                     caps.as_str()
@@ -63,43 +110,175 @@ impl FileInfo<'_, '_, '_, '_, '_> {
             } else {
                 None
             };
-            let (config, config_noreplace, _config_missingok) = match table.get("config") {
-                Some(Value::Boolean(v)) => (*v, false, false),
-                Some(Value::String(v)) if v.eq("noreplace") => (false, true, false),
-                //Some(Value::String(v)) if v.eq("missingok") => (false, false, true),
-                None => (false, false, false),
-                _ => {
-                    return Err(ConfigError::AssetFileWrongType(
-                        idx,
-                        "config",
-                        "bool or \"noreplace\"",
-                    ));
-                } //_ => return Err(ConfigError::AssetFileWrongType(idx, "config", "bool or \"noreplace\" or \"missingok\"")),
-            };
-            let doc = if let Some(is_doc) = table.get("doc") {
-                is_doc
-                    .as_bool()
-                    .ok_or(ConfigError::AssetFileWrongType(idx, "doc", "bool"))?
-            } else {
-                false
-            };
+            let (config, config_noreplace, config_missingok) = Self::get_config(table, idx)?;
+            let doc = Self::get_bool(table, "doc", idx)?;
+            let license = Self::get_bool(table, "license", idx)?;
+            let readme = Self::get_bool(table, "readme", idx)?;
+            let artifact = Self::get_bool(table, "artifact", idx)?;
+            let verify = Self::get_verify(table, idx)?;
+            let exclude = Self::get_exclude(table, idx)?;
+            let include_dirs = Self::get_bool(table, "include-dirs", idx)?;
 
             files.push(FileInfo {
                 source,
                 dest,
+                file_type,
                 user,
                 group,
                 mode,
                 config,
                 config_noreplace,
+                config_missingok,
                 doc,
+                license,
+                readme,
+                artifact,
                 caps,
+                verify,
+                exclude,
+                include_dirs,
             });
         }
         Ok(files)
     }
 
-    fn get_mode(table: &Table, source: &str, idx: usize) -> Result<Option<usize>, ConfigError> {
+    fn get_bool(table: &Table, name: &'static str, idx: usize) -> Result<bool, ConfigError> {
+        table
+            .get(name)
+            .map(|v| {
+                v.as_bool()
+                    .ok_or(ConfigError::AssetFileWrongType(idx, name, "bool"))
+            })
+            .unwrap_or(Ok(false))
+    }
+
+    /// Returns whether the file is a configuration file, with `noreplace` and with `missingok`.
+    fn get_config(table: &Table, idx: usize) -> Result<(bool, bool, bool), ConfigError> {
+        let wrong_type = || {
+            ConfigError::AssetFileWrongType(
+                idx,
+                "config",
+                "bool, \"noreplace\", \"missingok\" or array of them",
+            )
+        };
+        let options = match table.get("config") {
+            None => return Ok((false, false, false)),
+            Some(Value::Boolean(v)) => return Ok((*v, false, false)),
+            Some(Value::String(v)) => vec![v.as_str()],
+            Some(Value::Array(v)) => v
+                .iter()
+                .map(|v| v.as_str().ok_or_else(wrong_type))
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(_) => return Err(wrong_type()),
+        };
+        let (mut noreplace, mut missingok) = (false, false);
+        for option in options {
+            match option {
+                "noreplace" => noreplace = true,
+                "missingok" => missingok = true,
+                _ => return Err(wrong_type()),
+            }
+        }
+        Ok((!noreplace, noreplace, missingok))
+    }
+
+    fn get_exclude(table: &Table, idx: usize) -> Result<Vec<Pattern>, ConfigError> {
+        let Some(exclude) = table.get("exclude") else {
+            return Ok(Vec::new());
+        };
+        exclude
+            .as_array()
+            .ok_or(ConfigError::AssetFileWrongType(
+                idx,
+                "exclude",
+                "array of glob patterns",
+            ))?
+            .iter()
+            .map(|v| {
+                let pattern = v.as_str().ok_or(ConfigError::AssetFileWrongType(
+                    idx,
+                    "exclude",
+                    "array of glob patterns",
+                ))?;
+                Pattern::new(pattern).map_err(|e| ConfigError::AssetGlobInvalid(idx, e.msg))
+            })
+            .collect()
+    }
+
+    /// Parses `verify` like `%verify` of the spec file, such as `["not", "md5", "size", "mtime"]`.
+    fn get_verify(table: &Table, idx: usize) -> Result<Option<FileVerifyFlags>, ConfigError> {
+        let wrong_type = || {
+            ConfigError::AssetFileWrongType(
+                idx,
+                "verify",
+                "array of \"not\" and file attributes such as \"md5\"",
+            )
+        };
+        let Some(verify) = table.get("verify") else {
+            return Ok(None);
+        };
+        let verify = verify
+            .as_array()
+            .ok_or_else(wrong_type)?
+            .iter()
+            .map(|v| v.as_str().ok_or_else(wrong_type))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (negated, attributes) = match verify.split_first() {
+            Some((&"not", rest)) => (true, rest),
+            _ => (false, verify.as_slice()),
+        };
+        let mut flags = FileVerifyFlags::empty();
+        for attribute in attributes {
+            let (_, flag) = VERIFY_ATTRIBUTES
+                .iter()
+                .find(|(name, _)| name == attribute)
+                .ok_or_else(wrong_type)?;
+            flags |= *flag;
+        }
+        Ok(Some(if negated {
+            FileVerifyFlags::all() - flags
+        } else {
+            flags
+        }))
+    }
+
+    fn get_file_type<'a>(table: &'a Table, idx: usize) -> Result<FileType<'a>, ConfigError> {
+        let file_type = match table.get("type") {
+            None => FileType::File,
+            Some(Value::String(v)) if v == "file" => FileType::File,
+            Some(Value::String(v)) if v == "dir" => FileType::Dir,
+            Some(Value::String(v)) if v == "ghost" => FileType::Ghost,
+            Some(Value::String(v)) if v == "symlink" => FileType::Symlink(
+                table
+                    .get("target")
+                    .ok_or(ConfigError::AssetFileUndefined(idx, "target"))?
+                    .as_str()
+                    .ok_or(ConfigError::AssetFileWrongType(idx, "target", "string"))?,
+            ),
+            _ => {
+                return Err(ConfigError::AssetFileWrongType(
+                    idx,
+                    "type",
+                    "\"file\", \"dir\", \"symlink\" or \"ghost\"",
+                ));
+            }
+        };
+        Ok(file_type)
+    }
+
+    fn get_mode(
+        table: &Table,
+        source: &str,
+        file_type: FileType,
+        idx: usize,
+    ) -> Result<Option<usize>, ConfigError> {
+        // the files not read from disk have no permissions to inherit
+        let default_mode = match file_type {
+            FileType::File => None,
+            FileType::Dir => Some(0o040755),
+            FileType::Symlink(_) => Some(0o120777),
+            FileType::Ghost => Some(0o100644),
+        };
         if let Some(mode) = table.get("mode") {
             let mode = mode
                 .as_str()
@@ -108,6 +287,8 @@ impl FileInfo<'_, '_, '_, '_, '_> {
                 .map_err(|_| ConfigError::AssetFileWrongType(idx, "mode", "oct-string"))?;
             let file_mode = if mode & 0o170000 != 0 {
                 None
+            } else if let Some(default_mode) = default_mode {
+                Some(default_mode & 0o170000)
             } else if source.ends_with('/') {
                 Some(0o040000) // S_IFDIR
             } else {
@@ -115,7 +296,7 @@ impl FileInfo<'_, '_, '_, '_, '_> {
             };
             Ok(Some(file_mode.unwrap_or_default() | mode))
         } else {
-            Ok(None)
+            Ok(default_mode)
         }
     }
 
@@ -124,16 +305,22 @@ impl FileInfo<'_, '_, '_, '_, '_> {
         build_target: &BuildTarget,
         parent: P,
         idx: usize,
-    ) -> Result<Vec<(PathBuf, String)>, ConfigError> {
-        let source = get_asset_rel_path(self.source, build_target);
+    ) -> Result<Vec<(Option<PathBuf>, String)>, ConfigError> {
+        let source = get_asset_rel_path(self.source.unwrap_or_default(), build_target);
 
-        let expanded = expand_glob(source.as_str(), self.dest, idx)?;
+        let expanded = expand_glob(
+            source.as_str(),
+            self.dest,
+            &self.exclude,
+            self.include_dirs,
+            idx,
+        )?;
         if !expanded.is_empty() {
             return Ok(expanded);
         }
 
         if let Some(src) = parent.as_ref().join(&source).to_str() {
-            let expanded = expand_glob(src, self.dest, idx)?;
+            let expanded = expand_glob(src, self.dest, &self.exclude, self.include_dirs, idx)?;
             if !expanded.is_empty() {
                 return Ok(expanded);
             }
@@ -142,7 +329,7 @@ impl FileInfo<'_, '_, '_, '_, '_> {
         Err(ConfigError::AssetFileNotFound(PathBuf::from(source)))
     }
 
-    fn generate_rpm_file_options<T: ToString>(
+    pub(crate) fn generate_rpm_file_options<T: ToString>(
         &self,
         dest: T,
         idx: usize,
@@ -166,6 +353,20 @@ impl FileInfo<'_, '_, '_, '_, '_> {
         if self.doc {
             rpm_file_option = rpm_file_option.is_doc();
         }
+        if self.license {
+            rpm_file_option = rpm_file_option.is_license();
+        }
+        if self.readme {
+            rpm_file_option = rpm_file_option.is_readme();
+        }
+        if let Some(verify) = self.verify {
+            rpm_file_option = rpm_file_option.verify(verify);
+        }
+        match self.file_type {
+            FileType::Symlink(target) => rpm_file_option = rpm_file_option.symlink(target),
+            FileType::Ghost => rpm_file_option = rpm_file_option.is_ghost(),
+            FileType::File | FileType::Dir => {}
+        }
         if let Some(caps) = self.caps {
             rpm_file_option = rpm_file_option
                 .caps(caps)
@@ -174,24 +375,100 @@ impl FileInfo<'_, '_, '_, '_, '_> {
         Ok(rpm_file_option.into())
     }
 
+    /// Returns the source path, the destination and the options of the files to be packaged.
+    ///
+    /// The source path is `None` for the entries not read from disk, such as directories.
     pub(crate) fn generate_rpm_file_entry<P: AsRef<Path>>(
         &self,
         build_target: &BuildTarget,
         parent: P,
         idx: usize,
-    ) -> Result<Vec<(PathBuf, rpm::FileOptions)>, ConfigError> {
+    ) -> Result<Vec<(Option<PathBuf>, String, rpm::FileOptions)>, ConfigError> {
+        if self.file_type != FileType::File {
+            let dest = match self.dest.trim_end_matches('/') {
+                "" => "/",
+                v => v,
+            };
+            let options = self.generate_rpm_file_options(dest, idx)?;
+            return Ok(vec![(None, dest.to_string(), options)]);
+        }
         self.generate_expanded_path(build_target, parent, idx)?
-            .iter()
-            .map(|(src, dst)| {
-                self.generate_rpm_file_options(dst, idx)
-                    .map(|v| (src.clone(), v))
+            .into_iter()
+            .map(|(src, dst)| match src {
+                Some(src) => self
+                    .generate_rpm_file_options(&dst, idx)
+                    .map(|v| (Some(src), dst, v)),
+                None => {
+                    let options = self.generate_dir_options(&dst);
+                    Ok((None, dst, options))
+                }
             })
             .collect::<Result<Vec<_>, _>>()
     }
+
+    /// Same as `generate_rpm_file_entry`, but the missing file in the target directory is
+    /// returned as it is, since it is the output of the build not yet run.
+    pub(crate) fn generate_planned_rpm_file_entry<P: AsRef<Path>>(
+        &self,
+        build_target: &BuildTarget,
+        parent: P,
+        idx: usize,
+    ) -> Result<Vec<(Option<PathBuf>, String, rpm::FileOptions)>, ConfigError> {
+        match self.generate_rpm_file_entry(build_target, parent, idx) {
+            Err(ConfigError::AssetFileNotFound(source))
+                if source.starts_with(build_target.build_target_path())
+                    && !source.to_string_lossy().contains(GLOB_META_CHARS) =>
+            {
+                let dest = match source.file_name().and_then(|v| v.to_str()) {
+                    Some(file_name) if self.dest.ends_with('/') => {
+                        format!("{}{file_name}", self.dest)
+                    }
+                    _ => self.dest.to_string(),
+                };
+                let options = self.generate_rpm_file_options(&dest, idx)?;
+                Ok(vec![(Some(source), dest, options)])
+            }
+            entries => entries,
+        }
+    }
+
+    /// Returns the options of the subdirectory created by the glob, which inherits only the owner.
+    fn generate_dir_options(&self, dest: &str) -> rpm::FileOptions {
+        let mut rpm_file_option = rpm::FileOptions::new(dest).mode(0o040755);
+        if let Some(user) = self.user {
+            rpm_file_option = rpm_file_option.user(user);
+        }
+        if let Some(group) = self.group {
+            rpm_file_option = rpm_file_option.group(group);
+        }
+        rpm_file_option.into()
+    }
+
+    /// Returns the file flags which `rpm::FileOptions` cannot set, to be added after building.
+    pub(crate) fn extra_flags(&self) -> FileFlags {
+        let mut flags = FileFlags::empty();
+        if self.config_missingok {
+            flags |= FileFlags::MISSINGOK;
+        }
+        if self.artifact {
+            flags |= FileFlags::ARTIFACT;
+        }
+        flags
+    }
+
+    /// Returns the payload of the entry not read from disk, that is the target of the symbolic link.
+    pub(crate) fn contents(&self) -> Vec<u8> {
+        match self.file_type {
+            FileType::Symlink(target) => target.as_bytes().to_vec(),
+            FileType::File | FileType::Dir | FileType::Ghost => Vec::new(),
+        }
+    }
 }
 
+const GLOB_META_CHARS: [char; 3] = ['*', '?', '['];
+
 fn get_base_from_glob(glob: &'_ str) -> PathBuf {
-    let base = match glob.split_once('*') {
+    let base = match glob.split_once(GLOB_META_CHARS) {
         Some((before, _)) => before,
         None => glob,
     };
@@ -209,10 +486,7 @@ fn get_base_from_glob(glob: &'_ str) -> PathBuf {
 }
 
 pub(crate) fn get_asset_rel_path(asset: &str, build_target: &BuildTarget) -> String {
-    let dir_name = match build_target.profile() {
-        "dev" => "debug",
-        p => p,
-    };
+    let dir_name = build_target.profile_dir_name();
     asset
         .strip_prefix("target/release/")
         .or_else(|| asset.strip_prefix(&format!("target/{dir_name}/")))
@@ -226,19 +500,181 @@ pub(crate) fn get_asset_rel_path(asset: &str, build_target: &BuildTarget) -> Str
         .unwrap_or(asset.to_string())
 }
 
+/// Returns whether `rel_path` or any of its ancestors matches one of `exclude`.
+fn is_excluded(rel_path: &Path, exclude: &[Pattern]) -> bool {
+    rel_path
+        .ancestors()
+        .filter(|v| !v.as_os_str().is_empty())
+        .any(|v| exclude.iter().any(|pattern| pattern.matches_path(v)))
+}
+
+/// Translates the glob `pattern` into the regular expression capturing each wildcard.
+///
+/// `**/` captures the directories without the trailing `/`, which may be empty.
+fn wildcard_regex(pattern: &str) -> Option<Regex> {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.next_if_eq(&'*').is_some() => match chars.next_if_eq(&'/') {
+                Some(_) => regex.push_str("(?:(.*)/)?"),
+                None => regex.push_str("(.*)"),
+            },
+            '*' => regex.push_str("([^/]*)"),
+            '?' => regex.push_str("([^/])"),
+            '[' => {
+                regex.push_str("([");
+                if chars.next_if_eq(&'!').is_some() {
+                    regex.push('^');
+                }
+                // `]` right after `[` is a member of the class
+                if chars.next_if_eq(&']').is_some() {
+                    regex.push_str("\\]");
+                }
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => break,
+                        '-' => regex.push('-'),
+                        c => regex.push_str(&regex::escape(&c.to_string())),
+                    }
+                }
+                regex.push_str("])");
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).ok()
+}
+
+/// Part of the destination template of the glob matches
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum DestSegment {
+    Literal(String),
+    /// `{filename}`: the file name
+    FileName,
+    /// `{stem}`: the file name without the extension
+    Stem,
+    /// `{ext}`: the extension without the leading `.`
+    Ext,
+    /// `{relpath}`: the path relative to the base directory of the glob
+    RelPath,
+    /// `{reldir}`: the directory of `{relpath}`
+    RelDir,
+    /// `{1}`, `{2}`, ...: the text matched by the n-th wildcard of the glob
+    Capture(usize),
+}
+
+/// Parses `dest` containing the placeholders such as `/usr/share/foo/{stem}`.
+///
+/// Returns `None` if `dest` contains no known placeholder, in which case the braces are taken
+/// literally. Otherwise `{{` and `}}` stand for `{` and `}`.
+fn parse_dest_template(
+    dest: &str,
+    num_captures: usize,
+    idx: usize,
+) -> Result<Option<Vec<DestSegment>>, ConfigError> {
+    let placeholder = Regex::new(r"\{(filename|stem|ext|relpath|reldir|[0-9]+)\}").unwrap();
+    if !placeholder.is_match(dest) {
+        return Ok(None);
+    }
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = dest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let (name, rest) = chars.as_str().split_once('}').ok_or_else(|| {
+                    ConfigError::AssetDestTemplateInvalid(idx, chars.as_str().to_string())
+                })?;
+                let segment = match name {
+                    "filename" => DestSegment::FileName,
+                    "stem" => DestSegment::Stem,
+                    "ext" => DestSegment::Ext,
+                    "relpath" => DestSegment::RelPath,
+                    "reldir" => DestSegment::RelDir,
+                    n => match n.parse::<usize>() {
+                        Ok(n) if (1..=num_captures).contains(&n) => DestSegment::Capture(n),
+                        _ => return Err(ConfigError::AssetDestTemplateInvalid(idx, name.into())),
+                    },
+                };
+                segments.push(DestSegment::Literal(std::mem::take(&mut literal)));
+                segments.push(segment);
+                chars = rest.chars();
+            }
+            c => literal.push(c),
+        }
+    }
+    segments.push(DestSegment::Literal(literal));
+    Ok(Some(segments))
+}
+
+/// Returns the directory part of the template before the first placeholder.
+fn dest_template_root(segments: &[DestSegment]) -> &str {
+    match segments.first() {
+        Some(DestSegment::Literal(v)) => v.rsplit_once('/').map_or("", |(dir, _)| dir),
+        _ => "",
+    }
+}
+
+fn render_dest_template(segments: &[DestSegment], rel_path: &Path, captures: &[&str]) -> String {
+    fn to_str(v: Option<&std::ffi::OsStr>) -> &str {
+        v.and_then(|v| v.to_str()).unwrap_or_default()
+    }
+    let mut dest = String::new();
+    for segment in segments {
+        dest.push_str(match segment {
+            DestSegment::Literal(v) => v,
+            DestSegment::FileName => to_str(rel_path.file_name()),
+            DestSegment::Stem => to_str(rel_path.file_stem()),
+            DestSegment::Ext => to_str(rel_path.extension()),
+            DestSegment::RelPath => rel_path.to_str().unwrap_or_default(),
+            DestSegment::RelDir => to_str(rel_path.parent().map(Path::as_os_str)),
+            DestSegment::Capture(n) => captures.get(n - 1).copied().unwrap_or_default(),
+        });
+    }
+    // the empty placeholders such as `{reldir}` leave the redundant separators
+    while dest.contains("//") {
+        dest = dest.replace("//", "/");
+    }
+    dest
+}
+
+/// Expands `source` into the pairs of the source path and the destination.
+///
+/// The directory structure below the base directory of the glob is preserved in `dest`, unless
+/// `dest` is a template with the placeholders such as `{stem}`. If `include_dirs` is set, the
+/// subdirectories created below `dest` are also returned before their contents without the source.
 fn expand_glob(
     source: &str,
     dest: &str,
+    exclude: &[Pattern],
+    include_dirs: bool,
     idx: usize,
-) -> Result<Vec<(PathBuf, String)>, ConfigError> {
+) -> Result<Vec<(Option<PathBuf>, String)>, ConfigError> {
     let mut vec = Vec::new();
-    if source.contains('*') {
+    // the existing file whose name contains the meta characters such as `foo[1].txt` is literal
+    if source.contains(GLOB_META_CHARS) && !Path::new(source).exists() {
         let base = get_base_from_glob(source);
+        let regex = base
+            .to_str()
+            .and_then(|base| source.strip_prefix(base))
+            .and_then(|pattern| wildcard_regex(pattern.trim_start_matches('/')));
+        let num_captures = regex.as_ref().map_or(0, |v| v.captures_len() - 1);
+        let template = parse_dest_template(dest, num_captures, idx)?;
+        let root = Path::new(template.as_deref().map_or(dest, dest_template_root));
+
+        let mut dirs = std::collections::HashSet::new();
         for path in glob(source).map_err(|e| ConfigError::AssetGlobInvalid(idx, e.msg))? {
             let file = path.map_err(|_| ConfigError::AssetReadFailed(idx))?;
-            if file.is_dir() {
-                continue;
-            }
             let rel_path = file.strip_prefix(&base).map_err(|_| {
                 ConfigError::AssetGlobPathInvalid(
                     idx,
@@ -246,10 +682,46 @@ fn expand_glob(
                     base.to_str().unwrap().to_owned(),
                 )
             })?;
-            let dest_path = Path::new(&dest).join(rel_path);
-            let dst = dest_path.to_str().unwrap().to_owned();
+            if rel_path.as_os_str().is_empty() || is_excluded(rel_path, exclude) {
+                continue;
+            }
+            let dest_path = match &template {
+                Some(template) => {
+                    let rel_str = rel_path.to_str().unwrap_or_default();
+                    let captures = regex
+                        .as_ref()
+                        .and_then(|v| v.captures(rel_str))
+                        .map(|v| {
+                            v.iter()
+                                .skip(1)
+                                .map(|v| v.map_or("", |v| v.as_str()))
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_default();
+                    PathBuf::from(render_dest_template(template, rel_path, &captures))
+                }
+                None => root.join(rel_path),
+            };
 
-            vec.push((file, dst));
+            let is_dir = file.is_dir();
+            if include_dirs {
+                // the ancestors are not always matched by the glob such as `dir/**/*.txt`
+                let mut ancestors = dest_path
+                    .ancestors()
+                    .skip(usize::from(!is_dir))
+                    .take_while(|v| *v != root && v.starts_with(root))
+                    .map(|v| v.to_str().unwrap().to_owned())
+                    .collect::<Vec<_>>();
+                ancestors.reverse();
+                for dir in ancestors {
+                    if dirs.insert(dir.clone()) {
+                        vec.push((None, dir));
+                    }
+                }
+            }
+            if !is_dir {
+                vec.push((Some(file), dest_path.to_str().unwrap().to_owned()));
+            }
         }
     } else if Path::new(source).exists() {
         let file = PathBuf::from(source);
@@ -258,7 +730,7 @@ fn expand_glob(
             _ => dest.to_string(),
         };
 
-        vec.push((file, dst));
+        vec.push((Some(file), dst));
     }
 
     Ok(vec)
@@ -317,57 +789,201 @@ mod test {
             files,
             vec![
                 FileInfo {
-                    source: "target/release/cargo-generate-rpm",
+                    source: Some("target/release/cargo-generate-rpm"),
                     dest: "/usr/bin/cargo-generate-rpm",
+                    file_type: FileType::File,
                     user: None,
                     group: None,
                     mode: Some(0o0100755),
                     config: false,
                     config_noreplace: false,
+                    config_missingok: false,
                     doc: false,
+                    license: false,
+                    readme: false,
+                    artifact: false,
                     caps: None,
+                    verify: None,
+                    exclude: Vec::new(),
+                    include_dirs: false,
                 },
                 FileInfo {
-                    source: "LICENSE",
+                    source: Some("LICENSE"),
                     dest: "/usr/share/doc/cargo-generate-rpm/LICENSE",
+                    file_type: FileType::File,
                     user: None,
                     group: None,
                     mode: Some(0o0100644),
                     config: false,
                     config_noreplace: false,
+                    config_missingok: false,
                     doc: true,
+                    license: false,
+                    readme: false,
+                    artifact: false,
                     caps: None,
+                    verify: None,
+                    exclude: Vec::new(),
+                    include_dirs: false,
                 },
                 FileInfo {
-                    source: "README.md",
+                    source: Some("README.md"),
                     dest: "/usr/share/doc/cargo-generate-rpm/README.md",
+                    file_type: FileType::File,
                     user: None,
                     group: None,
                     mode: Some(0o0100644),
                     config: false,
                     config_noreplace: false,
+                    config_missingok: false,
                     doc: true,
+                    license: false,
+                    readme: false,
+                    artifact: false,
                     caps: None,
+                    verify: None,
+                    exclude: Vec::new(),
+                    include_dirs: false,
                 },
             ]
         );
     }
 
+    #[test]
+    fn test_file_type() {
+        let assets = toml::toml! {
+            assets = [
+                { dest = "/var/lib/foo/", type = "dir", user = "foo" },
+                { dest = "/usr/lib/libfoo.so", type = "symlink", target = "libfoo.so.1" },
+                { dest = "/run/foo.pid", type = "ghost", mode = "600" },
+                { source = "README.md", dest = "/usr/share/doc/foo/", type = "file" },
+            ]
+        };
+        let assets = assets.get("assets").and_then(|v| v.as_array()).unwrap();
+        let files = FileInfo::new(assets.as_slice()).unwrap();
+        assert_eq!(
+            files
+                .iter()
+                .map(|v| (v.source, v.file_type, v.mode))
+                .collect::<Vec<_>>(),
+            vec![
+                (None, FileType::Dir, Some(0o040755)),
+                (None, FileType::Symlink("libfoo.so.1"), Some(0o120777)),
+                (None, FileType::Ghost, Some(0o100600)),
+                (Some("README.md"), FileType::File, None),
+            ]
+        );
+
+        let target = BuildTarget::from_args(&crate::cli::Cli::default());
+        let entries = files[0].generate_rpm_file_entry(&target, "", 0).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, None);
+        assert_eq!(entries[0].1, "/var/lib/foo");
+        assert_eq!(files[0].contents(), b"");
+        assert_eq!(files[1].contents(), b"libfoo.so.1");
+
+        let assets = toml::toml! {
+            assets = [{ dest = "/usr/lib/libfoo.so", type = "symlink" }]
+        };
+        let assets = assets.get("assets").and_then(|v| v.as_array()).unwrap();
+        assert!(matches!(
+            FileInfo::new(assets.as_slice()),
+            Err(ConfigError::AssetFileUndefined(0, "target"))
+        ));
+        let assets = toml::toml! {
+            assets = [{ dest = "/dev/foo", type = "device" }]
+        };
+        let assets = assets.get("assets").and_then(|v| v.as_array()).unwrap();
+        assert!(matches!(
+            FileInfo::new(assets.as_slice()),
+            Err(ConfigError::AssetFileWrongType(0, "type", _))
+        ));
+    }
+
+    #[test]
+    fn test_file_flags() {
+        let assets = toml::toml! {
+            assets = [
+                { source = "foo.conf", dest = "/etc/foo.conf", config = ["noreplace", "missingok"] },
+                { source = "LICENSE", dest = "/usr/share/licenses/foo/", license = true },
+                { source = "README.md", dest = "/usr/share/doc/foo/", readme = true },
+                { dest = "/usr/lib/.build-id/", type = "dir", artifact = true },
+                { source = "foo.log", dest = "/var/log/foo.log", verify = ["not", "md5", "size", "mtime"] },
+                { source = "bar", dest = "/usr/bin/bar", verify = ["user", "group", "mode"] },
+            ]
+        };
+        let assets = assets.get("assets").and_then(|v| v.as_array()).unwrap();
+        let files = FileInfo::new(assets.as_slice()).unwrap();
+        assert_eq!(
+            files
+                .iter()
+                .map(|v| (v.config, v.config_noreplace, v.config_missingok))
+                .collect::<Vec<_>>()[0],
+            (false, true, true)
+        );
+        assert_eq!(files[0].extra_flags(), FileFlags::MISSINGOK);
+        assert!(files[1].license && !files[1].readme);
+        assert!(files[2].readme && !files[2].license);
+        assert_eq!(files[3].extra_flags(), FileFlags::ARTIFACT);
+        assert_eq!(
+            files[4].verify,
+            Some(
+                FileVerifyFlags::all()
+                    - FileVerifyFlags::FILEDIGEST
+                    - FileVerifyFlags::FILESIZE
+                    - FileVerifyFlags::MTIME
+            )
+        );
+        assert_eq!(
+            files[5].verify,
+            Some(FileVerifyFlags::USER | FileVerifyFlags::GROUP | FileVerifyFlags::MODE)
+        );
+        assert_eq!(files[5].extra_flags(), FileFlags::empty());
+
+        let assets = toml::toml! {
+            assets = [
+                { source = "foo", dest = "/etc/foo", config = "unknown" },
+                { source = "foo", dest = "/etc/foo", config = [true] },
+                { source = "foo", dest = "/etc/foo", license = "yes" },
+                { source = "foo", dest = "/etc/foo", verify = "md5" },
+                { source = "foo", dest = "/etc/foo", verify = ["sha256"] },
+            ]
+        };
+        let assets = assets.get("assets").and_then(|v| v.as_array()).unwrap();
+        for (asset, field) in assets
+            .iter()
+            .zip(["config", "config", "license", "verify", "verify"])
+        {
+            assert!(matches!(
+                FileInfo::new(std::slice::from_ref(asset)),
+                Err(ConfigError::AssetFileWrongType(0, f, _)) if f == field
+            ));
+        }
+    }
+
     #[test]
     fn test_generate_rpm_file_path() {
         let tempdir = tempfile::tempdir().unwrap();
         let args = crate::cli::Cli::default();
-        let target = BuildTarget::new(&args);
+        let target = BuildTarget::from_args(&args);
         let file_info = FileInfo {
-            source: "README.md",
+            source: Some("README.md"),
             dest: "/usr/share/doc/cargo-generate-rpm/README.md",
+            file_type: FileType::File,
             user: None,
             group: None,
             mode: None,
             config: false,
             config_noreplace: false,
+            config_missingok: false,
             doc: true,
+            license: false,
+            readme: false,
+            artifact: false,
             caps: Some("cap_sys_admin=pe"),
+            verify: None,
+            exclude: Vec::new(),
+            include_dirs: false,
         };
         let expanded = file_info
             .generate_expanded_path(&target, &tempdir, 0)
@@ -375,39 +991,55 @@ mod test {
         assert_eq!(
             expanded
                 .iter()
-                .map(|(src, dst)| { (src.as_path().to_str(), dst) })
+                .map(|(src, dst)| { (src.as_deref().and_then(Path::to_str), dst) })
                 .collect::<Vec<_>>(),
-            vec![(Some(file_info.source), &file_info.dest.to_string())]
+            vec![(file_info.source, &file_info.dest.to_string())]
         );
 
         let file_info = FileInfo {
-            source: "not-exist-file",
+            source: Some("not-exist-file"),
             dest: "/usr/share/doc/cargo-generate-rpm/not-exist-file",
+            file_type: FileType::File,
             user: None,
             group: None,
             mode: None,
             config: false,
             config_noreplace: false,
+            config_missingok: false,
             doc: true,
+            license: false,
+            readme: false,
+            artifact: false,
             caps: None,
+            verify: None,
+            exclude: Vec::new(),
+            include_dirs: false,
         };
         assert!(
             matches!(file_info.generate_expanded_path(&target, &tempdir, 0),
-                   Err(ConfigError::AssetFileNotFound(v)) if v == PathBuf::from( "not-exist-file"))
+                   Err(ConfigError::AssetFileNotFound(v)) if v == Path::new( "not-exist-file"))
         );
 
         std::fs::create_dir_all(tempdir.path().join("target/release")).unwrap();
         File::create(tempdir.path().join("target/release/foobar")).unwrap();
         let file_info = FileInfo {
-            source: "target/release/foobar",
+            source: Some("target/release/foobar"),
             dest: "/usr/bin/foobar",
+            file_type: FileType::File,
             user: None,
             group: None,
             mode: None,
             config: false,
             config_noreplace: false,
+            config_missingok: false,
             doc: false,
+            license: false,
+            readme: false,
+            artifact: false,
             caps: None,
+            verify: None,
+            exclude: Vec::new(),
+            include_dirs: false,
         };
         let expanded = file_info
             .generate_expanded_path(&target, &tempdir, 0)
@@ -415,7 +1047,7 @@ mod test {
         assert_eq!(
             expanded
                 .iter()
-                .map(|(src, dst)| { (src.as_path().to_str(), dst) })
+                .map(|(src, dst)| { (src.as_deref().and_then(Path::to_str), dst) })
                 .collect::<Vec<_>>(),
             vec![(
                 Some(
@@ -441,14 +1073,14 @@ mod test {
             ),
             ..Default::default()
         };
-        let target = BuildTarget::new(&args);
+        let target = BuildTarget::from_args(&args);
         let expanded = file_info
             .generate_expanded_path(&target, &tempdir, 0)
             .unwrap();
         assert_eq!(
             expanded
                 .iter()
-                .map(|(src, dst)| { (src.as_path().to_str(), dst) })
+                .map(|(src, dst)| { (src.as_deref().and_then(Path::to_str), dst) })
                 .collect::<Vec<_>>(),
             vec![(
                 Some(
@@ -470,15 +1102,23 @@ mod test {
         )
         .unwrap();
         let file_info = FileInfo {
-            source: "target/release/my-bin",
+            source: Some("target/release/my-bin"),
             dest: "/usr/bin/my-bin",
+            file_type: FileType::File,
             user: None,
             group: None,
             mode: None,
             config: false,
             config_noreplace: false,
+            config_missingok: false,
             doc: false,
+            license: false,
+            readme: false,
+            artifact: false,
             caps: None,
+            verify: None,
+            exclude: Vec::new(),
+            include_dirs: false,
         };
         let args = crate::cli::Cli {
             target_dir: Some(
@@ -494,14 +1134,14 @@ mod test {
             profile: "my-profile".to_string(),
             ..Default::default()
         };
-        let target = BuildTarget::new(&args);
+        let target = BuildTarget::from_args(&args);
         let expanded = file_info
             .generate_expanded_path(&target, &tempdir, 0)
             .unwrap();
         assert_eq!(
             expanded
                 .iter()
-                .map(|(src, dst)| { (src.as_path().to_str(), dst) })
+                .map(|(src, dst)| { (src.as_deref().and_then(Path::to_str), dst) })
                 .collect::<Vec<_>>(),
             vec![(
                 Some(
@@ -519,15 +1159,22 @@ mod test {
     #[test]
     fn test_expand_glob() {
         assert_eq!(
-            expand_glob("*.md", "/usr/share/doc/cargo-generate-rpm/", 0).unwrap(),
+            expand_glob("*.md", "/usr/share/doc/cargo-generate-rpm/", &[], false, 0).unwrap(),
             vec![(
-                PathBuf::from("README.md"),
+                Some(PathBuf::from("README.md")),
                 "/usr/share/doc/cargo-generate-rpm/README.md".into()
             )]
         );
 
         assert_eq!(
-            expand_glob("*-not-exist-glob", "/usr/share/doc/cargo-generate-rpm/", 0).unwrap(),
+            expand_glob(
+                "*-not-exist-glob",
+                "/usr/share/doc/cargo-generate-rpm/",
+                &[],
+                false,
+                0
+            )
+            .unwrap(),
             vec![]
         );
 
@@ -535,11 +1182,13 @@ mod test {
             expand_glob(
                 "README.md",
                 "/usr/share/doc/cargo-generate-rpm/README.md",
+                &[],
+                false,
                 2
             )
             .unwrap(),
             vec![(
-                PathBuf::from("README.md"),
+                Some(PathBuf::from("README.md")),
                 "/usr/share/doc/cargo-generate-rpm/README.md".into()
             )]
         );
@@ -548,13 +1197,187 @@ mod test {
             expand_glob(
                 "README.md",
                 "/usr/share/doc/cargo-generate-rpm/", // specifying directory
+                &[],
+                false,
                 0
             )
             .unwrap(),
             vec![(
-                PathBuf::from("README.md"),
+                Some(PathBuf::from("README.md")),
                 "/usr/share/doc/cargo-generate-rpm/README.md".into()
             )]
         );
     }
+
+    #[test]
+    fn test_expand_glob_literal_meta_chars() {
+        let tempdir = tempfile::tempdir().unwrap();
+        for file in ["foo[1].txt", "foo1.txt", "what?.md"] {
+            std::fs::write(tempdir.path().join(file), "").unwrap();
+        }
+        let expand = |file: &str| {
+            let source = tempdir.path().join(file);
+            expand_glob(source.to_str().unwrap(), "/usr/share/foo/", &[], false, 0)
+                .unwrap()
+                .into_iter()
+                .map(|(src, dst)| (src.unwrap(), dst))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            expand("foo[1].txt"),
+            vec![(
+                tempdir.path().join("foo[1].txt"),
+                "/usr/share/foo/foo[1].txt".to_string()
+            )]
+        );
+        assert_eq!(
+            expand("what?.md"),
+            vec![(
+                tempdir.path().join("what?.md"),
+                "/usr/share/foo/what?.md".to_string()
+            )]
+        );
+        // a glob unless the file exists
+        assert_eq!(
+            expand("foo[0-9].txt"),
+            vec![(
+                tempdir.path().join("foo1.txt"),
+                "/usr/share/foo/foo1.txt".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_expand_glob_recursive() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path().join("dist");
+        std::fs::create_dir_all(root.join("js/vendor")).unwrap();
+        std::fs::create_dir_all(root.join("css")).unwrap();
+        std::fs::create_dir_all(root.join("tmp")).unwrap();
+        for file in [
+            "index.html",
+            "js/app.js",
+            "js/app.js.map",
+            "js/vendor/lib.js",
+            "css/style.css",
+            "tmp/cache",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        let source = format!("{}/**/*", root.display());
+        let exclude = [Pattern::new("*.map").unwrap(), Pattern::new("tmp").unwrap()];
+        let dests = |include_dirs| {
+            let mut dests = expand_glob(&source, "/usr/share/foo/", &exclude, include_dirs, 0)
+                .unwrap()
+                .into_iter()
+                .map(|(src, dst)| (dst, src.is_none()))
+                .collect::<Vec<_>>();
+            dests.sort();
+            dests
+        };
+
+        assert_eq!(
+            dests(false),
+            vec![
+                ("/usr/share/foo/css/style.css".to_string(), false),
+                ("/usr/share/foo/index.html".to_string(), false),
+                ("/usr/share/foo/js/app.js".to_string(), false),
+                ("/usr/share/foo/js/vendor/lib.js".to_string(), false),
+            ]
+        );
+        assert_eq!(
+            dests(true),
+            vec![
+                ("/usr/share/foo/css".to_string(), true),
+                ("/usr/share/foo/css/style.css".to_string(), false),
+                ("/usr/share/foo/index.html".to_string(), false),
+                ("/usr/share/foo/js".to_string(), true),
+                ("/usr/share/foo/js/app.js".to_string(), false),
+                ("/usr/share/foo/js/vendor".to_string(), true),
+                ("/usr/share/foo/js/vendor/lib.js".to_string(), false),
+            ]
+        );
+
+        // the ancestors not matched by the glob
+        let source = format!("{}/**/*.js", root.display());
+        let expanded = expand_glob(&source, "/usr/share/foo/", &[], true, 0).unwrap();
+        assert_eq!(
+            expanded.iter().map(|v| v.1.as_str()).collect::<Vec<_>>(),
+            vec![
+                "/usr/share/foo/js",
+                "/usr/share/foo/js/app.js",
+                "/usr/share/foo/js/vendor",
+                "/usr/share/foo/js/vendor/lib.js",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_glob_dest_template() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path().join("completions");
+        std::fs::create_dir_all(root.join("extra")).unwrap();
+        for file in ["foo.bash", "foo.zsh", "extra/bar.bash"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        let expand = |source: &str, dest: &str, include_dirs| {
+            let source = format!("{}/{source}", root.display());
+            let mut dests = expand_glob(&source, dest, &[], include_dirs, 0)
+                .unwrap()
+                .into_iter()
+                .map(|(_, dst)| dst)
+                .collect::<Vec<_>>();
+            dests.sort();
+            dests
+        };
+
+        assert_eq!(
+            expand(
+                "*.bash",
+                "/usr/share/bash-completion/completions/{stem}",
+                false
+            ),
+            vec!["/usr/share/bash-completion/completions/foo"]
+        );
+        assert_eq!(
+            expand("**/*.bash", "/usr/share/foo/{reldir}/{1}-{2}.{ext}", false),
+            vec![
+                "/usr/share/foo/-foo.bash",
+                "/usr/share/foo/extra/extra-bar.bash"
+            ]
+        );
+        assert_eq!(
+            expand("**/*.bash", "/usr/share/foo/{relpath}.d/{filename}", true),
+            vec![
+                "/usr/share/foo/extra",
+                "/usr/share/foo/extra/bar.bash.d",
+                "/usr/share/foo/extra/bar.bash.d/bar.bash",
+                "/usr/share/foo/foo.bash.d",
+                "/usr/share/foo/foo.bash.d/foo.bash",
+            ]
+        );
+        assert_eq!(
+            expand("foo.?[a-s]*", "/usr/share/{{{2}}}/{1}{3}", false),
+            vec!["/usr/share/{a}/bsh", "/usr/share/{s}/zh"]
+        );
+
+        // braces without the known placeholders are literal
+        assert_eq!(
+            expand("*.bash", "/usr/share/{foo}/", false),
+            vec!["/usr/share/{foo}/foo.bash"]
+        );
+
+        let source = format!("{}/*.bash", root.display());
+        for dest in [
+            "/usr/share/{2}",
+            "/usr/share/{path}/{stem}",
+            "/usr/share/{stem}/{name",
+        ] {
+            assert!(matches!(
+                expand_glob(&source, dest, &[], false, 0),
+                Err(ConfigError::AssetDestTemplateInvalid(0, _))
+            ));
+        }
+    }
 }
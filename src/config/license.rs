@@ -0,0 +1,564 @@
+use std::path::{Path, PathBuf};
+
+use toml::value::Table;
+
+use crate::error::ConfigError;
+
+use super::cargo_lock::LockedPackage;
+
+/// How the license expression is written in the package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) enum LicenseStyle {
+    /// Normalized SPDX expression such as `MIT OR Apache-2.0`
+    Spdx,
+    /// Legacy Fedora short names such as `MIT or ASL 2.0`
+    Fedora,
+    /// Use the license as it is
+    #[default]
+    Verbatim,
+}
+
+impl LicenseStyle {
+    /// Returns the style given by `value`, which defaults to `Spdx` if the licenses are
+    /// aggregated as they are parsed to be combined, or `Verbatim` otherwise.
+    pub(super) fn new(value: Option<&str>, aggregate: bool) -> Result<Self, ConfigError> {
+        match value {
+            None if aggregate => Ok(Self::Spdx),
+            None => Ok(Self::Verbatim),
+            Some("spdx") => Ok(Self::Spdx),
+            Some("fedora") => Ok(Self::Fedora),
+            Some("verbatim") => Ok(Self::Verbatim),
+            Some(_) => Err(ConfigError::WrongType(
+                "license-style".to_string(),
+                "\"spdx\", \"fedora\" or \"verbatim\"",
+            )),
+        }
+    }
+}
+
+/// Common SPDX identifiers, used to correct the case such as `mit`.
+const LICENSE_IDS: &[&str] = &[
+    "0BSD",
+    "AGPL-3.0-only",
+    "AGPL-3.0-or-later",
+    "Apache-1.1",
+    "Apache-2.0",
+    "Artistic-2.0",
+    "BSD-1-Clause",
+    "BSD-2-Clause",
+    "BSD-3-Clause",
+    "BSL-1.0",
+    "CC-BY-4.0",
+    "CC-BY-SA-4.0",
+    "CC0-1.0",
+    "CDLA-Permissive-2.0",
+    "EPL-2.0",
+    "EUPL-1.2",
+    "GPL-2.0-only",
+    "GPL-2.0-or-later",
+    "GPL-3.0-only",
+    "GPL-3.0-or-later",
+    "ISC",
+    "LGPL-2.1-only",
+    "LGPL-2.1-or-later",
+    "LGPL-3.0-only",
+    "LGPL-3.0-or-later",
+    "MIT",
+    "MIT-0",
+    "MPL-1.1",
+    "MPL-2.0",
+    "NCSA",
+    "OpenSSL",
+    "PSF-2.0",
+    "Python-2.0",
+    "Unicode-3.0",
+    "Unicode-DFS-2016",
+    "Unlicense",
+    "WTFPL",
+    "Zlib",
+];
+
+/// Common SPDX exceptions following `WITH`.
+const EXCEPTION_IDS: &[&str] = &[
+    "Classpath-exception-2.0",
+    "GCC-exception-3.1",
+    "LLVM-exception",
+];
+
+/// Deprecated SPDX identifiers and their replacements, if any.
+const DEPRECATED_IDS: &[(&str, Option<&str>)] = &[
+    ("AGPL-1.0", Some("AGPL-1.0-only")),
+    ("AGPL-3.0", Some("AGPL-3.0-only")),
+    ("GFDL-1.1", Some("GFDL-1.1-only")),
+    ("GFDL-1.2", Some("GFDL-1.2-only")),
+    ("GFDL-1.3", Some("GFDL-1.3-only")),
+    ("GPL-1.0", Some("GPL-1.0-only")),
+    ("GPL-1.0+", Some("GPL-1.0-or-later")),
+    ("GPL-2.0", Some("GPL-2.0-only")),
+    ("GPL-2.0+", Some("GPL-2.0-or-later")),
+    ("GPL-3.0", Some("GPL-3.0-only")),
+    ("GPL-3.0+", Some("GPL-3.0-or-later")),
+    ("LGPL-2.0", Some("LGPL-2.0-only")),
+    ("LGPL-2.0+", Some("LGPL-2.0-or-later")),
+    ("LGPL-2.1", Some("LGPL-2.1-only")),
+    ("LGPL-2.1+", Some("LGPL-2.1-or-later")),
+    ("LGPL-3.0", Some("LGPL-3.0-only")),
+    ("LGPL-3.0+", Some("LGPL-3.0-or-later")),
+    ("StandardML-NJ", Some("SMLNJ")),
+    ("bzip2-1.0.5", Some("bzip2-1.0.6")),
+    ("Nunit", None),
+    ("eCos-2.0", None),
+    ("wxWindows", None),
+];
+
+/// SPDX identifiers and the legacy Fedora short names.
+const FEDORA_NAMES: &[(&str, &str)] = &[
+    ("AGPL-3.0-only", "AGPLv3"),
+    ("AGPL-3.0-or-later", "AGPLv3+"),
+    ("Apache-1.1", "ASL 1.1"),
+    ("Apache-2.0", "ASL 2.0"),
+    ("Artistic-2.0", "Artistic 2.0"),
+    ("BSD-2-Clause", "BSD"),
+    ("BSD-3-Clause", "BSD"),
+    ("BSL-1.0", "Boost"),
+    ("CC-BY-4.0", "CC-BY"),
+    ("CC-BY-SA-4.0", "CC-BY-SA"),
+    ("CC0-1.0", "CC0"),
+    ("GPL-2.0-only", "GPLv2"),
+    ("GPL-2.0-or-later", "GPLv2+"),
+    ("GPL-3.0-only", "GPLv3"),
+    ("GPL-3.0-or-later", "GPLv3+"),
+    ("LGPL-2.1-only", "LGPLv2"),
+    ("LGPL-2.1-or-later", "LGPLv2+"),
+    ("LGPL-3.0-only", "LGPLv3"),
+    ("LGPL-3.0-or-later", "LGPLv3+"),
+    ("MPL-1.1", "MPLv1.1"),
+    ("MPL-2.0", "MPLv2.0"),
+    ("Python-2.0", "Python"),
+    ("Unicode-DFS-2016", "Unicode"),
+    ("Zlib", "zlib"),
+];
+
+/// SPDX license expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expression {
+    License {
+        id: String,
+        or_later: bool,
+        exception: Option<String>,
+    },
+    And(Vec<Expression>),
+    Or(Vec<Expression>),
+}
+
+/// Parser of the SPDX license expression, which accepts the legacy `/` of Cargo as `OR` and the
+/// operators in lower case.
+struct Parser<'a> {
+    expression: &'a str,
+    tokens: Vec<&'a str>,
+    pos: usize,
+    warnings: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(expression: &'a str) -> Self {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (idx, c) in expression.char_indices() {
+            if c.is_whitespace() || matches!(c, '(' | ')' | '/') {
+                if let Some(start) = start.take() {
+                    tokens.push(&expression[start..idx]);
+                }
+                if !c.is_whitespace() {
+                    tokens.push(&expression[idx..idx + 1]);
+                }
+            } else if start.is_none() {
+                start = Some(idx);
+            }
+        }
+        if let Some(start) = start {
+            tokens.push(&expression[start..]);
+        }
+        Self {
+            expression,
+            tokens,
+            pos: 0,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn error(&self, reason: impl Into<String>) -> ConfigError {
+        ConfigError::InvalidLicense(self.expression.to_string(), reason.into())
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next_if(&mut self, f: impl Fn(&str) -> bool) -> bool {
+        let matched = self.peek().is_some_and(f);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn parse(mut self) -> Result<(Expression, Vec<String>), ConfigError> {
+        let expression = self.parse_or()?;
+        match self.peek() {
+            None => Ok((expression, self.warnings)),
+            Some(token) => Err(self.error(format!("unexpected `{token}'"))),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, ConfigError> {
+        let mut operands = vec![self.parse_and()?];
+        while self.next_if(|v| v == "/" || v.eq_ignore_ascii_case("OR")) {
+            operands.push(self.parse_and()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.remove(0),
+            _ => Expression::Or(operands),
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, ConfigError> {
+        let mut operands = vec![self.parse_primary()?];
+        while self.next_if(|v| v.eq_ignore_ascii_case("AND")) {
+            operands.push(self.parse_primary()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.remove(0),
+            _ => Expression::And(operands),
+        })
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ConfigError> {
+        if self.next_if(|v| v == "(") {
+            let expression = self.parse_or()?;
+            if !self.next_if(|v| v == ")") {
+                return Err(self.error("unbalanced parentheses"));
+            }
+            return Ok(expression);
+        }
+
+        let token = self.peek().ok_or(self.error("missing license"))?;
+        let is_operator = ["AND", "OR", "WITH"]
+            .iter()
+            .any(|v| token.eq_ignore_ascii_case(v));
+        if is_operator || matches!(token, "/" | ")") {
+            return Err(self.error(format!("unexpected `{token}'")));
+        }
+        self.pos += 1;
+        let (id, or_later) = self.license_id(token)?;
+        let exception = match self.next_if(|v| v.eq_ignore_ascii_case("WITH")) {
+            true => {
+                let token = self.peek().ok_or(self.error("missing exception"))?;
+                self.pos += 1;
+                Some(self.canonical_id(token, EXCEPTION_IDS)?)
+            }
+            false => None,
+        };
+        Ok(Expression::License {
+            id,
+            or_later,
+            exception,
+        })
+    }
+
+    /// Returns the identifier in the canonical case, replacing the deprecated one.
+    fn license_id(&mut self, token: &str) -> Result<(String, bool), ConfigError> {
+        if let Some((deprecated, replacement)) = DEPRECATED_IDS
+            .iter()
+            .find(|(v, _)| v.eq_ignore_ascii_case(token))
+        {
+            self.warnings.push(match replacement {
+                Some(replacement) => {
+                    format!("deprecated license `{deprecated}' is replaced with `{replacement}'")
+                }
+                None => format!("license `{deprecated}' is deprecated"),
+            });
+            return Ok((replacement.unwrap_or(deprecated).to_string(), false));
+        }
+        let (id, or_later) = match token.strip_suffix('+') {
+            Some(id) => (id, true),
+            None => (token, false),
+        };
+        let id = self.canonical_id(id, LICENSE_IDS)?;
+        Ok((id, or_later))
+    }
+
+    fn canonical_id(&self, id: &str, known_ids: &[&str]) -> Result<String, ConfigError> {
+        let is_valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | ':'));
+        if !is_valid {
+            return Err(self.error(format!("invalid identifier `{id}'")));
+        }
+        Ok(known_ids
+            .iter()
+            .find(|v| v.eq_ignore_ascii_case(id))
+            .map_or(id, |v| *v)
+            .to_string())
+    }
+}
+
+impl Expression {
+    /// Returns the expression of all the `expressions`, omitting the duplicates.
+    fn all(expressions: Vec<Expression>) -> Self {
+        let mut operands = Vec::<Expression>::new();
+        for expression in expressions {
+            let expression = match expression {
+                Self::And(v) => v,
+                v => vec![v],
+            };
+            for expression in expression {
+                if !operands.contains(&expression) {
+                    operands.push(expression);
+                }
+            }
+        }
+        match operands.len() {
+            1 => operands.remove(0),
+            _ => Self::And(operands),
+        }
+    }
+
+    fn write(&self, style: LicenseStyle, nested: bool, out: &mut String) {
+        let (operands, operator) = match self {
+            Self::License {
+                id,
+                or_later,
+                exception,
+            } => {
+                match (style, FEDORA_NAMES.iter().find(|(v, _)| v == id)) {
+                    (LicenseStyle::Fedora, Some((_, name))) => out.push_str(name),
+                    _ => out.push_str(id),
+                }
+                if *or_later {
+                    out.push('+');
+                }
+                match (style, exception) {
+                    (LicenseStyle::Fedora, Some(_)) => out.push_str(" with exceptions"),
+                    (_, Some(exception)) => {
+                        out.push_str(" WITH ");
+                        out.push_str(exception);
+                    }
+                    (_, None) => {}
+                }
+                return;
+            }
+            Self::And(v) => (v, "AND"),
+            Self::Or(v) => (v, "OR"),
+        };
+        let operator = match style {
+            LicenseStyle::Fedora => operator.to_lowercase(),
+            _ => operator.to_string(),
+        };
+        if nested {
+            out.push('(');
+        }
+        for (idx, operand) in operands.iter().enumerate() {
+            if idx > 0 {
+                out.push_str(&format!(" {operator} "));
+            }
+            operand.write(style, !matches!(operand, Self::License { .. }), out);
+        }
+        if nested {
+            out.push(')');
+        }
+    }
+
+    fn to_string_with_style(&self, style: LicenseStyle) -> String {
+        let mut out = String::new();
+        self.write(style, false, &mut out);
+        out
+    }
+}
+
+/// Returns the `license` in the `style`, and the warnings such as the deprecated identifiers.
+///
+/// `dependency_licenses` are combined with `AND` unless the style is `Verbatim`.
+pub(super) fn normalize_license(
+    license: &str,
+    dependency_licenses: &[String],
+    style: LicenseStyle,
+) -> Result<(String, Vec<String>), ConfigError> {
+    if style == LicenseStyle::Verbatim {
+        return Ok((license.to_string(), Vec::new()));
+    }
+    let (expression, warnings) = Parser::new(license).parse()?;
+    let mut expressions = vec![expression];
+    for license in dependency_licenses {
+        expressions.push(Parser::new(license).parse()?.0);
+    }
+    let expression = Expression::all(expressions);
+    Ok((expression.to_string_with_style(style), warnings))
+}
+
+/// Returns the licenses of the `packages`, read from the manifests in the registry cache of
+/// `cargo_home`.
+///
+/// The crates not in the cache, such as the ones from git, are returned separately.
+pub(super) fn dependency_licenses(
+    packages: &[LockedPackage],
+    cargo_home: &Path,
+) -> (Vec<String>, Vec<String>) {
+    let registry_dirs: Vec<PathBuf> = std::fs::read_dir(cargo_home.join("registry").join("src"))
+        .map(|v| v.filter_map(|v| v.ok()).map(|v| v.path()).collect())
+        .unwrap_or_default();
+    let mut licenses = Vec::new();
+    let mut missing = Vec::new();
+    for LockedPackage { name, version, .. } in packages {
+        let license = registry_dirs
+            .iter()
+            .map(|v| v.join(format!("{name}-{version}")).join("Cargo.toml"))
+            .find_map(|v| std::fs::read_to_string(v).ok())
+            .and_then(|v| v.parse::<Table>().ok())
+            .and_then(|v| {
+                v.get("package")?
+                    .get("license")?
+                    .as_str()
+                    .map(str::to_string)
+            });
+        match license {
+            Some(license) if !licenses.contains(&license) => licenses.push(license),
+            Some(_) => {}
+            None => missing.push(format!("{name} {version}")),
+        }
+    }
+    licenses.sort();
+    (licenses, missing)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn normalize(license: &str, style: LicenseStyle) -> String {
+        normalize_license(license, &[], style).unwrap().0
+    }
+
+    #[test]
+    fn test_normalize_license() {
+        assert_eq!(
+            normalize("MIT/Apache-2.0", LicenseStyle::Spdx),
+            "MIT OR Apache-2.0"
+        );
+        assert_eq!(
+            normalize("mit or apache-2.0", LicenseStyle::Spdx),
+            "MIT OR Apache-2.0"
+        );
+        assert_eq!(
+            normalize(
+                "(MIT OR Apache-2.0) AND Unicode-DFS-2016",
+                LicenseStyle::Spdx
+            ),
+            "(MIT OR Apache-2.0) AND Unicode-DFS-2016"
+        );
+        assert_eq!(
+            normalize("Apache-2.0 WITH LLVM-exception", LicenseStyle::Spdx),
+            "Apache-2.0 WITH LLVM-exception"
+        );
+        assert_eq!(
+            normalize("LicenseRef-Proprietary", LicenseStyle::Spdx),
+            "LicenseRef-Proprietary"
+        );
+        assert_eq!(
+            normalize("MIT/Apache-2.0", LicenseStyle::Verbatim),
+            "MIT/Apache-2.0"
+        );
+
+        assert_eq!(
+            LicenseStyle::new(None, false).unwrap(),
+            LicenseStyle::Verbatim
+        );
+        assert_eq!(LicenseStyle::new(None, true).unwrap(), LicenseStyle::Spdx);
+        assert_eq!(
+            LicenseStyle::new(Some("fedora"), true).unwrap(),
+            LicenseStyle::Fedora
+        );
+        assert!(LicenseStyle::new(Some("rpm"), false).is_err());
+
+        let (license, warnings) =
+            normalize_license("GPL-2.0+ OR MIT", &[], LicenseStyle::Spdx).unwrap();
+        assert_eq!(license, "GPL-2.0-or-later OR MIT");
+        assert_eq!(
+            warnings,
+            ["deprecated license `GPL-2.0+' is replaced with `GPL-2.0-or-later'"]
+        );
+
+        assert_eq!(
+            normalize("MIT OR Apache-2.0", LicenseStyle::Fedora),
+            "MIT or ASL 2.0"
+        );
+        assert_eq!(
+            normalize(
+                "(GPL-3.0-or-later WITH GCC-exception-3.1) AND BSD-3-Clause",
+                LicenseStyle::Fedora
+            ),
+            "GPLv3+ with exceptions and BSD"
+        );
+
+        for (license, reason) in [
+            ("MIT OR", "missing license"),
+            ("(MIT OR Apache-2.0", "unbalanced parentheses"),
+            ("MIT Apache-2.0", "unexpected `Apache-2.0'"),
+            ("MIT, Apache-2.0", "invalid identifier `MIT,'"),
+            ("", "missing license"),
+        ] {
+            assert!(
+                matches!(
+                    normalize_license(license, &[], LicenseStyle::Spdx),
+                    Err(ConfigError::InvalidLicense(l, r)) if l == license && r == reason
+                ),
+                "{license}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_dependency_licenses() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let cargo_home = tempdir.path().join("cargo");
+        let registry = cargo_home.join("registry/src/index.crates.io-0123456789abcdef");
+        for (name, license) in [("foo-1.0.0", "MIT OR Apache-2.0"), ("bar-0.2.0", "MIT")] {
+            std::fs::create_dir_all(registry.join(name)).unwrap();
+            std::fs::write(
+                registry.join(name).join("Cargo.toml"),
+                format!("[package]\nlicense = \"{license}\"\n"),
+            )
+            .unwrap();
+        }
+        let packages = [
+            (
+                "bar",
+                "0.2.0",
+                "registry+https://github.com/rust-lang/crates.io-index",
+            ),
+            (
+                "foo",
+                "1.0.0",
+                "registry+https://github.com/rust-lang/crates.io-index",
+            ),
+            (
+                "qux",
+                "0.3.0",
+                "git+https://example.com/qux#0123456789abcdef",
+            ),
+        ]
+        .map(|(name, version, source)| LockedPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            source: Some(source.to_string()),
+        });
+
+        let (licenses, missing) = dependency_licenses(&packages, &cargo_home);
+        assert_eq!(licenses, ["MIT", "MIT OR Apache-2.0"]);
+        assert_eq!(missing, ["qux 0.3.0"]);
+
+        let (license, _) = normalize_license("MIT", &licenses, LicenseStyle::Spdx).unwrap();
+        assert_eq!(license, "MIT AND (MIT OR Apache-2.0)");
+    }
+}
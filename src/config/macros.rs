@@ -0,0 +1,357 @@
+//! Expansion of the RPM-style macros such as `%{name}` and `%{_bindir}` in the metadata.
+
+use std::collections::BTreeMap;
+
+use toml::Value;
+use toml::value::Table;
+
+use crate::error::ConfigError;
+
+/// Directories defined by rpm, which may be overridden in the `macros` table.
+const BUILTIN_MACROS: &[(&str, &str)] = &[
+    ("_prefix", "/usr"),
+    ("_exec_prefix", "%{_prefix}"),
+    ("_bindir", "%{_exec_prefix}/bin"),
+    ("_sbindir", "%{_exec_prefix}/sbin"),
+    ("_libexecdir", "%{_exec_prefix}/libexec"),
+    ("_libdir", "%{_exec_prefix}/%{_lib}"),
+    ("_includedir", "%{_prefix}/include"),
+    ("_datarootdir", "%{_prefix}/share"),
+    ("_datadir", "%{_datarootdir}"),
+    ("_infodir", "%{_datarootdir}/info"),
+    ("_mandir", "%{_datarootdir}/man"),
+    ("_docdir", "%{_datadir}/doc"),
+    ("_pkgdocdir", "%{_docdir}/%{name}"),
+    ("_licensedir", "%{_datadir}/licenses"),
+    ("_sysconfdir", "/etc"),
+    ("_initddir", "%{_sysconfdir}/rc.d/init.d"),
+    ("_localstatedir", "/var"),
+    ("_sharedstatedir", "/var/lib"),
+    ("_rundir", "/run"),
+    ("_unitdir", "/usr/lib/systemd/system"),
+    ("_userunitdir", "/usr/lib/systemd/user"),
+    ("_presetdir", "/usr/lib/systemd/system-preset"),
+    ("_tmpfilesdir", "/usr/lib/tmpfiles.d"),
+    ("_sysusersdir", "/usr/lib/sysusers.d"),
+    ("_udevrulesdir", "/usr/lib/udev/rules.d"),
+    (
+        "bash_completions_dir",
+        "%{_datadir}/bash-completion/completions",
+    ),
+    ("zsh_completions_dir", "%{_datadir}/zsh/site-functions"),
+    (
+        "fish_completions_dir",
+        "%{_datadir}/fish/vendor_completions.d",
+    ),
+];
+
+/// Architectures whose libraries are installed in `lib64`.
+const LIB64_ARCHES: &[&str] = &[
+    "x86_64",
+    "aarch64",
+    "ppc64",
+    "ppc64le",
+    "s390x",
+    "riscv64",
+    "loongarch64",
+    "sparc64",
+    "mips64",
+    "mips64el",
+];
+
+/// Keys whose values are not expanded: the definitions of the macros are expanded when used,
+/// and the subpackages and the variants are expanded once selected.
+const UNEXPANDED_KEYS: &[&str] = &["macros", "subpackages", "variants"];
+
+/// Macros which can also be written without `%` such as `{name}`.
+const BRACE_KEYS: &[&str] = &["name", "version", "release", "arch"];
+
+/// Limit of the nested expansions, which stops macros referring to themselves.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug)]
+pub(super) struct Macros {
+    macros: BTreeMap<String, String>,
+}
+
+impl Macros {
+    /// Creates the built-in macros for the `arch`.
+    pub(super) fn new(arch: &str) -> Self {
+        let mut macros = BTreeMap::new();
+        for (name, body) in BUILTIN_MACROS {
+            macros.insert(name.to_string(), body.to_string());
+        }
+        let lib = if LIB64_ARCHES.contains(&arch) {
+            "lib64"
+        } else {
+            "lib"
+        };
+        macros.insert("_lib".to_string(), lib.to_string());
+        macros.insert("arch".to_string(), arch.to_string());
+        Self { macros }
+    }
+
+    /// Defines the macro `name`, overriding the previous definition if any.
+    pub(super) fn define(&mut self, name: &str, body: &str) {
+        self.macros.insert(name.to_string(), body.to_string());
+    }
+
+    /// Expands the macros in `text`, the value of the field `key`.
+    ///
+    /// `%{name}` is replaced by the macro, `%{getenv:NAME}` by the environment variable, and
+    /// `%{?name}` or `%{?getenv:NAME}` by an empty string if undefined. `{name}`, `{version}`,
+    /// `{release}` and `{arch}` are the same as `%{name}` and so on, except `${name}` of the
+    /// shell. An undefined macro is left as is, as rpm does. `%%{` is kept as `%{`, and `{{name}}`
+    /// as `{name}`.
+    pub(super) fn expand(&self, text: &str, key: &str) -> Result<String, ConfigError> {
+        self.expand_nested(text, key, 0)
+    }
+
+    fn expand_nested(&self, text: &str, key: &str, depth: usize) -> Result<String, ConfigError> {
+        let mut expanded = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(pos) = rest.find('{') {
+            let (before, after) = rest.split_at(pos);
+            if let Some(escaped) = before.strip_suffix("%%") {
+                expanded.push_str(escaped);
+                expanded.push_str("%{");
+                rest = &after[1..];
+                continue;
+            }
+            if let Some(prefix) = before.strip_suffix('%') {
+                // an unterminated `%{` is left as is
+                let Some(end) = after.find('}') else {
+                    break;
+                };
+                expanded.push_str(prefix);
+                match self.expand_macro(&after[1..end], key, depth)? {
+                    Some(value) => expanded.push_str(&value),
+                    None => {
+                        expanded.push('%');
+                        expanded.push_str(&after[..=end]);
+                    }
+                }
+                rest = &after[end + 1..];
+                continue;
+            }
+
+            expanded.push_str(before);
+            if let Some(name) = after.strip_prefix('{').and_then(brace_key)
+                && after[name.len() + 3..].starts_with('}')
+            {
+                expanded.push('{');
+                expanded.push_str(name);
+                expanded.push('}');
+                rest = &after[name.len() + 4..];
+            } else if let Some(name) = brace_key(after)
+                && !before.ends_with('$')
+            {
+                match self.expand_macro(name, key, depth)? {
+                    Some(value) => expanded.push_str(&value),
+                    None => expanded.push_str(&after[..name.len() + 2]),
+                }
+                rest = &after[name.len() + 2..];
+            } else {
+                expanded.push('{');
+                rest = &after[1..];
+            }
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// Returns the expansion of the macro `body` such as `name` or `?getenv:NAME`, or `None` if
+    /// undefined.
+    fn expand_macro(
+        &self,
+        body: &str,
+        key: &str,
+        depth: usize,
+    ) -> Result<Option<String>, ConfigError> {
+        let (optional, name) = match body.strip_prefix('?') {
+            Some(name) => (true, name),
+            None => (false, body),
+        };
+        match self.lookup(name) {
+            Some(Lookup::Env(value)) => Ok(Some(value)),
+            Some(Lookup::Macro(_)) if depth >= MAX_DEPTH => Err(ConfigError::RecursiveMacro(
+                key.to_string(),
+                body.to_string(),
+            )),
+            Some(Lookup::Macro(value)) => Ok(Some(self.expand_nested(value, key, depth + 1)?)),
+            None if optional => Ok(Some(String::new())),
+            None => Ok(None),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Lookup<'_>> {
+        match name.strip_prefix("getenv:") {
+            Some(var) => std::env::var(var).ok().map(Lookup::Env),
+            None => self.macros.get(name).map(|v| Lookup::Macro(v.as_str())),
+        }
+    }
+
+    /// Expands the macros in the strings of `table`, the layer of the metadata at `key`.
+    pub(super) fn expand_table(
+        &self,
+        table: &Table,
+        key: Option<&str>,
+    ) -> Result<Table, ConfigError> {
+        let mut expanded = Table::new();
+        for (name, value) in table {
+            let value = if UNEXPANDED_KEYS.contains(&name.as_str()) {
+                value.clone()
+            } else {
+                let path = match key {
+                    Some(key) => format!("{key}.{name}"),
+                    None => name.clone(),
+                };
+                self.expand_value(value, &path)?
+            };
+            expanded.insert(name.clone(), value);
+        }
+        Ok(expanded)
+    }
+
+    fn expand_value(&self, value: &Value, key: &str) -> Result<Value, ConfigError> {
+        Ok(match value {
+            Value::String(v) => Value::String(self.expand(v, key)?),
+            Value::Array(v) => Value::Array(
+                v.iter()
+                    .enumerate()
+                    .map(|(idx, v)| self.expand_value(v, &format!("{key}[{idx}]")))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Table(v) => {
+                let mut expanded = Table::new();
+                for (name, v) in v {
+                    expanded.insert(
+                        name.clone(),
+                        self.expand_value(v, &format!("{key}.{name}"))?,
+                    );
+                }
+                Value::Table(expanded)
+            }
+            v => v.clone(),
+        })
+    }
+}
+
+/// Returns the name of `{name}` and so on at the start of `text`.
+fn brace_key(text: &str) -> Option<&'static str> {
+    BRACE_KEYS.iter().copied().find(|name| {
+        text.strip_prefix('{')
+            .and_then(|v| v.strip_prefix(name))
+            .is_some_and(|v| v.starts_with('}'))
+    })
+}
+
+enum Lookup<'a> {
+    Macro(&'a str),
+    Env(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let mut macros = Macros::new("x86_64");
+        macros.define("name", "foo");
+        macros.define("_unitdir", "/lib/systemd/system");
+        macros.define("loop", "%{loop}");
+
+        assert_eq!(
+            macros.expand("%{_bindir}/%{name}", "dest").unwrap(),
+            "/usr/bin/foo"
+        );
+        assert_eq!(macros.expand("%{_libdir}", "dest").unwrap(), "/usr/lib64");
+        assert_eq!(
+            Macros::new("armv7hl").expand("%{_libdir}", "dest").unwrap(),
+            "/usr/lib"
+        );
+        assert_eq!(
+            macros
+                .expand("%{_unitdir}/%{name}.service", "dest")
+                .unwrap(),
+            "/lib/systemd/system/foo.service"
+        );
+        assert_eq!(
+            macros.expand("%{_pkgdocdir}", "dest").unwrap(),
+            "/usr/share/doc/foo"
+        );
+        assert_eq!(macros.expand("a%{?not_defined}b", "dest").unwrap(), "ab");
+        assert_eq!(
+            macros
+                .expand(
+                    "printf '%s' %%{name} {{name}} ${name} {foo} ${x%{y",
+                    "script"
+                )
+                .unwrap(),
+            "printf '%s' %{name} {name} ${name} {foo} ${x%{y"
+        );
+        assert_eq!(
+            macros
+                .expand("/opt/{name}-{version}/{arch}", "dest")
+                .unwrap(),
+            "/opt/foo-{version}/x86_64"
+        );
+
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(macros.expand("%{getenv:PATH}", "dest").unwrap(), path);
+        assert_eq!(
+            macros
+                .expand("%{?getenv:NOT_DEFINED_ENV_VAR}", "dest")
+                .unwrap(),
+            ""
+        );
+        // undefined macros are left as is
+        assert_eq!(
+            macros
+                .expand("%{getenv:NOT_DEFINED_ENV_VAR}", "dest")
+                .unwrap(),
+            "%{getenv:NOT_DEFINED_ENV_VAR}"
+        );
+        assert_eq!(
+            macros.expand("%{_bindir}/%{not_defined}", "dest").unwrap(),
+            "/usr/bin/%{not_defined}"
+        );
+        assert!(matches!(
+            macros.expand("%{loop}", "dest"),
+            Err(ConfigError::RecursiveMacro(_, v)) if v == "loop"
+        ));
+    }
+
+    #[test]
+    fn test_expand_table() {
+        let mut macros = Macros::new("noarch");
+        macros.define("version", "1.2.3");
+        let table = r#"
+            assets = [{ source = "foo", dest = "%{_datadir}/foo/", mode = "644" }]
+            requires = { foo-data = "= %{version}" }
+            macros = { foo = "%{not_defined}" }
+            variants.bar = { version = "%{not_defined}" }
+        "#
+        .parse::<Table>()
+        .unwrap();
+        let expanded = macros
+            .expand_table(&table, Some("package.metadata.generate-rpm"))
+            .unwrap();
+        assert_eq!(
+            expanded["assets"][0]["dest"].as_str(),
+            Some("/usr/share/foo/")
+        );
+        assert_eq!(expanded["requires"]["foo-data"].as_str(), Some("= 1.2.3"));
+        assert_eq!(expanded["macros"], table["macros"]);
+        assert_eq!(expanded["variants"], table["variants"]);
+
+        let table = "requires = { foo = '= %{release}' }"
+            .parse::<Table>()
+            .unwrap();
+        let expanded = macros
+            .expand_table(&table, Some("package.metadata.generate-rpm"))
+            .unwrap();
+        assert_eq!(expanded["requires"]["foo"].as_str(), Some("= %{release}"));
+    }
+}
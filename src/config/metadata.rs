@@ -1,3 +1,4 @@
+use super::macros::Macros;
 use crate::Error;
 use crate::cli::ExtraMetadataSource;
 use crate::error::{ConfigError, FileAnnotatedError};
@@ -164,6 +165,12 @@ impl ExtraMetaData {
     }
 }
 
+const WORKSPACE_BRANCH_PATH: &str = "workspace.metadata.generate-rpm";
+
+/// Keys describing the package itself, which are not inherited from the workspace layer along
+/// with the scriptlets.
+const PACKAGE_KEYS: [&str; 5] = ["name", "summary", "description", "assets", "subpackages"];
+
 pub(super) struct MetadataConfig<'a> {
     metadata: &'a Table,
     branch_path: Option<String>,
@@ -177,6 +184,25 @@ impl<'a> MetadataConfig<'a> {
         }
     }
 
+    /// Creates the layer of `[workspace.metadata.generate-rpm]`, which gives the defaults of the
+    /// members.
+    pub fn new_from_workspace(metadata: &'a Table) -> Self {
+        Self::new(metadata, Some(WORKSPACE_BRANCH_PATH.to_string()))
+    }
+
+    fn is_workspace(&self) -> bool {
+        self.branch_path.as_deref() == Some(WORKSPACE_BRANCH_PATH)
+    }
+
+    /// Returns the value of `name`, except the keys of the package in the workspace layer.
+    fn value(&self, name: &str) -> Option<&'a Value> {
+        let is_package_key = PACKAGE_KEYS.contains(&name) || name.contains("_script");
+        match self.is_workspace() && is_package_key {
+            true => None,
+            false => self.metadata.get(name),
+        }
+    }
+
     pub fn new_from_extra_metadata(extra_metadata: &'a ExtraMetaData) -> Self {
         Self::new(
             &extra_metadata.0,
@@ -220,20 +246,49 @@ impl<'a> MetadataConfig<'a> {
         })
     }
 
-    fn create_config_error(&self, name: &str, type_name: &'static str) -> ConfigError {
-        let toml_path = self
-            .branch_path
+    /// Returns the names of the subpackages defined in `subpackages` table.
+    pub(super) fn subpackage_names(&self) -> Result<Vec<&'a str>, ConfigError> {
+        Ok(self
+            .get_table("subpackages")?
+            .map(|v| v.keys().map(String::as_str).collect())
+            .unwrap_or_default())
+    }
+
+    /// Returns the configuration of the subpackage `name` if defined in this layer.
+    pub(super) fn subpackage(&self, name: &str) -> Result<Option<Self>, ConfigError> {
+        let key = format!("subpackages.{name}");
+        match self.get_table("subpackages")?.and_then(|v| v.get(name)) {
+            Some(Value::Table(metadata)) => Ok(Some(Self {
+                metadata,
+                branch_path: Some(self.toml_path(&key)),
+            })),
+            Some(_) => Err(self.create_config_error(&key, "table")),
+            None => Ok(None),
+        }
+    }
+
+    fn expand_macros(&self, macros: &Macros) -> Result<ExpandedMetadata, ConfigError> {
+        Ok(ExpandedMetadata {
+            metadata: macros.expand_table(self.metadata, self.branch_path.as_deref())?,
+            branch_path: self.branch_path.clone(),
+        })
+    }
+
+    fn toml_path(&self, name: &str) -> String {
+        self.branch_path
             .as_ref()
             .map(|v| [v, name].join("."))
-            .unwrap_or(name.to_string());
-        ConfigError::WrongType(toml_path, type_name)
+            .unwrap_or(name.to_string())
+    }
+
+    fn create_config_error(&self, name: &str, type_name: &'static str) -> ConfigError {
+        ConfigError::WrongType(self.toml_path(name), type_name)
     }
 }
 
 impl<'a> TomlValueHelper<'a> for MetadataConfig<'a> {
     fn get_str(&self, name: &str) -> Result<Option<&'a str>, ConfigError> {
-        self.metadata
-            .get(name)
+        self.value(name)
             .map(|val| match val {
                 Value::String(v) => Ok(Some(v.as_str())),
                 _ => Err(self.create_config_error(name, "string")),
@@ -242,8 +297,7 @@ impl<'a> TomlValueHelper<'a> for MetadataConfig<'a> {
     }
 
     fn get_i64(&self, name: &str) -> Result<Option<i64>, ConfigError> {
-        self.metadata
-            .get(name)
+        self.value(name)
             .map(|val| match val {
                 Value::Integer(v) => Ok(Some(*v)),
                 _ => Err(self.create_config_error(name, "integer")),
@@ -252,8 +306,7 @@ impl<'a> TomlValueHelper<'a> for MetadataConfig<'a> {
     }
 
     fn get_string_or_i64(&self, name: &str) -> Result<Option<String>, ConfigError> {
-        self.metadata
-            .get(name)
+        self.value(name)
             .map(|val| match val {
                 Value::String(v) => Ok(Some(v.clone())),
                 Value::Integer(v) => Ok(Some(v.to_string())),
@@ -263,8 +316,7 @@ impl<'a> TomlValueHelper<'a> for MetadataConfig<'a> {
     }
 
     fn get_bool(&self, name: &str) -> Result<Option<bool>, ConfigError> {
-        self.metadata
-            .get(name)
+        self.value(name)
             .map(|val| match val {
                 Value::Boolean(v) => Ok(Some(*v)),
                 _ => Err(self.create_config_error(name, "bool")),
@@ -273,8 +325,7 @@ impl<'a> TomlValueHelper<'a> for MetadataConfig<'a> {
     }
 
     fn get_table(&self, name: &str) -> Result<Option<&'a Table>, ConfigError> {
-        self.metadata
-            .get(name)
+        self.value(name)
             .map(|val| match val {
                 Value::Table(v) => Ok(Some(v)),
                 _ => Err(self.create_config_error(name, "string or integer")),
@@ -283,8 +334,7 @@ impl<'a> TomlValueHelper<'a> for MetadataConfig<'a> {
     }
 
     fn get_array(&self, name: &str) -> Result<Option<&'a [Value]>, ConfigError> {
-        self.metadata
-            .get(name)
+        self.value(name)
             .map(|val| match val {
                 Value::Array(v) => Ok(Some(v.as_slice())),
                 _ => Err(self.create_config_error(name, "array")),
@@ -293,6 +343,18 @@ impl<'a> TomlValueHelper<'a> for MetadataConfig<'a> {
     }
 }
 
+/// Layer of the metadata whose macros are expanded.
+pub(super) struct ExpandedMetadata {
+    metadata: Table,
+    branch_path: Option<String>,
+}
+
+impl ExpandedMetadata {
+    pub(super) fn config(&self) -> MetadataConfig<'_> {
+        MetadataConfig::new(&self.metadata, self.branch_path.clone())
+    }
+}
+
 pub(super) struct CompoundMetadataConfig<'a> {
     config: &'a [MetadataConfig<'a>],
 }
@@ -315,6 +377,50 @@ impl<'a> CompoundMetadataConfig<'a> {
         Ok(None)
     }
 
+    /// Returns the table `name` merged into the one of the workspace layer, so that the keys of
+    /// the package are added to or override the defaults of the workspace.
+    pub(super) fn get_merged_table(&self, name: &str) -> Result<Option<Table>, ConfigError> {
+        let table = self.get_table(name)?;
+        let defaults = match self.config.iter().find(|v| v.is_workspace()) {
+            Some(workspace) => workspace.get_table(name)?,
+            None => None,
+        };
+        Ok(match (defaults, table) {
+            (Some(defaults), Some(table)) => {
+                let mut merged = defaults.clone();
+                merged.extend(table.clone());
+                Some(merged)
+            }
+            (_, table) => table.cloned(),
+        })
+    }
+
+    /// Returns the macros defined in the `macros` tables, where the upper layers override the
+    /// definitions of the lower ones.
+    pub(super) fn get_macros(&self) -> Result<Vec<(&'a str, &'a str)>, ConfigError> {
+        let mut macros = Vec::new();
+        for config in self.config {
+            for (name, body) in config.get_table("macros")?.into_iter().flatten() {
+                let body = body
+                    .as_str()
+                    .ok_or(config.create_config_error(&format!("macros.{name}"), "string"))?;
+                macros.push((name.as_str(), body));
+            }
+        }
+        Ok(macros)
+    }
+
+    /// Returns the layers with the `macros` expanded in their string values.
+    pub(super) fn expand_macros(
+        &self,
+        macros: &Macros,
+    ) -> Result<Vec<ExpandedMetadata>, ConfigError> {
+        self.config
+            .iter()
+            .map(|v| v.expand_macros(macros))
+            .collect()
+    }
+
     /// Returns a configured scriptlet,
     ///
     pub(super) fn get_scriptlet(
@@ -459,6 +565,95 @@ mod test {
         assert_eq!(metadata.get_i64("not-exist").unwrap(), None);
     }
 
+    #[test]
+    fn test_compound_metadata_config_merged_table() {
+        let workspace = toml! {
+            vendor = "foo"
+            requires = { bar = "*", baz = ">= 1" }
+        };
+        let package = toml! {
+            vendor = "qux"
+            requires = { baz = ">= 2", quux = "*" }
+            provides = { corge = "*" }
+        };
+        let metadata_config = [
+            MetadataConfig::new_from_workspace(&workspace),
+            MetadataConfig::new(&package, None),
+        ];
+        let metadata = CompoundMetadataConfig::new(&metadata_config);
+        assert_eq!(metadata.get_str("vendor").unwrap(), Some("qux"));
+        assert_eq!(
+            metadata.get_merged_table("requires").unwrap(),
+            Some(toml! {
+                bar = "*"
+                baz = ">= 2"
+                quux = "*"
+            })
+        );
+        assert_eq!(
+            metadata.get_merged_table("provides").unwrap(),
+            Some(toml! { corge = "*" })
+        );
+        assert_eq!(metadata.get_merged_table("conflicts").unwrap(), None);
+
+        // the tables of the layers other than the workspace are replaced
+        let metadata_config = [
+            MetadataConfig::new(&workspace, None),
+            MetadataConfig::new(&package, None),
+        ];
+        let metadata = CompoundMetadataConfig::new(&metadata_config);
+        assert_eq!(
+            metadata.get_merged_table("requires").unwrap(),
+            Some(toml! {
+                baz = ">= 2"
+                quux = "*"
+            })
+        );
+    }
+
+    #[test]
+    fn test_subpackage() {
+        let metadata = toml! {
+            summary = "main"
+            subpackages.devel = { summary = "devel" }
+            subpackages.doc = { summary = "doc" }
+        };
+        let metadata_config = MetadataConfig {
+            metadata: &metadata,
+            branch_path: Some("branch".to_string()),
+        };
+        assert_eq!(
+            metadata_config.subpackage_names().unwrap(),
+            ["devel", "doc"]
+        );
+
+        let devel = metadata_config.subpackage("devel").unwrap().unwrap();
+        assert_eq!(devel.get_str("summary").unwrap(), Some("devel"));
+        assert!(matches!(
+            devel.get_i64("summary"),
+            Err(ConfigError::WrongType(v, "integer")) if v == "branch.subpackages.devel.summary"
+        ));
+        assert!(metadata_config.subpackage("not-exist").unwrap().is_none());
+
+        let metadata = toml! {
+            subpackages.devel = "devel"
+        };
+        let metadata_config = MetadataConfig {
+            metadata: &metadata,
+            branch_path: None,
+        };
+        assert!(matches!(
+            metadata_config.subpackage("devel"),
+            Err(ConfigError::WrongType(v, "table")) if v == "subpackages.devel"
+        ));
+        assert!(
+            MetadataConfig::new(&Table::new(), None)
+                .subpackage_names()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn test_get_scriptlet_config() {
         let metadata = toml! {
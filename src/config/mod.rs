@@ -1,29 +1,129 @@
 use std::path::{Path, PathBuf};
 
-use cargo_toml::Error as CargoTomlError;
 use cargo_toml::Manifest;
-use rpm::Dependency;
+use rpm::signature::Signing;
+use rpm::{Dependency, FileFlags, Scriptlet};
 use toml::value::Table;
 
-use crate::auto_req::{AutoReqMode, find_requires};
-use crate::build_target::BuildTarget;
+use crate::auto_req::{AutoProvMode, AutoReqMode, find_provides, find_requires, is_elf};
+use crate::build_target::{BuildTarget, cargo_home};
+use crate::cargo_metadata::linked_packages;
 use crate::cli::{Cli, ExtraMetadataSource};
+use crate::debuginfo::{SplitDebuginfo, debug_file_paths, split_debuginfo};
 use crate::error::{ConfigError, Error};
+use crate::header::{
+    RPMTAG_BUGURL, RPMTAG_DISTRIBUTION, RPMTAG_GROUP, RPMTAG_PACKAGER, add_file_flags,
+    set_string_tags,
+};
+use cargo_lock::{LockedPackage, bundled_packages, bundled_provides, locked_packages};
+use changelog::changelog_entries;
+use dependency_filter::DependencyFilter;
+use description::{first_line, load_description_if_path, readme_description};
 use file_info::FileInfo;
-use metadata::{CompoundMetadataConfig, ExtraMetaData, MetadataConfig, TomlValueHelper};
-
+use license::{LicenseStyle, dependency_licenses, normalize_license};
+use macros::Macros;
+use metadata::{
+    CompoundMetadataConfig, ExpandedMetadata, ExtraMetaData, MetadataConfig, TomlValueHelper,
+};
+use version::{VersionScheme, validate_version};
+
+pub(crate) use changelog::format_date;
+
+mod cargo_lock;
+mod changelog;
+mod dependency_filter;
+mod description;
 mod file_info;
+mod license;
+mod macros;
 mod metadata;
+mod vcs;
+mod version;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct BuilderConfig<'a> {
     build_target: &'a BuildTarget,
     args: &'a Cli,
+    plan: bool,
 }
 
 impl<'a> BuilderConfig<'a> {
     pub fn new(build_target: &'a BuildTarget, args: &'a Cli) -> BuilderConfig<'a> {
-        BuilderConfig { build_target, args }
+        BuilderConfig {
+            build_target,
+            args,
+            plan: false,
+        }
+    }
+
+    /// Builds the plan of the package, whose files are left empty without reading the assets.
+    ///
+    /// The assets to be built into the target directory may be missing, which are not scanned
+    /// for the dependencies, and the debug info is not extracted.
+    pub fn plan(self) -> Self {
+        Self { plan: true, ..self }
+    }
+}
+
+/// Builder of the package, which adds the file flags and the tags `rpm::PackageBuilder` cannot
+/// set after building the package.
+pub struct PackageBuilder {
+    builder: rpm::PackageBuilder,
+    file_flags: Vec<(String, FileFlags)>,
+    string_tags: Vec<(u32, String)>,
+    source_date: Option<u32>,
+    sources: Vec<(PathBuf, String)>,
+    warnings: Vec<String>,
+    debuginfo: Option<Box<PackageBuilder>>,
+    debuginfo_enabled: bool,
+}
+
+impl PackageBuilder {
+    /// Returns the warnings on the configuration of the package, such as the deprecated
+    /// license identifiers.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Returns the pairs of the source path and the destination of the files read from disk.
+    pub fn sources(&self) -> &[(PathBuf, String)] {
+        &self.sources
+    }
+
+    /// Returns whether `debuginfo` is enabled, even if no debug info is found such as in the plan.
+    pub fn debuginfo_enabled(&self) -> bool {
+        self.debuginfo_enabled
+    }
+
+    /// Takes the builder of the `-debuginfo` package if `debuginfo` is enabled.
+    pub fn take_debuginfo(&mut self) -> Option<PackageBuilder> {
+        self.debuginfo.take().map(|v| *v)
+    }
+
+    pub fn build(self) -> Result<rpm::Package, Error> {
+        let mut pkg = self.builder.build()?;
+        if !self.file_flags.is_empty() {
+            pkg = add_file_flags(pkg, &self.file_flags)?;
+        }
+        if !self.string_tags.is_empty() {
+            pkg = set_string_tags(pkg, &self.string_tags)?;
+        }
+        Ok(pkg)
+    }
+
+    pub fn build_and_sign<S>(self, signer: S) -> Result<rpm::Package, Error>
+    where
+        S: Signing<Signature = Vec<u8>>,
+    {
+        // same as `rpm::PackageBuilder::build_and_sign`
+        let now = rpm::Timestamp::now();
+        let timestamp = match self.source_date.map(rpm::Timestamp) {
+            Some(t) if t < now => t,
+            _ => now,
+        };
+        let mut pkg = self.build()?;
+        pkg.sign_with_timestamp(signer, timestamp)?;
+        Ok(pkg)
     }
 }
 
@@ -31,46 +131,53 @@ impl<'a> BuilderConfig<'a> {
 pub struct Config {
     manifest: Manifest,
     manifest_path: PathBuf,
+    workspace_metadata: Option<Table>,
     extra_metadata: Vec<ExtraMetaData>,
 }
 
 impl Config {
+    /// Reads the manifest in `project_base_path`, which inherits from the workspace whose root is
+    /// `workspace_base_path` as given by `cargo metadata`. If `None`, the workspace is looked up
+    /// in the parent directories.
     pub fn new(
         project_base_path: &Path,
         workspace_base_path: Option<&Path>,
         extra_metadata_src: &[ExtraMetadataSource],
     ) -> Result<Self, Error> {
         let manifest_path = Self::create_cargo_toml_path(project_base_path)?;
-
-        let manifest = if let Some(p) = workspace_base_path {
-            // HACK when workspace used, manifest is generated from slice directly instead of
-            // `from_path_with_metadata`. Because it call `inherit_workspace`, which yields an error
-            // in case `edition.workspace = true` specified in the project manifest file.
-            // TODO future fix when https://gitlab.com/crates.rs/cargo_toml/-/issues/20 fixed
-
-            let cargo_toml_content = std::fs::read(&manifest_path)
-                .map_err(|e| Error::FileIo(manifest_path.clone(), e))?;
-            let mut manifest = Manifest::from_slice_with_metadata(&cargo_toml_content)?;
-
-            let workspace_manifest_path = Self::create_cargo_toml_path(p)?;
-            let workspace_manifest =
-                Manifest::from_path(&workspace_manifest_path).map_err(|err| match err {
-                    CargoTomlError::Io(e) => {
-                        Error::FileIo(workspace_manifest_path.to_path_buf(), e)
-                    }
-                    _ => Error::CargoToml(err),
-                })?;
-            manifest.complete_from_path_and_workspace(
-                manifest_path.as_path(),
-                Some((&workspace_manifest, p)),
-            )?;
-            manifest
-        } else {
-            Manifest::from_path(&manifest_path).map_err(|err| match err {
-                CargoTomlError::Io(e) => Error::FileIo(manifest_path.to_path_buf(), e),
-                _ => Error::CargoToml(err),
-            })?
+        let mut manifest = Self::read_manifest(&manifest_path)?;
+
+        let workspace_manifest = match workspace_base_path {
+            Some(p) => {
+                let workspace_manifest_path = Self::create_cargo_toml_path(p)?;
+                if workspace_manifest_path == manifest_path {
+                    None
+                } else {
+                    Some((Self::read_manifest(&workspace_manifest_path)?, p))
+                }
+            }
+            None => None,
         };
+        match &workspace_manifest {
+            Some((workspace_manifest, p)) => manifest
+                .complete_from_path_and_workspace(&manifest_path, Some((workspace_manifest, *p)))?,
+            None => manifest.complete_from_path(&manifest_path)?,
+        }
+
+        let workspace_metadata = workspace_manifest
+            .as_ref()
+            .map_or(&manifest, |(v, _)| v)
+            .workspace
+            .as_ref()
+            .and_then(|v| v.metadata.as_ref())
+            .and_then(|v| v.get("generate-rpm"))
+            .map(|v| {
+                v.as_table().cloned().ok_or(ConfigError::WrongType(
+                    "workspace.metadata.generate-rpm".to_string(),
+                    "table",
+                ))
+            })
+            .transpose()?;
 
         let extra_metadata = extra_metadata_src
             .iter()
@@ -80,10 +187,16 @@ impl Config {
         Ok(Config {
             manifest,
             manifest_path,
+            workspace_metadata,
             extra_metadata,
         })
     }
 
+    fn read_manifest(path: &Path) -> Result<Manifest, Error> {
+        let content = std::fs::read(path).map_err(|e| Error::FileIo(path.to_path_buf(), e))?;
+        Ok(Manifest::from_slice_with_metadata(&content)?)
+    }
+
     pub(crate) fn create_cargo_toml_path<P: AsRef<Path>>(base_path: P) -> Result<PathBuf, Error> {
         let path = base_path.as_ref().join("Cargo.toml");
         path.canonicalize().map_err(|e| Error::FileIo(path, e))
@@ -119,166 +232,385 @@ impl Config {
         Ok(dependencies)
     }
 
-    pub fn create_rpm_builder(&self, cfg: BuilderConfig) -> Result<rpm::PackageBuilder, Error> {
+    fn metadata_configs(&self) -> Result<Vec<MetadataConfig<'_>>, Error> {
         let mut metadata_config = Vec::new();
+        if let Some(workspace_metadata) = &self.workspace_metadata {
+            metadata_config.push(MetadataConfig::new_from_workspace(workspace_metadata));
+        }
         metadata_config.push(MetadataConfig::new_from_manifest(&self.manifest)?);
         for v in &self.extra_metadata {
             metadata_config.push(MetadataConfig::new_from_extra_metadata(v));
         }
-        let metadata = CompoundMetadataConfig::new(metadata_config.as_slice());
+        Ok(metadata_config)
+    }
 
+    /// Returns the layers of the metadata of the main package and the ones of the `subpackage`
+    /// with the macros expanded, where `%{name}`, `%{version}`, `%{release}` and `%{arch}` are
+    /// the ones of the main package.
+    fn expanded_metadata(
+        &self,
+        build_target: &BuildTarget,
+        subpackage: Option<&str>,
+    ) -> Result<(Vec<ExpandedMetadata>, Option<Vec<ExpandedMetadata>>), Error> {
+        let metadata_config = self.metadata_configs()?;
+        let common = CompoundMetadataConfig::new(metadata_config.as_slice());
         let pkg = self
             .manifest
             .package
             .as_ref()
             .ok_or(ConfigError::Missing("package".to_string()))?;
-        let name = metadata.get_str("name")?.unwrap_or(pkg.name.as_str());
-        let version = match metadata.get_str("version")? {
-            Some(v) => v,
-            None => pkg.version.get()?,
-        };
-        let license = match (metadata.get_str("license")?, pkg.license.as_ref()) {
-            (Some(v), _) => v,
-            (None, None) => Err(ConfigError::Missing("package.license".to_string()))?,
-            (None, Some(v)) => v.get()?,
-        };
-        let arch = cfg.build_target.binary_arch();
-        let desc = match (
-            metadata.get_str("summary")?,
-            metadata.get_str("description")?,
-            pkg.description.as_ref(),
-        ) {
-            (Some(v), _, _) => v,
-            (None, Some(v), _) => v,
-            (None, None, Some(v)) => v.get()?,
-            (None, None, None) => Err(ConfigError::Missing("package.description".to_string()))?,
-        };
-        let assets = metadata
-            .get_array("assets")?
-            .ok_or(ConfigError::Missing("package.assets".to_string()))?;
-        let files = FileInfo::new(assets)?;
-        let parent = self.manifest_path.parent().unwrap();
 
-        let build_config = rpm::BuildConfig::default().compression(cfg.args.payload_compress);
-        let mut builder = rpm::PackageBuilder::new(name, version, license, arch.as_str(), desc)
-            .using_config(build_config);
-        builder = if let Some(t) = cfg.args.source_date {
-            builder.source_date(t)
-        } else if let Ok(t) = std::env::var("SOURCE_DATE_EPOCH") {
-            let t = t
-                .parse::<u32>()
-                .map_err(|err| Error::EnvError("SOURCE_DATE_EPOCH", err.to_string()))?;
-            builder.source_date(t)
+        let arch = if common.get_bool("noarch")?.unwrap_or(false) {
+            "noarch".to_string()
         } else {
-            builder
+            build_target.binary_arch()
         };
-
-        let mut expanded_file_paths = vec![];
-        for (idx, file) in files.iter().enumerate() {
-            let entries = file.generate_rpm_file_entry(cfg.build_target, parent, idx)?;
-            for (file_source, options) in entries {
-                expanded_file_paths.push(file_source.clone());
-                builder = builder.with_file(file_source, options)?;
-            }
+        let mut macros = Macros::new(&arch);
+        for (name, body) in common.get_macros()? {
+            macros.define(name, body);
         }
+        // the values are expanded when used as the bodies of the macros
+        let (version, release_suffix) = Self::package_version(&common, pkg)?;
+        let release = Self::package_release(&common, release_suffix)?;
+        macros.define("name", common.get_str("name")?.unwrap_or(pkg.name.as_str()));
+        macros.define("version", &version);
+        macros.define("release", release.as_deref().unwrap_or("1"));
+
+        let subpackage_metadata = subpackage
+            .map(|name| {
+                let config = Self::subpackage_metadata_configs(&metadata_config, name)?;
+                Ok::<_, Error>(
+                    CompoundMetadataConfig::new(config.as_slice()).expand_macros(&macros)?,
+                )
+            })
+            .transpose()?;
+        Ok((common.expand_macros(&macros)?, subpackage_metadata))
+    }
 
-        if let Some(release) = metadata.get_string_or_i64("release")? {
-            builder = builder.release(release);
-        }
-        if let Some(epoch) = metadata.get_i64("epoch")? {
-            builder = builder.epoch(epoch as u32);
-        }
+    /// Returns the version, translated by the version scheme unless given in the metadata, and
+    /// the suffix of the release the translation may add.
+    fn package_version(
+        common: &CompoundMetadataConfig,
+        pkg: &cargo_toml::Package,
+    ) -> Result<(String, Option<String>), Error> {
+        let version_scheme = VersionScheme::new(common.get_str("version-scheme")?)?;
+        Ok(match common.get_str("version")? {
+            Some(v) => (v.to_string(), None),
+            None => version_scheme.translate(pkg.version.get()?),
+        })
+    }
 
-        if let Some(pre_install_script) = metadata.get_str("pre_install_script")? {
-            let scriptlet = metadata.get_scriptlet(
-                "pre_install_script",
-                load_script_if_path(pre_install_script, parent, cfg.build_target)?,
-            )?;
+    fn package_release(
+        common: &CompoundMetadataConfig,
+        release_suffix: Option<String>,
+    ) -> Result<Option<String>, Error> {
+        Ok(
+            match (common.get_string_or_i64("release")?, release_suffix) {
+                (Some(release), Some(suffix)) => Some(format!("{release}.{suffix}")),
+                (None, Some(suffix)) => Some(format!("1.{suffix}")),
+                (release, None) => release,
+            },
+        )
+    }
 
-            if let Some(scriptlet) = scriptlet {
-                builder = builder.pre_install_script(scriptlet);
+    /// Returns the names of the subpackages defined in `subpackages` tables.
+    pub fn subpackage_names(&self) -> Result<Vec<String>, Error> {
+        let mut names = Vec::<String>::new();
+        for config in self.metadata_configs()? {
+            for name in config.subpackage_names()? {
+                if !names.iter().any(|v| v == name) {
+                    names.push(name.to_string());
+                }
             }
         }
+        Ok(names)
+    }
 
-        if let Some(pre_uninstall_script) = metadata.get_str("pre_uninstall_script")? {
-            let scriptlet = metadata.get_scriptlet(
-                "pre_uninstall_script",
-                load_script_if_path(pre_uninstall_script, parent, cfg.build_target)?,
-            )?;
-
-            if let Some(scriptlet) = scriptlet {
-                builder = builder.pre_uninstall_script(scriptlet);
+    fn subpackage_metadata_configs<'b>(
+        metadata_config: &[MetadataConfig<'b>],
+        name: &str,
+    ) -> Result<Vec<MetadataConfig<'b>>, Error> {
+        let mut subpackage_config = Vec::new();
+        for config in metadata_config {
+            if let Some(config) = config.subpackage(name)? {
+                subpackage_config.push(config);
             }
         }
+        if subpackage_config.is_empty() {
+            Err(ConfigError::Missing(format!(
+                "package.metadata.generate-rpm.subpackages.{name}"
+            )))?;
+        }
+        Ok(subpackage_config)
+    }
 
-        if let Some(post_install_script) = metadata.get_str("post_install_script")? {
-            let scriptlet = metadata.get_scriptlet(
-                "post_install_script",
-                load_script_if_path(post_install_script, parent, cfg.build_target)?,
-            )?;
+    /// Returns the pairs of the source path and the destination of the files to be packaged
+    /// into the main package or the `subpackage`.
+    pub fn expand_assets(
+        &self,
+        build_target: &BuildTarget,
+        subpackage: Option<&str>,
+    ) -> Result<Vec<(PathBuf, String)>, Error> {
+        let (main_metadata, subpackage_metadata) =
+            self.expanded_metadata(build_target, subpackage)?;
+        let metadata_config = subpackage_metadata
+            .as_ref()
+            .unwrap_or(&main_metadata)
+            .iter()
+            .map(ExpandedMetadata::config)
+            .collect::<Vec<_>>();
+        let metadata = CompoundMetadataConfig::new(metadata_config.as_slice());
+        let assets =
+            metadata
+                .get_array("assets")?
+                .ok_or(ConfigError::Missing(Self::metadata_key_path(
+                    subpackage, "assets",
+                )))?;
+        let files = FileInfo::new(assets)?;
+        let parent = self.manifest_path.parent().unwrap();
 
-            if let Some(scriptlet) = scriptlet {
-                builder = builder.post_install_script(scriptlet);
+        let mut expanded_file_paths = vec![];
+        for (idx, file) in files.iter().enumerate() {
+            let entries = file.generate_rpm_file_entry(build_target, parent, idx)?;
+            for (file_source, file_dest, _) in entries {
+                if let Some(file_source) = file_source {
+                    expanded_file_paths.push((file_source, file_dest));
+                }
             }
         }
+        Ok(expanded_file_paths)
+    }
 
-        if let Some(post_uninstall_script) = metadata.get_str("post_uninstall_script")? {
-            let scriptlet = metadata.get_scriptlet(
-                "post_uninstall_script",
-                load_script_if_path(post_uninstall_script, parent, cfg.build_target)?,
-            )?;
-
-            if let Some(scriptlet) = scriptlet {
-                builder = builder.post_uninstall_script(scriptlet);
-            }
+    fn metadata_key_path(subpackage: Option<&str>, key: &str) -> String {
+        match subpackage {
+            Some(name) => format!("package.metadata.generate-rpm.subpackages.{name}.{key}"),
+            None => format!("package.{key}"),
         }
+    }
+
+    pub fn create_rpm_builder(&self, cfg: BuilderConfig) -> Result<PackageBuilder, Error> {
+        self.create_package_rpm_builder(cfg, None)
+    }
 
-        if let Some(pre_trans_script) = metadata.get_str("pre_trans_script")? {
-            let scriptlet = metadata.get_scriptlet(
-                "pre_trans_script",
-                load_script_if_path(pre_trans_script, parent, cfg.build_target)?,
-            )?;
+    /// Creates the builder of the `subpackage`, which requires the main package of the same
+    /// epoch, version and release.
+    pub fn create_subpackage_rpm_builder(
+        &self,
+        cfg: BuilderConfig,
+        subpackage: &str,
+    ) -> Result<PackageBuilder, Error> {
+        self.create_package_rpm_builder(cfg, Some(subpackage))
+    }
 
-            if let Some(scriptlet) = scriptlet {
-                builder = builder.pre_trans_script(scriptlet);
-            }
+    fn create_package_rpm_builder(
+        &self,
+        cfg: BuilderConfig,
+        subpackage: Option<&str>,
+    ) -> Result<PackageBuilder, Error> {
+        let (main_metadata, subpackage_metadata) =
+            self.expanded_metadata(cfg.build_target, subpackage)?;
+        let metadata_config = main_metadata
+            .iter()
+            .map(ExpandedMetadata::config)
+            .collect::<Vec<_>>();
+        let subpackage_config = subpackage_metadata
+            .as_ref()
+            .map(|v| v.iter().map(ExpandedMetadata::config).collect::<Vec<_>>());
+        // `common` holds the configuration shared with the subpackages such as the version,
+        // while `metadata` holds the one of the package to be built.
+        let common = CompoundMetadataConfig::new(metadata_config.as_slice());
+        let metadata = match &subpackage_config {
+            Some(config) => CompoundMetadataConfig::new(config.as_slice()),
+            None => CompoundMetadataConfig::new(metadata_config.as_slice()),
+        };
+        let pkg = self
+            .manifest
+            .package
+            .as_ref()
+            .ok_or(ConfigError::Missing("package".to_string()))?;
+        let main_name = common.get_str("name")?.unwrap_or(pkg.name.as_str());
+        let name = match (subpackage, metadata.get_str("name")?) {
+            (None, _) => main_name.to_string(),
+            (Some(_), Some(v)) => v.to_string(),
+            (Some(suffix), None) => format!("{main_name}-{suffix}"),
+        };
+        let (version, release_suffix) = Self::package_version(&common, pkg)?;
+        validate_version("version", &version)?;
+        let release = Self::package_release(&common, release_suffix)?;
+        if let Some(release) = &release {
+            validate_version("release", release)?;
         }
+        let parent = self.manifest_path.parent().unwrap();
+        let (license, warnings) =
+            self.package_license(&cfg, &common, &metadata, pkg, subpackage)?;
+        let binary_arch = cfg.build_target.binary_arch();
+        let arch = if metadata.get_bool("noarch")?.unwrap_or(false) {
+            "noarch".to_string()
+        } else {
+            binary_arch.clone()
+        };
+        let description = match (
+            metadata.get_str("description")?,
+            pkg.readme.get()?.as_path(),
+        ) {
+            (Some(v), _) => Some(load_description_if_path(v, parent)?),
+            (None, Some(readme)) if subpackage.is_none() => readme_description(readme, parent),
+            (None, _) => None,
+        };
+        let summary = match (
+            metadata.get_str("summary")?,
+            pkg.description.as_ref(),
+            description.as_deref().and_then(first_line),
+        ) {
+            (Some(v), _, _) => v,
+            (None, Some(v), _) if subpackage.is_none() => v.get()?,
+            (None, _, Some(v)) => v,
+            (None, _, None) => Err(ConfigError::Missing(Self::metadata_key_path(
+                subpackage,
+                "description",
+            )))?,
+        };
+        let assets =
+            metadata
+                .get_array("assets")?
+                .ok_or(ConfigError::Missing(Self::metadata_key_path(
+                    subpackage, "assets",
+                )))?;
+        let files = FileInfo::new(assets)?;
 
-        if let Some(post_trans_script) = metadata.get_str("post_trans_script")? {
-            let scriptlet = metadata.get_scriptlet(
-                "post_trans_script",
-                load_script_if_path(post_trans_script, parent, cfg.build_target)?,
-            )?;
-
-            if let Some(scriptlet) = scriptlet {
-                builder = builder.post_trans_script(scriptlet);
-            }
+        let header = CommonHeader {
+            version: &version,
+            license: &license,
+            release: release.as_deref(),
+            epoch: common.get_i64("epoch")?.map(|v| v as u32),
+            source_date: source_date(cfg.args)?,
+            build_config: rpm::BuildConfig::default().compression(cfg.args.payload_compress),
+        };
+        let mut builder = header.builder(&name, &arch, summary);
+        if let Some(description) = description {
+            builder = builder.description(description);
         }
+        let debuginfo_enabled = metadata
+            .get_bool("debuginfo")?
+            .or(common.get_bool("debuginfo")?)
+            .unwrap_or(false);
+        let (mut builder, files) = match cfg.plan {
+            true => plan_files(builder, &files, cfg.build_target, parent)?,
+            false => add_files(builder, &files, cfg.build_target, parent, debuginfo_enabled)?,
+        };
 
-        if let Some(pre_untrans_script) = metadata.get_str("pre_untrans_script")? {
-            let scriptlet = metadata.get_scriptlet(
-                "pre_untrans_script",
-                load_script_if_path(pre_untrans_script, parent, cfg.build_target)?,
-            )?;
+        if subpackage.is_some() {
+            let evr = format!(
+                "{}{version}-{}",
+                header.epoch.map(|v| format!("{v}:")).unwrap_or_default(),
+                header.release.unwrap_or("1"),
+            );
+            builder = builder.requires(Dependency::eq(main_name, evr));
+        }
 
-            if let Some(scriptlet) = scriptlet {
-                builder = builder.pre_untrans_script(scriptlet);
-            }
+        for entry in changelog_entries(&common, parent, pkg.authors.get()?)? {
+            builder = builder.add_changelog_entry(entry.name, entry.text, entry.time);
         }
 
-        if let Some(post_untrans_script) = metadata.get_str("post_untrans_script")? {
-            let scriptlet = metadata.get_scriptlet(
-                "post_untrans_script",
-                load_script_if_path(post_untrans_script, parent, cfg.build_target)?,
-            )?;
+        let builder = add_scriptlets(builder, &metadata, parent, cfg.build_target)?;
+        let (builder, string_tags) = self.add_header_tags(builder, &common, &metadata, pkg)?;
+        let builder = self.add_dependencies(builder, &cfg, &metadata, &files.sources)?;
+        let debuginfo = header
+            .debuginfo_builder(&name, &binary_arch, files.debug_files)?
+            .map(Box::new);
+
+        Ok(PackageBuilder {
+            builder,
+            file_flags: files.file_flags,
+            string_tags,
+            source_date: header.source_date,
+            sources: files.sources,
+            warnings,
+            debuginfo,
+            debuginfo_enabled,
+        })
+    }
 
-            if let Some(scriptlet) = scriptlet {
-                builder = builder.post_untrans_script(scriptlet);
+    /// Returns the license of the package, normalized by `license-style` and combined with the
+    /// ones of the dependencies if `aggregate-licenses`, and the warnings on it.
+    fn package_license(
+        &self,
+        cfg: &BuilderConfig,
+        common: &CompoundMetadataConfig,
+        metadata: &CompoundMetadataConfig,
+        pkg: &cargo_toml::Package,
+        subpackage: Option<&str>,
+    ) -> Result<(String, Vec<String>), Error> {
+        let license = match (
+            metadata.get_str("license")?,
+            common.get_str("license")?,
+            pkg.license.as_ref(),
+        ) {
+            (Some(v), _, _) => v,
+            (None, Some(v), _) => v,
+            (None, None, None) => Err(ConfigError::Missing("package.license".to_string()))?,
+            (None, None, Some(v)) => v.get()?,
+        };
+        let aggregate = metadata
+            .get_bool("aggregate-licenses")?
+            .or(common.get_bool("aggregate-licenses")?)
+            .unwrap_or(false);
+        let license_style = LicenseStyle::new(
+            metadata
+                .get_str("license-style")?
+                .or(common.get_str("license-style")?),
+            aggregate,
+        )?;
+        let mut warnings = Vec::new();
+        let dependency_licenses = if aggregate && license_style != LicenseStyle::Verbatim {
+            let cargo_home = cargo_home().ok_or(ConfigError::Missing("CARGO_HOME".to_string()))?;
+            let (licenses, missing) =
+                dependency_licenses(&self.bundled_packages(cfg)?, &cargo_home);
+            for name in missing {
+                warnings.push(format!("license of {name} not found in the registry cache"));
             }
+            licenses
+        } else {
+            Vec::new()
+        };
+        let (license, license_warnings) =
+            normalize_license(license, &dependency_licenses, license_style)?;
+        warnings.extend(license_warnings);
+        // the subpackages inheriting the license do not repeat the warnings
+        if subpackage.is_some() && metadata.get_str("license")?.is_none() {
+            warnings.clear();
         }
+        Ok((license, warnings))
+    }
+
+    /// Returns the non-local crates of `Cargo.lock` linked into the binaries of the package.
+    fn bundled_packages(&self, cfg: &BuilderConfig) -> Result<Vec<LockedPackage>, Error> {
+        let linked = linked_packages(
+            &self.manifest_path,
+            cfg.build_target.target(),
+            &cfg.args.feature_args(),
+            cfg.args.locked,
+        )?;
+        let parent = self.manifest_path.parent().unwrap();
+        Ok(bundled_packages(locked_packages(parent)?, &linked))
+    }
+
+    /// Adds the URL, the vendor, the build host and the VCS to `builder`, and returns the string
+    /// tags `rpm::PackageBuilder` cannot set.
+    fn add_header_tags(
+        &self,
+        mut builder: rpm::PackageBuilder,
+        common: &CompoundMetadataConfig,
+        metadata: &CompoundMetadataConfig,
+        pkg: &cargo_toml::Package,
+    ) -> Result<(rpm::PackageBuilder, Vec<(u32, String)>), Error> {
+        let parent = self.manifest_path.parent().unwrap();
+        let inherited_str = |key: &str| -> Result<Option<&str>, ConfigError> {
+            Ok(metadata.get_str(key)?.or(common.get_str(key)?))
+        };
 
         if let Some(url) = match (
-            metadata.get_str("url")?,
+            inherited_str("url")?,
             pkg.homepage.as_ref(),
             pkg.repository.as_ref(),
         ) {
@@ -290,16 +622,67 @@ impl Config {
             builder = builder.url(url);
         }
 
-        if let Some(vendor) = metadata.get_str("vendor")? {
+        if let Some(vendor) = inherited_str("vendor")? {
             builder = builder.vendor(vendor);
         }
 
+        if let Some(build_host) = inherited_str("build-host")? {
+            builder = builder.build_host(build_host);
+        }
+
+        let repository = pkg.repository.as_ref().map(|v| v.get()).transpose()?;
+        if let Some(vcs) = match (inherited_str("vcs")?, repository) {
+            (Some(v), _) => Some(v.to_string()),
+            (None, Some(v)) => vcs::vcs(v, parent),
+            (None, None) => None,
+        } {
+            builder = builder.vcs(vcs);
+        }
+
+        let mut string_tags = Vec::new();
+        if let Some(group) = inherited_str("group")? {
+            string_tags.push((RPMTAG_GROUP, group.to_string()));
+        }
+        if let Some(packager) = match (inherited_str("packager")?, pkg.authors.get()?.first()) {
+            (Some(v), _) => Some(v),
+            (None, v) => v.map(String::as_str),
+        } {
+            string_tags.push((RPMTAG_PACKAGER, packager.to_string()));
+        }
+        if let Some(distribution) = inherited_str("distribution")? {
+            string_tags.push((RPMTAG_DISTRIBUTION, distribution.to_string()));
+        }
+        if let Some(bug_url) = match (inherited_str("bug-url")?, repository) {
+            (Some(v), _) => Some(v.to_string()),
+            (None, Some(v)) => vcs::bug_url(v),
+            (None, None) => None,
+        } {
+            string_tags.push((RPMTAG_BUGURL, bug_url));
+        }
+        Ok((builder, string_tags))
+    }
+
+    /// Adds the dependencies given in the metadata and the ones discovered automatically in the
+    /// `sources`.
+    fn add_dependencies(
+        &self,
+        mut builder: rpm::PackageBuilder,
+        cfg: &BuilderConfig,
+        metadata: &CompoundMetadataConfig,
+        sources: &[(PathBuf, String)],
+    ) -> Result<rpm::PackageBuilder, Error> {
+        // the outputs of the build not yet run are missing in the plan
+        let sources = sources
+            .iter()
+            .filter(|(v, _)| !cfg.plan || v.exists())
+            .cloned()
+            .collect::<Vec<_>>();
         if metadata.get_bool("require-sh")?.unwrap_or(true) {
             builder = builder.requires(Dependency::any("/bin/sh".to_string()));
         }
 
-        if let Some(requires) = metadata.get_table("requires")? {
-            for dependency in Self::table_to_dependencies(requires)? {
+        if let Some(requires) = metadata.get_merged_table("requires")? {
+            for dependency in Self::table_to_dependencies(&requires)? {
                 builder = builder.requires(dependency);
             }
         }
@@ -310,46 +693,300 @@ impl Config {
             (v, _) => AutoReqMode::from(v.clone()),
         };
 
-        for requires in find_requires(expanded_file_paths, auto_req)? {
-            builder = builder.requires(Dependency::any(requires));
+        let auto_req_filter = DependencyFilter::new(metadata, "auto-req")?;
+        let auto_req_files = auto_req_filter.scanned_files(&sources);
+        for requires in find_requires(auto_req_files, auto_req)? {
+            if auto_req_filter.is_accepted(&requires) {
+                builder = builder.requires(Dependency::any(requires));
+            }
         }
-        if let Some(obsoletes) = metadata.get_table("obsoletes")? {
-            for dependency in Self::table_to_dependencies(obsoletes)? {
-                builder = builder.obsoletes(dependency);
+
+        let meta_auto_prov = metadata.get_str("auto-prov")?;
+        let auto_prov = match (&cfg.args.auto_prov, meta_auto_prov) {
+            (crate::cli::AutoProvMode::Auto, Some("no" | "disabled")) => AutoProvMode::Disabled,
+            (v, _) => AutoProvMode::from(v.clone()),
+        };
+
+        let auto_prov_filter = DependencyFilter::new(metadata, "auto-prov")?;
+        let auto_prov_files = auto_prov_filter.scanned_files(&sources);
+        for provides in find_provides(auto_prov_files, auto_prov)? {
+            if auto_prov_filter.is_accepted(&provides) {
+                builder = builder.provides(Dependency::any(provides));
             }
         }
-        if let Some(conflicts) = metadata.get_table("conflicts")? {
-            for dependency in Self::table_to_dependencies(conflicts)? {
-                builder = builder.conflicts(dependency);
+        if metadata.get_bool("bundled-provides")?.unwrap_or(false) {
+            for provides in bundled_provides(&self.bundled_packages(cfg)?) {
+                builder = builder.provides(provides);
             }
         }
-        if let Some(provides) = metadata.get_table("provides")? {
-            for dependency in Self::table_to_dependencies(provides)? {
-                builder = builder.provides(dependency);
+
+        for (key, add_dependency) in DEPENDENCY_TABLES {
+            if let Some(table) = metadata.get_merged_table(key)? {
+                for dependency in Self::table_to_dependencies(&table)? {
+                    builder = add_dependency(builder, dependency);
+                }
             }
         }
-        if let Some(recommends) = metadata.get_table("recommends")? {
-            for dependency in Self::table_to_dependencies(recommends)? {
-                builder = builder.recommends(dependency);
+        Ok(builder)
+    }
+}
+
+/// Method of `rpm::PackageBuilder` adding the value of `T`.
+type BuilderSetter<T> = fn(rpm::PackageBuilder, T) -> rpm::PackageBuilder;
+
+/// Tables of the dependencies other than `requires`, and the setters of the builder.
+const DEPENDENCY_TABLES: [(&str, BuilderSetter<Dependency>); 7] = [
+    ("obsoletes", |b, v| b.obsoletes(v)),
+    ("conflicts", |b, v| b.conflicts(v)),
+    ("provides", |b, v| b.provides(v)),
+    ("recommends", |b, v| b.recommends(v)),
+    ("supplements", |b, v| b.supplements(v)),
+    ("suggests", |b, v| b.suggests(v)),
+    ("enhances", |b, v| b.enhances(v)),
+];
+
+/// Keys of the scriptlets, and the setters of the builder.
+const SCRIPTLETS: [(&str, BuilderSetter<Scriptlet>); 8] = [
+    ("pre_install_script", |b, v| b.pre_install_script(v)),
+    ("pre_uninstall_script", |b, v| b.pre_uninstall_script(v)),
+    ("post_install_script", |b, v| b.post_install_script(v)),
+    ("post_uninstall_script", |b, v| b.post_uninstall_script(v)),
+    ("pre_trans_script", |b, v| b.pre_trans_script(v)),
+    ("post_trans_script", |b, v| b.post_trans_script(v)),
+    ("pre_untrans_script", |b, v| b.pre_untrans_script(v)),
+    ("post_untrans_script", |b, v| b.post_untrans_script(v)),
+];
+
+/// Header values shared by the package and its debuginfo package.
+struct CommonHeader<'a> {
+    version: &'a str,
+    license: &'a str,
+    release: Option<&'a str>,
+    epoch: Option<u32>,
+    source_date: Option<u32>,
+    build_config: rpm::BuildConfig,
+}
+
+impl CommonHeader<'_> {
+    fn builder(&self, name: &str, arch: &str, summary: &str) -> rpm::PackageBuilder {
+        let mut builder = rpm::PackageBuilder::new(name, self.version, self.license, arch, summary)
+            .using_config(self.build_config);
+        if let Some(t) = self.source_date {
+            builder = builder.source_date(t);
+        }
+        if let Some(release) = self.release {
+            builder = builder.release(release);
+        }
+        if let Some(epoch) = self.epoch {
+            builder = builder.epoch(epoch);
+        }
+        builder
+    }
+
+    /// Creates the builder of the `-debuginfo` package of the package `name` containing the
+    /// `debug_files`, or `None` if there is no debug info.
+    fn debuginfo_builder(
+        &self,
+        name: &str,
+        arch: &str,
+        debug_files: Vec<DebugFile>,
+    ) -> Result<Option<PackageBuilder>, Error> {
+        if debug_files.is_empty() {
+            return Ok(None);
+        }
+        let summary = format!("Debug information for package {name}");
+        let mut builder = self.builder(&format!("{name}-debuginfo"), arch, &summary);
+        for DebugFile {
+            dest,
+            debug,
+            build_id,
+        } in debug_files
+        {
+            let (debug_path, links) = debug_file_paths(&dest, build_id.as_deref());
+            let options = rpm::FileOptions::new(debug_path).mode(0o100644);
+            builder = builder.with_file_contents(debug, options)?;
+            for (link, target) in links {
+                let options = rpm::FileOptions::new(link).symlink(&target).mode(0o120777);
+                builder = builder.with_file_contents(target.into_bytes(), options)?;
+            }
+            if let Some(build_id) = build_id {
+                builder = builder.provides(Dependency::eq("debuginfo(build-id)", build_id));
             }
         }
-        if let Some(supplements) = metadata.get_table("supplements")? {
-            for dependency in Self::table_to_dependencies(supplements)? {
-                builder = builder.supplements(dependency);
+        Ok(Some(PackageBuilder {
+            builder,
+            file_flags: Vec::new(),
+            string_tags: Vec::new(),
+            source_date: self.source_date,
+            sources: Vec::new(),
+            warnings: Vec::new(),
+            debuginfo: None,
+            debuginfo_enabled: false,
+        }))
+    }
+}
+
+/// Debug info split from the ELF file installed at `dest`.
+struct DebugFile {
+    dest: String,
+    debug: Vec<u8>,
+    build_id: Option<String>,
+}
+
+/// Files added to the package by `add_files`.
+struct PackageFiles {
+    /// Pairs of the source path and the destination of the files read from disk
+    sources: Vec<(PathBuf, String)>,
+    file_flags: Vec<(String, FileFlags)>,
+    debug_files: Vec<DebugFile>,
+}
+
+/// Adds the files of the assets to `builder`, stripping the ELF files into the debug info if
+/// `debuginfo`.
+fn add_files(
+    mut builder: rpm::PackageBuilder,
+    files: &[FileInfo],
+    build_target: &BuildTarget,
+    parent: &Path,
+    debuginfo: bool,
+) -> Result<(rpm::PackageBuilder, PackageFiles), Error> {
+    let mut sources = vec![];
+    let mut file_flags = vec![];
+    let mut debug_files = vec![];
+    for (idx, file) in files.iter().enumerate() {
+        let entries = file.generate_rpm_file_entry(build_target, parent, idx)?;
+        for (file_source, file_dest, options) in entries {
+            if !file.extra_flags().is_empty() {
+                file_flags.push((file_dest.clone(), file.extra_flags()));
+            }
+            let Some(file_source) = file_source else {
+                builder = builder.with_file_contents(file.contents(), options)?;
+                continue;
+            };
+            sources.push((file_source.clone(), file_dest.clone()));
+            let split = match debuginfo && is_elf(&file_source) {
+                true => {
+                    let data = std::fs::read(&file_source)
+                        .map_err(|e| Error::FileIo(file_source.clone(), e))?;
+                    split_debuginfo(&data, &file_dest)
+                        .map_err(|e| Error::Debuginfo(file_source.clone(), e))?
+                }
+                false => None,
+            };
+            if let Some(SplitDebuginfo {
+                stripped,
+                debug,
+                build_id,
+            }) = split
+            {
+                // unlike `with_file`, the permissions are not inherited from the file
+                let file = FileInfo {
+                    mode: file.mode.or(file_mode(&file_source)),
+                    ..file.clone()
+                };
+                let options = file.generate_rpm_file_options(&file_dest, idx)?;
+                builder = builder.with_file_contents(stripped, options)?;
+                debug_files.push(DebugFile {
+                    dest: file_dest,
+                    debug,
+                    build_id,
+                });
+            } else {
+                builder = builder.with_file(file_source, options)?;
             }
         }
-        if let Some(suggests) = metadata.get_table("suggests")? {
-            for dependency in Self::table_to_dependencies(suggests)? {
-                builder = builder.suggests(dependency);
+    }
+    Ok((
+        builder,
+        PackageFiles {
+            sources,
+            file_flags,
+            debug_files,
+        },
+    ))
+}
+
+/// Adds the files of the assets to `builder` with the empty contents, which are planned to be
+/// packaged once built if missing.
+fn plan_files(
+    mut builder: rpm::PackageBuilder,
+    files: &[FileInfo],
+    build_target: &BuildTarget,
+    parent: &Path,
+) -> Result<(rpm::PackageBuilder, PackageFiles), Error> {
+    let mut sources = vec![];
+    let mut file_flags = vec![];
+    for (idx, file) in files.iter().enumerate() {
+        let entries = file.generate_planned_rpm_file_entry(build_target, parent, idx)?;
+        for (file_source, file_dest, options) in entries {
+            if !file.extra_flags().is_empty() {
+                file_flags.push((file_dest.clone(), file.extra_flags()));
             }
+            let Some(file_source) = file_source else {
+                builder = builder.with_file_contents(file.contents(), options)?;
+                continue;
+            };
+            // the outputs of the build not yet run are usually executables
+            let file = FileInfo {
+                mode: file.mode.or(file_mode(&file_source)).or(Some(0o100755)),
+                ..file.clone()
+            };
+            let options = file.generate_rpm_file_options(&file_dest, idx)?;
+            builder = builder.with_file_contents(Vec::new(), options)?;
+            sources.push((file_source, file_dest));
         }
-        if let Some(enhances) = metadata.get_table("enhances")? {
-            for dependency in Self::table_to_dependencies(enhances)? {
-                builder = builder.enhances(dependency);
+    }
+    Ok((
+        builder,
+        PackageFiles {
+            sources,
+            file_flags,
+            debug_files: Vec::new(),
+        },
+    ))
+}
+
+/// Adds the scriptlets, each of which is either inline or the path to the script.
+fn add_scriptlets(
+    mut builder: rpm::PackageBuilder,
+    metadata: &CompoundMetadataConfig,
+    parent: &Path,
+    build_target: &BuildTarget,
+) -> Result<rpm::PackageBuilder, Error> {
+    for (key, add_scriptlet) in SCRIPTLETS {
+        if let Some(script) = metadata.get_str(key)? {
+            let content = load_script_if_path(script, parent, build_target)?;
+            if let Some(scriptlet) = metadata.get_scriptlet(key, content)? {
+                builder = add_scriptlet(builder, scriptlet);
             }
         }
+    }
+    Ok(builder)
+}
 
-        Ok(builder)
+#[cfg(unix)]
+fn file_mode(path: &Path) -> Option<usize> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = std::fs::metadata(path).ok()?;
+    Some(metadata.permissions().mode() as usize)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_path: &Path) -> Option<usize> {
+    None
+}
+
+/// Returns the timestamp specified by `--source-date` or `SOURCE_DATE_EPOCH`.
+pub(crate) fn source_date(args: &Cli) -> Result<Option<u32>, Error> {
+    if let Some(t) = args.source_date {
+        Ok(Some(t))
+    } else if let Ok(t) = std::env::var("SOURCE_DATE_EPOCH") {
+        let t = t
+            .parse::<u32>()
+            .map_err(|err| Error::EnvError("SOURCE_DATE_EPOCH", err.to_string()))?;
+        Ok(Some(t))
+    } else {
+        Ok(None)
     }
 }
 
@@ -362,10 +999,10 @@ pub(crate) fn load_script_if_path<P: AsRef<Path>>(
 
     if Path::new(&relpath).exists() {
         return std::fs::read_to_string(relpath);
-    } else if let Some(p) = parent.as_ref().join(&relpath).to_str() {
-        if Path::new(&p).exists() {
-            return std::fs::read_to_string(p);
-        }
+    } else if let Some(p) = parent.as_ref().join(&relpath).to_str()
+        && Path::new(&p).exists()
+    {
+        return std::fs::read_to_string(p);
     }
 
     Ok(asset.to_string())
@@ -384,10 +1021,10 @@ mod test {
         assert_eq!(pkg.name, "cargo-generate-rpm");
 
         assert!(matches!(Config::new(Path::new("not_exist_dir"), None, &[]),
-            Err(Error::FileIo(path, error)) if path == PathBuf::from("not_exist_dir/Cargo.toml") && error.kind() == std::io::ErrorKind::NotFound));
+            Err(Error::FileIo(path, error)) if path == Path::new("not_exist_dir/Cargo.toml") && error.kind() == std::io::ErrorKind::NotFound));
         assert!(
             matches!(Config::new(Path::new(""), Some(Path::new("not_exist_dir")), &[]),
-            Err(Error::FileIo(path, error)) if path == PathBuf::from("not_exist_dir/Cargo.toml") && error.kind() == std::io::ErrorKind::NotFound)
+            Err(Error::FileIo(path, error)) if path == Path::new("not_exist_dir/Cargo.toml") && error.kind() == std::io::ErrorKind::NotFound)
         );
     }
 
@@ -410,6 +1047,16 @@ version = "1.2.3"
 authors = ["Nice Folks"]
 description = "A short description of my package"
 documentation = "https://example.com/bar"
+edition = "2021"
+
+[workspace.metadata.generate-rpm]
+vendor = "Nice Folks"
+name = "workspace"
+assets = [{ source = "README.md", dest = "/usr/share/doc/workspace/README.md" }]
+post_install_script = "echo workspace"
+
+[workspace.metadata.generate-rpm.subpackages.doc]
+assets = []
         "#,
         )
         .unwrap();
@@ -423,23 +1070,36 @@ version.workspace = true
 authors.workspace = true
 description.workspace = true
 documentation.workspace = true
+edition.workspace = true
+
+[package.metadata.generate-rpm]
+assets = []
         "#,
         )
         .unwrap();
 
         let config =
             Config::new(project_dir.as_path(), Some(workspace_dir.as_path()), &[]).unwrap();
+        let metadata_config = config.metadata_configs().unwrap();
+        let metadata = CompoundMetadataConfig::new(&metadata_config);
+        assert_eq!(metadata.get_str("vendor").unwrap(), Some("Nice Folks"));
+        // the keys of the package itself are not inherited
+        assert_eq!(metadata.get_str("name").unwrap(), None);
+        assert_eq!(metadata.get_array("assets").unwrap(), Some(&[][..]));
+        assert_eq!(metadata.get_str("post_install_script").unwrap(), None);
+        assert!(config.subpackage_names().unwrap().is_empty());
         let pkg = config.manifest.package.unwrap();
         assert_eq!(pkg.name, "bar");
         assert_eq!(pkg.version.get().unwrap(), "1.2.3");
+        assert_eq!(pkg.edition.get().unwrap(), &cargo_toml::Edition::E2021);
 
         assert!(
             matches!(Config::new(Path::new("not_exist_dir"), Some(workspace_dir.as_path()), &[]),
-            Err(Error::FileIo(path, error)) if path == PathBuf::from("not_exist_dir/Cargo.toml") && error.kind() == std::io::ErrorKind::NotFound)
+            Err(Error::FileIo(path, error)) if path == Path::new("not_exist_dir/Cargo.toml") && error.kind() == std::io::ErrorKind::NotFound)
         );
         assert!(
             matches!(Config::new(project_dir.as_path(), Some(Path::new("not_exist_dir")), &[]),
-            Err(Error::FileIo(path, error)) if path == PathBuf::from("not_exist_dir/Cargo.toml") && error.kind() == std::io::ErrorKind::NotFound)
+            Err(Error::FileIo(path, error)) if path == Path::new("not_exist_dir/Cargo.toml") && error.kind() == std::io::ErrorKind::NotFound)
         );
     }
 
@@ -553,13 +1213,38 @@ documentation.workspace = true
         ));
     }
 
+    #[test]
+    fn test_config_expand_assets() {
+        let config = Config::new(Path::new("."), None, &[]).unwrap();
+        let args = crate::cli::Cli {
+            ..Default::default()
+        };
+        let target = BuildTarget::from_args(&args);
+        let assets = config.expand_assets(&target, None);
+
+        if Path::new("target/release/cargo-generate-rpm").exists() {
+            let assets = assets.unwrap();
+            assert_eq!(
+                assets.last().unwrap(),
+                &(
+                    PathBuf::from("README.md"),
+                    "/usr/share/doc/cargo-generate-rpm/README.md".to_string()
+                )
+            );
+        } else {
+            assert!(
+                matches!(assets, Err(Error::Config(ConfigError::AssetFileNotFound(path))) if path.to_str() == Some("target/release/cargo-generate-rpm"))
+            );
+        }
+    }
+
     #[test]
     fn test_config_create_rpm_builder() {
         let config = Config::new(Path::new("."), None, &[]).unwrap();
         let args = crate::cli::Cli {
             ..Default::default()
         };
-        let target = BuildTarget::new(&args);
+        let target = BuildTarget::from_args(&args);
         let cfg = BuilderConfig::new(&target, &args);
         let builder = config.create_rpm_builder(cfg);
 
@@ -569,4 +1254,348 @@ documentation.workspace = true
             matches!(builder, Err(Error::Config(ConfigError::AssetFileNotFound(path))) if path.to_str() == Some("target/release/cargo-generate-rpm"))
         });
     }
+
+    #[test]
+    fn test_config_create_subpackage_rpm_builder() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo.h"), "").unwrap();
+        std::fs::write(
+            tempdir.path().join("Cargo.toml"),
+            r#"
+[package]
+name = "foo"
+version = "1.2.0"
+license = "MIT"
+description = "foo"
+
+[package.metadata.generate-rpm]
+epoch = 1
+release = 2
+assets = []
+
+[package.metadata.generate-rpm.subpackages.devel]
+summary = "foo development files"
+assets = [{ source = "foo.h", dest = "/usr/include/foo.h" }]
+require-sh = false
+noarch = true
+
+[package.metadata.generate-rpm.subpackages.doc]
+name = "foo-documents"
+        "#,
+        )
+        .unwrap();
+
+        let config = Config::new(tempdir.path(), None, &[]).unwrap();
+        assert_eq!(config.subpackage_names().unwrap(), ["devel", "doc"]);
+
+        let args = crate::cli::Cli {
+            auto_req: crate::cli::AutoReqMode::Disabled,
+            ..Default::default()
+        };
+        let target = BuildTarget::from_args(&args);
+        let pkg = config
+            .create_subpackage_rpm_builder(BuilderConfig::new(&target, &args), "devel")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pkg.metadata.get_name().unwrap(), "foo-devel");
+        assert_eq!(pkg.metadata.get_summary().unwrap(), "foo development files");
+        assert_eq!(pkg.metadata.get_license().unwrap(), "MIT");
+        assert_eq!(pkg.metadata.get_epoch().unwrap(), 1);
+        assert_eq!(pkg.metadata.get_release().unwrap(), "2");
+        assert_eq!(pkg.metadata.get_arch().unwrap(), "noarch");
+        assert!(
+            pkg.metadata
+                .get_requires()
+                .unwrap()
+                .contains(&Dependency::eq("foo", "1:1.2.0-2"))
+        );
+        assert!(
+            !pkg.metadata
+                .get_requires()
+                .unwrap()
+                .contains(&Dependency::any("/bin/sh"))
+        );
+
+        assert!(matches!(
+            config.create_subpackage_rpm_builder(BuilderConfig::new(&target, &args), "doc"),
+            Err(Error::Config(ConfigError::Missing(v))) if v == "package.metadata.generate-rpm.subpackages.doc.description"
+        ));
+        assert!(matches!(
+            config.create_subpackage_rpm_builder(BuilderConfig::new(&target, &args), "not-exist"),
+            Err(Error::Config(ConfigError::Missing(v))) if v == "package.metadata.generate-rpm.subpackages.not-exist"
+        ));
+    }
+
+    #[test]
+    fn test_config_expand_macros() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("foo.service"), "").unwrap();
+        std::fs::write(
+            tempdir.path().join("Cargo.toml"),
+            r#"
+[package]
+name = "foo"
+version = "1.2.0"
+license = "MIT"
+description = "foo"
+
+[package.metadata.generate-rpm]
+release = "3"
+noarch = true
+assets = [
+    { source = "foo.service", dest = "%{_unitdir}/%{name}.service", mode = "644" },
+    { source = "*.service", dest = "%{foo_confdir}/{filename}", mode = "644" },
+]
+post_install_script = "echo %{name}-%{version}-%{release}.%{arch} %{_undefined} ${name}"
+requires = { foo-data = "= {version}-{release}" }
+macros = { _unitdir = "/lib/systemd/system", foo_confdir = "%{_sysconfdir}/%{name}" }
+
+[package.metadata.generate-rpm.subpackages.data]
+description = "foo data"
+assets = [{ source = "foo.service", dest = "%{_datadir}/%{undefined}", mode = "644" }]
+        "#,
+        )
+        .unwrap();
+
+        let config = Config::new(tempdir.path(), None, &[]).unwrap();
+        let args = crate::cli::Cli {
+            auto_req: crate::cli::AutoReqMode::Disabled,
+            ..Default::default()
+        };
+        let target = BuildTarget::from_args(&args);
+        let pkg = config
+            .create_rpm_builder(BuilderConfig::new(&target, &args))
+            .unwrap()
+            .build()
+            .unwrap();
+        let paths = pkg.metadata.get_file_paths().unwrap();
+        assert!(paths.contains(&PathBuf::from("/lib/systemd/system/foo.service")));
+        assert!(paths.contains(&PathBuf::from("/etc/foo/foo.service")));
+        assert_eq!(
+            pkg.metadata.get_post_install_script().unwrap().script,
+            "echo foo-1.2.0-3.noarch %{_undefined} ${name}"
+        );
+        assert!(
+            pkg.metadata
+                .get_requires()
+                .unwrap()
+                .contains(&Dependency::eq("foo-data", "1.2.0-3"))
+        );
+
+        let pkg = config
+            .create_subpackage_rpm_builder(BuilderConfig::new(&target, &args), "data")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            pkg.metadata.get_file_paths().unwrap(),
+            vec![PathBuf::from("/usr/share/%{undefined}")]
+        );
+    }
+
+    #[test]
+    fn test_config_summary_and_description() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tempdir.path().join("README.md"),
+            "# foo\n\nFoo is a tool\nto do foo.\n\n## Usage\n",
+        )
+        .unwrap();
+        std::fs::write(tempdir.path().join("DESCRIPTION"), "Foo data files.\n").unwrap();
+        std::fs::write(
+            tempdir.path().join("Cargo.toml"),
+            r#"
+[package]
+name = "foo"
+version = "1.2.0"
+license = "MIT"
+description = "A tool to do foo"
+
+[package.metadata.generate-rpm]
+assets = []
+
+[package.metadata.generate-rpm.subpackages.data]
+description = "DESCRIPTION"
+assets = []
+
+[package.metadata.generate-rpm.subpackages.devel]
+summary = "foo development files"
+description = """
+Headers of foo.
+
+Install it to build with foo.
+"""
+assets = []
+        "#,
+        )
+        .unwrap();
+
+        let config = Config::new(tempdir.path(), None, &[]).unwrap();
+        let args = crate::cli::Cli {
+            auto_req: crate::cli::AutoReqMode::Disabled,
+            ..Default::default()
+        };
+        let target = BuildTarget::from_args(&args);
+        let pkg = config
+            .create_rpm_builder(BuilderConfig::new(&target, &args))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pkg.metadata.get_summary().unwrap(), "A tool to do foo");
+        assert_eq!(
+            pkg.metadata.get_description().unwrap(),
+            "Foo is a tool\nto do foo."
+        );
+
+        let pkg = config
+            .create_subpackage_rpm_builder(BuilderConfig::new(&target, &args), "data")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pkg.metadata.get_summary().unwrap(), "Foo data files.");
+        assert_eq!(pkg.metadata.get_description().unwrap(), "Foo data files.\n");
+
+        let pkg = config
+            .create_subpackage_rpm_builder(BuilderConfig::new(&target, &args), "devel")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pkg.metadata.get_summary().unwrap(), "foo development files");
+        assert_eq!(
+            pkg.metadata.get_description().unwrap(),
+            "Headers of foo.\n\nInstall it to build with foo.\n"
+        );
+    }
+
+    #[test]
+    fn test_config_header_tags() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tempdir.path().join("Cargo.toml"),
+            r#"
+[package]
+name = "foo"
+version = "1.2.0"
+license = "MIT"
+description = "foo"
+authors = ["Foo <foo@example.com>"]
+repository = "https://github.com/foo/foo"
+
+[package.metadata.generate-rpm]
+assets = []
+group = "Applications/System"
+distribution = "Foo Linux"
+build-host = "build.example.com"
+vcs = "git+https://github.com/foo/foo#0123456789abcdef0123456789abcdef01234567"
+
+[package.metadata.generate-rpm.subpackages.devel]
+summary = "foo development files"
+assets = []
+packager = "Bar <bar@example.com>"
+bug-url = "https://example.com/foo/bugs"
+        "#,
+        )
+        .unwrap();
+
+        let config = Config::new(tempdir.path(), None, &[]).unwrap();
+        let args = crate::cli::Cli {
+            auto_req: crate::cli::AutoReqMode::Disabled,
+            ..Default::default()
+        };
+        let target = BuildTarget::from_args(&args);
+        let pkg = config
+            .create_rpm_builder(BuilderConfig::new(&target, &args))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pkg.metadata.get_group().unwrap(), "Applications/System");
+        assert_eq!(
+            pkg.metadata.get_packager().unwrap(),
+            "Foo <foo@example.com>"
+        );
+        assert_eq!(pkg.metadata.get_build_host().unwrap(), "build.example.com");
+        assert_eq!(
+            pkg.metadata.get_vcs().unwrap(),
+            "git+https://github.com/foo/foo#0123456789abcdef0123456789abcdef01234567"
+        );
+        assert_eq!(
+            crate::header::string_tag(&pkg.metadata, RPMTAG_DISTRIBUTION).unwrap(),
+            Some("Foo Linux".to_string())
+        );
+        assert_eq!(
+            crate::header::string_tag(&pkg.metadata, RPMTAG_BUGURL).unwrap(),
+            Some("https://github.com/foo/foo/issues".to_string())
+        );
+
+        let pkg = config
+            .create_subpackage_rpm_builder(BuilderConfig::new(&target, &args), "devel")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pkg.metadata.get_group().unwrap(), "Applications/System");
+        assert_eq!(
+            pkg.metadata.get_packager().unwrap(),
+            "Bar <bar@example.com>"
+        );
+        assert_eq!(
+            crate::header::string_tag(&pkg.metadata, RPMTAG_BUGURL).unwrap(),
+            Some("https://example.com/foo/bugs".to_string())
+        );
+    }
+
+    #[test]
+    fn test_config_license() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tempdir.path().join("Cargo.toml"),
+            r#"
+[package]
+name = "foo"
+version = "1.2.0"
+license = "MIT/Apache-2.0"
+description = "foo"
+
+[package.metadata.generate-rpm]
+assets = []
+
+[package.metadata.generate-rpm.subpackages.data]
+summary = "foo data"
+assets = []
+license = "Public Domain"
+
+[package.metadata.generate-rpm.subpackages.devel]
+summary = "foo development files"
+assets = []
+license-style = "fedora"
+        "#,
+        )
+        .unwrap();
+
+        let config = Config::new(tempdir.path(), None, &[]).unwrap();
+        let args = crate::cli::Cli {
+            auto_req: crate::cli::AutoReqMode::Disabled,
+            ..Default::default()
+        };
+        let target = BuildTarget::from_args(&args);
+        let cfg = || BuilderConfig::new(&target, &args);
+        let builder = config.create_rpm_builder(cfg()).unwrap();
+        assert!(builder.warnings().is_empty());
+        let pkg = builder.build().unwrap();
+        assert_eq!(pkg.metadata.get_license().unwrap(), "MIT/Apache-2.0");
+
+        let pkg = config
+            .create_subpackage_rpm_builder(cfg(), "data")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pkg.metadata.get_license().unwrap(), "Public Domain");
+
+        let pkg = config
+            .create_subpackage_rpm_builder(cfg(), "devel")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pkg.metadata.get_license().unwrap(), "MIT or ASL 2.0");
+    }
 }
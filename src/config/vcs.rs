@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use super::changelog::git;
+
+/// Forges whose issue trackers are derived from the repository URL, with the path to append.
+const FORGE_ISSUES: &[(&str, &str)] = &[
+    ("https://github.com/", "/issues"),
+    ("https://gitlab.com/", "/-/issues"),
+    ("https://codeberg.org/", "/issues"),
+];
+
+/// Returns the issue tracker of the `repository` hosted on a well-known forge.
+pub(super) fn bug_url(repository: &str) -> Option<String> {
+    let repository = repository.trim_end_matches('/');
+    let repository = repository.strip_suffix(".git").unwrap_or(repository);
+    FORGE_ISSUES
+        .iter()
+        .find(|(forge, _)| repository.starts_with(forge))
+        .map(|(_, issues)| format!("{repository}{issues}"))
+}
+
+/// Returns the `VCS` tag `git+<repository>#<commit>` of the git checkout containing `dir`, or
+/// `None` unless `dir` is in a git checkout.
+pub(super) fn vcs(repository: &str, dir: &Path) -> Option<String> {
+    let commit = git_commit(dir)?;
+    let repository = repository.trim_end_matches('/');
+    Some(
+        match repository.starts_with("git+") || repository.starts_with("git://") {
+            true => format!("{repository}#{commit}"),
+            false => format!("git+{repository}#{commit}"),
+        },
+    )
+}
+
+/// Returns the commit checked out in the git repository containing `dir`.
+fn git_commit(dir: &Path) -> Option<String> {
+    let commit = git(dir, &["rev-parse", "--verify", "-q", "HEAD"]).ok()?;
+    let commit = commit.trim();
+    (!commit.is_empty()).then(|| commit.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bug_url() {
+        assert_eq!(
+            bug_url("https://github.com/foo/bar.git").as_deref(),
+            Some("https://github.com/foo/bar/issues")
+        );
+        assert_eq!(
+            bug_url("https://gitlab.com/foo/bar/").as_deref(),
+            Some("https://gitlab.com/foo/bar/-/issues")
+        );
+        assert_eq!(bug_url("https://example.com/foo/bar"), None);
+    }
+
+    #[test]
+    fn test_vcs() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = tempdir.path().join("repo");
+        std::fs::create_dir_all(repo.join("crates/foo")).unwrap();
+        git(&repo, &["init", "-q"]).unwrap();
+        // no commit yet
+        assert_eq!(vcs("https://github.com/foo/bar", &repo), None);
+
+        git(
+            &repo,
+            &[
+                "-c",
+                "user.name=Foo",
+                "-c",
+                "user.email=foo@example.com",
+                "-c",
+                "commit.gpgsign=false",
+                "commit",
+                "--allow-empty",
+                "-m",
+                "Initial commit",
+            ],
+        )
+        .unwrap();
+        let commit = git(&repo, &["rev-parse", "HEAD"]).unwrap();
+        let commit = commit.trim();
+        assert_eq!(
+            vcs("https://github.com/foo/bar", &repo.join("crates/foo")),
+            Some(format!("git+https://github.com/foo/bar#{commit}"))
+        );
+        assert_eq!(
+            vcs("git+https://example.com/foo.git", &repo),
+            Some(format!("git+https://example.com/foo.git#{commit}"))
+        );
+
+        assert_eq!(vcs("https://example.com/foo", tempdir.path()), None);
+    }
+}
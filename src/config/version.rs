@@ -0,0 +1,148 @@
+use crate::error::ConfigError;
+
+/// How the Cargo (semver) version is translated into the RPM version and release
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) enum VersionScheme {
+    /// `1.2.0-rc.1+git.abc` to version `1.2.0~rc.1^git.abc`
+    #[default]
+    Semver,
+    /// `1.2.0-rc.1+git.abc` to version `1.2.0~rc.1` and release suffix `.git.abc`
+    SemverRelease,
+    /// Use the version as it is
+    Verbatim,
+}
+
+impl VersionScheme {
+    pub(super) fn new(value: Option<&str>) -> Result<Self, ConfigError> {
+        match value {
+            None | Some("semver") => Ok(Self::Semver),
+            Some("semver-release") => Ok(Self::SemverRelease),
+            Some("verbatim") => Ok(Self::Verbatim),
+            Some(_) => Err(ConfigError::WrongType(
+                "version-scheme".to_string(),
+                "\"semver\", \"semver-release\" or \"verbatim\"",
+            )),
+        }
+    }
+
+    /// Returns the RPM version and the suffix to be appended to the release.
+    pub(super) fn translate(&self, version: &str) -> (String, Option<String>) {
+        if *self == Self::Verbatim {
+            return (version.to_string(), None);
+        }
+
+        let (version, build) = match version.split_once('+') {
+            Some((v, build)) => (v, Some(build.replace('-', "_"))),
+            None => (version, None),
+        };
+        let mut rpm_version = match version.split_once('-') {
+            Some((core, pre)) => format!("{core}~{}", pre.replace('-', "_")),
+            None => version.to_string(),
+        };
+        match (self, build) {
+            (Self::Semver, Some(build)) => {
+                rpm_version.push('^');
+                rpm_version.push_str(&build);
+                (rpm_version, None)
+            }
+            (_, build) => (rpm_version, build),
+        }
+    }
+}
+
+/// Verifies that `value` of the version or release `field` is acceptable for rpm.
+pub(super) fn validate_version(field: &str, value: &str) -> Result<(), ConfigError> {
+    let is_valid = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '+' | '~' | '^'));
+    if is_valid {
+        Ok(())
+    } else {
+        Err(ConfigError::InvalidVersion(
+            field.to_string(),
+            value.to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_version_scheme_new() {
+        assert_eq!(VersionScheme::new(None).unwrap(), VersionScheme::Semver);
+        assert_eq!(
+            VersionScheme::new(Some("semver")).unwrap(),
+            VersionScheme::Semver
+        );
+        assert_eq!(
+            VersionScheme::new(Some("semver-release")).unwrap(),
+            VersionScheme::SemverRelease
+        );
+        assert_eq!(
+            VersionScheme::new(Some("verbatim")).unwrap(),
+            VersionScheme::Verbatim
+        );
+        assert!(matches!(
+            VersionScheme::new(Some("unknown")),
+            Err(ConfigError::WrongType(v, _)) if v == "version-scheme"
+        ));
+    }
+
+    #[test]
+    fn test_version_scheme_translate() {
+        let tests = [
+            ("1.2.0", "1.2.0", ("1.2.0", None)),
+            ("1.2.0-rc.1", "1.2.0~rc.1", ("1.2.0~rc.1", None)),
+            ("1.2.0-alpha-1", "1.2.0~alpha_1", ("1.2.0~alpha_1", None)),
+            ("1.2.0+git.abc", "1.2.0^git.abc", ("1.2.0", Some("git.abc"))),
+            (
+                "1.2.0-rc.1+git.abc",
+                "1.2.0~rc.1^git.abc",
+                ("1.2.0~rc.1", Some("git.abc")),
+            ),
+            (
+                "1.2.0-rc.1+build-5",
+                "1.2.0~rc.1^build_5",
+                ("1.2.0~rc.1", Some("build_5")),
+            ),
+        ];
+        for (version, semver, (semver_release, suffix)) in tests {
+            assert_eq!(
+                VersionScheme::Semver.translate(version),
+                (semver.to_string(), None),
+                "{version}"
+            );
+            assert_eq!(
+                VersionScheme::SemverRelease.translate(version),
+                (semver_release.to_string(), suffix.map(str::to_string)),
+                "{version}"
+            );
+            assert_eq!(
+                VersionScheme::Verbatim.translate(version),
+                (version.to_string(), None)
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_version() {
+        assert!(validate_version("version", "1.2.0").is_ok());
+        assert!(validate_version("version", "1.2.0~rc.1^git.abc").is_ok());
+        assert!(validate_version("release", "1.el9_3").is_ok());
+        assert!(matches!(
+            validate_version("version", "1.2.0-rc.1"),
+            Err(ConfigError::InvalidVersion(f, v)) if f == "version" && v == "1.2.0-rc.1"
+        ));
+        assert!(matches!(
+            validate_version("release", "1 2"),
+            Err(ConfigError::InvalidVersion(f, _)) if f == "release"
+        ));
+        assert!(matches!(
+            validate_version("version", ""),
+            Err(ConfigError::InvalidVersion(_, _))
+        ));
+    }
+}
@@ -0,0 +1,507 @@
+//! Separation of the debug information of ELF files like `find-debuginfo` of rpm does.
+//!
+//! An ELF file is split into the stripped one and the debug file, which is equivalent to
+//! `objcopy --only-keep-debug`. The stripped file refers to the debug file by the
+//! `.gnu_debuglink` section. The sections are re-laid out without `objcopy`.
+
+use elf::abi::{SHF_ALLOC, SHF_INFO_LINK, SHT_NOBITS, SHT_NOTE, SHT_PROGBITS};
+use elf::endian::{AnyEndian, EndianParse};
+use elf::file::Class;
+use elf::note::Note;
+use elf::section::SectionHeader;
+use elf::{ElfBytes, ParseError};
+use std::path::Path;
+
+/// The directory where the debug files are installed
+pub(crate) const DEBUG_DIR: &str = "/usr/lib/debug";
+
+/// The result of splitting an ELF file
+#[derive(Debug)]
+pub(crate) struct SplitDebuginfo {
+    /// The ELF file without the debug information, linked to `debug`
+    pub(crate) stripped: Vec<u8>,
+    /// The debug file to be installed as `/usr/lib/debug/<path>.debug`
+    pub(crate) debug: Vec<u8>,
+    /// The GNU build ID in hex, if any
+    pub(crate) build_id: Option<String>,
+}
+
+/// How a section is written into the output ELF file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SectionAction {
+    Keep,
+    Remove,
+    /// Keep the header only, as `SHT_NOBITS`
+    NoBits,
+}
+
+/// Returns whether the section is removed by stripping, i.e. the debug information and symbols.
+fn is_debug_section(name: &str, shdr: &SectionHeader) -> bool {
+    shdr.sh_flags & SHF_ALLOC as u64 == 0
+        && (name.starts_with(".debug_")
+            || name.starts_with(".zdebug_")
+            || matches!(name, ".symtab" | ".strtab" | ".gnu_debuglink"))
+}
+
+/// Splits the ELF file `data` installed as `dest` into the stripped file and the debug file.
+///
+/// Returns `None` if there is nothing to be stripped, or it is neither an executable nor a
+/// shared object.
+pub(crate) fn split_debuginfo(
+    data: &[u8],
+    dest: &str,
+) -> Result<Option<SplitDebuginfo>, ParseError> {
+    let elf = ElfBytes::<AnyEndian>::minimal_parse(data)?;
+    if !matches!(elf.ehdr.e_type, elf::abi::ET_EXEC | elf::abi::ET_DYN) {
+        return Ok(None);
+    }
+    let (Some(shdrs), Some(strtab)) = elf.section_headers_with_strtab()? else {
+        return Ok(None);
+    };
+    let sections = shdrs
+        .iter()
+        .map(|shdr| Ok((strtab.get(shdr.sh_name as usize)?, shdr)))
+        .collect::<Result<Vec<_>, ParseError>>()?;
+    // the link to the debug file does not make it unstripped
+    if !sections
+        .iter()
+        .any(|(name, shdr)| is_debug_section(name, shdr) && *name != ".gnu_debuglink")
+    {
+        return Ok(None);
+    }
+
+    let build_id = sections
+        .iter()
+        .filter(|(_, shdr)| shdr.sh_type == SHT_NOTE)
+        .find_map(|(_, shdr)| {
+            elf.section_data_as_notes(shdr)
+                .ok()?
+                .find_map(|note| match note {
+                    Note::GnuBuildId(id) => Some(id.0.iter().map(|v| format!("{v:02x}")).collect()),
+                    _ => None,
+                })
+        });
+
+    let writer = ElfWriter::new(&elf, data, &sections);
+    let debug_actions = sections
+        .iter()
+        .map(
+            |(_, shdr)| match shdr.sh_flags & SHF_ALLOC as u64 != 0 && shdr.sh_type != SHT_NOTE {
+                true => SectionAction::NoBits,
+                false => SectionAction::Keep,
+            },
+        )
+        .collect::<Vec<_>>();
+    let debug = writer.write(&debug_actions, None, false);
+
+    let debug_file_name = Path::new(dest)
+        .file_name()
+        .and_then(|v| v.to_str())
+        .map(|v| format!("{v}.debug"))
+        .unwrap_or_default();
+    let mut debuglink = debug_file_name.into_bytes();
+    debuglink.push(0);
+    debuglink.resize(debuglink.len().next_multiple_of(4), 0);
+    let mut crc = flate2::Crc::new();
+    crc.update(&debug);
+    debuglink.extend(writer.u32_bytes(crc.sum()));
+
+    let strip_actions = sections
+        .iter()
+        .map(|(name, shdr)| match is_debug_section(name, shdr) {
+            true => SectionAction::Remove,
+            false => SectionAction::Keep,
+        })
+        .collect::<Vec<_>>();
+    let stripped = writer.write(&strip_actions, Some((".gnu_debuglink", &debuglink)), true);
+
+    Ok(Some(SplitDebuginfo {
+        stripped,
+        debug,
+        build_id,
+    }))
+}
+
+/// Returns the debug file path and the `.build-id` symbolic links to the file installed at
+/// `dest` and its debug file, as the pairs of the link path and the target.
+pub(crate) fn debug_file_paths(
+    dest: &str,
+    build_id: Option<&str>,
+) -> (String, Vec<(String, String)>) {
+    let debug_path = format!("{DEBUG_DIR}{dest}.debug");
+    let links = match build_id {
+        Some(id) if id.len() > 2 => {
+            let link = format!("{DEBUG_DIR}/.build-id/{}/{}", &id[..2], &id[2..]);
+            vec![
+                (link.clone(), format!("../../../../..{dest}")),
+                (format!("{link}.debug"), format!("../..{dest}.debug")),
+            ]
+        }
+        _ => Vec::new(),
+    };
+    (debug_path, links)
+}
+
+/// Writer of the ELF file with the sections of the original file re-laid out
+struct ElfWriter<'a> {
+    data: &'a [u8],
+    class: Class,
+    endian: AnyEndian,
+    sections: &'a [(&'a str, SectionHeader)],
+    shstrndx: usize,
+    phoff: usize,
+    phentsize: usize,
+    phnum: usize,
+    /// The end of the ELF header and the program header table
+    headers_end: usize,
+    /// The end of the contents loaded into the memory
+    loaded_end: usize,
+}
+
+impl<'a> ElfWriter<'a> {
+    fn new(
+        elf: &ElfBytes<'a, AnyEndian>,
+        data: &'a [u8],
+        sections: &'a [(&'a str, SectionHeader)],
+    ) -> Self {
+        let ehdr = &elf.ehdr;
+        let headers_end = (ehdr.e_ehsize as u64)
+            .max(ehdr.e_phoff + ehdr.e_phnum as u64 * ehdr.e_phentsize as u64)
+            as usize;
+        let segments_end = elf
+            .segments()
+            .into_iter()
+            .flatten()
+            .map(|v| v.p_offset + v.p_filesz)
+            .max()
+            .unwrap_or_default();
+        let sections_end = sections
+            .iter()
+            .filter(|(_, v)| v.sh_flags & SHF_ALLOC as u64 != 0 && v.sh_type != SHT_NOBITS)
+            .map(|(_, v)| v.sh_offset + v.sh_size)
+            .max()
+            .unwrap_or_default();
+        let loaded_end = (segments_end.max(sections_end) as usize)
+            .max(headers_end)
+            .min(data.len());
+        Self {
+            data,
+            class: ehdr.class,
+            endian: ehdr.endianness,
+            sections,
+            shstrndx: ehdr.e_shstrndx as usize,
+            phoff: ehdr.e_phoff as usize,
+            phentsize: ehdr.e_phentsize as usize,
+            phnum: ehdr.e_phnum as usize,
+            headers_end,
+            loaded_end,
+        }
+    }
+
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        match self.endian.is_little() {
+            true => value.to_le_bytes(),
+            false => value.to_be_bytes(),
+        }
+    }
+
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        match self.endian.is_little() {
+            true => value.to_le_bytes(),
+            false => value.to_be_bytes(),
+        }
+    }
+
+    /// Returns the bytes of the address or the offset, whose width depends on the class.
+    fn addr_bytes(&self, value: u64) -> Vec<u8> {
+        match (self.class, self.endian.is_little()) {
+            (Class::ELF32, _) => self.u32_bytes(value as u32).to_vec(),
+            (Class::ELF64, true) => value.to_le_bytes().to_vec(),
+            (Class::ELF64, false) => value.to_be_bytes().to_vec(),
+        }
+    }
+
+    fn section_header_bytes(&self, shdr: &SectionHeader) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(self.u32_bytes(shdr.sh_name));
+        out.extend(self.u32_bytes(shdr.sh_type));
+        out.extend(self.addr_bytes(shdr.sh_flags));
+        out.extend(self.addr_bytes(shdr.sh_addr));
+        out.extend(self.addr_bytes(shdr.sh_offset));
+        out.extend(self.addr_bytes(shdr.sh_size));
+        out.extend(self.u32_bytes(shdr.sh_link));
+        out.extend(self.u32_bytes(shdr.sh_info));
+        out.extend(self.addr_bytes(shdr.sh_addralign));
+        out.extend(self.addr_bytes(shdr.sh_entsize));
+        out
+    }
+
+    fn section_data(&self, shdr: &SectionHeader) -> &'a [u8] {
+        let start = (shdr.sh_offset as usize).min(self.data.len());
+        let end = (start + shdr.sh_size as usize).min(self.data.len());
+        &self.data[start..end]
+    }
+
+    /// Writes the ELF file with the sections processed by `actions` and the `extra` section.
+    ///
+    /// If `keep_loaded` is set, the contents loaded into the memory are kept as they are, so
+    /// that the file remains executable. Otherwise only the headers are kept and the segments
+    /// are left empty, as the debug file does.
+    fn write(
+        &self,
+        actions: &[SectionAction],
+        extra: Option<(&str, &[u8])>,
+        keep_loaded: bool,
+    ) -> Vec<u8> {
+        let mut out = match keep_loaded {
+            true => self.data[..self.loaded_end].to_vec(),
+            false => self.data[..self.headers_end].to_vec(),
+        };
+        let align = |out: &mut Vec<u8>, alignment: u64| {
+            out.resize(out.len().next_multiple_of(alignment.max(1) as usize), 0);
+        };
+
+        // new indexes of the sections kept
+        let mut indexes = Vec::with_capacity(self.sections.len());
+        let mut num_sections = 0;
+        for (idx, action) in actions.iter().enumerate() {
+            if *action == SectionAction::Remove && idx != 0 {
+                indexes.push(None);
+            } else {
+                indexes.push(Some(num_sections as u32));
+                num_sections += 1;
+            }
+        }
+        let remap = |idx: u32| {
+            indexes
+                .get(idx as usize)
+                .copied()
+                .flatten()
+                .unwrap_or_default()
+        };
+
+        let mut shstrtab = vec![0u8];
+        let mut add_name = |name: &str| {
+            let offset = shstrtab.len() as u32;
+            shstrtab.extend(name.as_bytes());
+            shstrtab.push(0);
+            offset
+        };
+
+        let mut shdrs = Vec::new();
+        for ((name, shdr), action) in self.sections.iter().zip(actions) {
+            if *action == SectionAction::Remove && !shdrs.is_empty() {
+                continue;
+            }
+            let mut shdr = *shdr;
+            if !shdrs.is_empty() {
+                shdr.sh_name = add_name(name);
+            }
+            shdr.sh_link = remap(shdr.sh_link);
+            if shdr.sh_flags & SHF_INFO_LINK as u64 != 0 {
+                shdr.sh_info = remap(shdr.sh_info);
+            }
+            if shdrs.is_empty() || shdr.sh_type == SHT_NOBITS {
+                // no contents
+            } else if *action == SectionAction::NoBits {
+                shdr.sh_type = SHT_NOBITS;
+                shdr.sh_offset = out.len() as u64;
+            } else if keep_loaded
+                && shdr.sh_offset as usize + shdr.sh_size as usize <= self.loaded_end
+            {
+                // kept at the same offset
+            } else if shdrs.len() != remap(self.shstrndx as u32) as usize {
+                let data = self.section_data(&shdr);
+                align(&mut out, shdr.sh_addralign);
+                shdr.sh_offset = out.len() as u64;
+                out.extend(data);
+            }
+            shdrs.push(shdr);
+        }
+
+        if let Some((name, data)) = extra {
+            align(&mut out, 4);
+            shdrs.push(SectionHeader {
+                sh_name: add_name(name),
+                sh_type: SHT_PROGBITS,
+                sh_flags: 0,
+                sh_addr: 0,
+                sh_offset: out.len() as u64,
+                sh_size: data.len() as u64,
+                sh_link: 0,
+                sh_info: 0,
+                sh_addralign: 4,
+                sh_entsize: 0,
+            });
+            out.extend(data);
+        }
+
+        // the section names are written at last, following the name of the extra section
+        let shstrndx = remap(self.shstrndx as u32) as usize;
+        if let Some(shdr) = shdrs.get_mut(shstrndx).filter(|_| shstrndx != 0) {
+            shdr.sh_offset = out.len() as u64;
+            shdr.sh_size = shstrtab.len() as u64;
+            out.extend(&shstrtab);
+        }
+
+        let (shoff_pos, shnum_pos, addr_align) = match self.class {
+            Class::ELF32 => (32, 48, 4),
+            Class::ELF64 => (40, 60, 8),
+        };
+        align(&mut out, addr_align);
+        let shoff = self.addr_bytes(out.len() as u64);
+        out[shoff_pos..shoff_pos + shoff.len()].copy_from_slice(&shoff);
+        out[shnum_pos..shnum_pos + 2].copy_from_slice(&self.u16_bytes(shdrs.len() as u16));
+        out[shnum_pos + 2..shnum_pos + 4].copy_from_slice(&self.u16_bytes(shstrndx as u16));
+        for shdr in &shdrs {
+            out.extend(self.section_header_bytes(shdr));
+        }
+
+        if !keep_loaded {
+            // the segments are truncated to the headers, which are the only contents kept
+            let (offset_pos, filesz_pos) = match self.class {
+                Class::ELF32 => (4, 16),
+                Class::ELF64 => (8, 32),
+            };
+            let width = self.addr_bytes(0).len();
+            for i in 0..self.phnum {
+                let pos = self.phoff + i * self.phentsize;
+                let (Some(offset), Some(filesz)) = (
+                    self.read_addr(pos + offset_pos),
+                    self.read_addr(pos + filesz_pos),
+                ) else {
+                    continue;
+                };
+                let kept = filesz.min((self.headers_end as u64).saturating_sub(offset));
+                let kept = self.addr_bytes(kept);
+                out[pos + filesz_pos..pos + filesz_pos + width].copy_from_slice(&kept);
+            }
+        }
+        out
+    }
+
+    fn read_addr(&self, pos: usize) -> Option<u64> {
+        let bytes = self.data.get(pos..pos + self.addr_bytes(0).len())?;
+        let mut buf = [0u8; 8];
+        Some(match self.endian.is_little() {
+            true => {
+                buf[..bytes.len()].copy_from_slice(bytes);
+                u64::from_le_bytes(buf)
+            }
+            false => {
+                buf[8 - bytes.len()..].copy_from_slice(bytes);
+                u64::from_be_bytes(buf)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_debug_file_paths() {
+        assert_eq!(
+            debug_file_paths("/usr/bin/foo", Some("0123abcd")),
+            (
+                "/usr/lib/debug/usr/bin/foo.debug".to_string(),
+                vec![
+                    (
+                        "/usr/lib/debug/.build-id/01/23abcd".to_string(),
+                        "../../../../../usr/bin/foo".to_string()
+                    ),
+                    (
+                        "/usr/lib/debug/.build-id/01/23abcd.debug".to_string(),
+                        "../../usr/bin/foo.debug".to_string()
+                    ),
+                ]
+            )
+        );
+        assert_eq!(
+            debug_file_paths("/usr/bin/foo", None),
+            ("/usr/lib/debug/usr/bin/foo.debug".to_string(), vec![])
+        );
+    }
+
+    #[test]
+    fn test_split_debuginfo() {
+        assert!(split_debuginfo(b"#!/bin/sh\n", "/usr/bin/foo").is_err());
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let source = tempdir.path().join("hello.c");
+        let binary = tempdir.path().join("hello");
+        std::fs::write(
+            &source,
+            "#include <stdio.h>\nint main(){puts(\"hello\");}\n",
+        )
+        .unwrap();
+        let Ok(status) = Command::new("cc")
+            .args(["-g", "-Wl,--build-id", "-o"])
+            .arg(&binary)
+            .arg(&source)
+            .status()
+        else {
+            return; // no C compiler to build the test binary
+        };
+        assert!(status.success());
+
+        let data = std::fs::read(&binary).unwrap();
+        let split = split_debuginfo(&data, "/usr/bin/hello").unwrap().unwrap();
+        assert!(split.build_id.is_some());
+
+        let section_names = |data: &[u8]| {
+            let elf = ElfBytes::<AnyEndian>::minimal_parse(data).unwrap();
+            let (shdrs, strtab) = elf.section_headers_with_strtab().unwrap();
+            let (shdrs, strtab) = (shdrs.unwrap(), strtab.unwrap());
+            shdrs
+                .iter()
+                .map(|v| {
+                    (
+                        strtab.get(v.sh_name as usize).unwrap().to_string(),
+                        v.sh_type,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let stripped = section_names(&split.stripped);
+        assert!(
+            stripped
+                .iter()
+                .all(|(name, _)| !name.starts_with(".debug_"))
+        );
+        assert!(stripped.iter().all(|(name, _)| name != ".symtab"));
+        assert!(stripped.iter().any(|(name, _)| name == ".text"));
+        let debug = section_names(&split.debug);
+        assert!(debug.contains(&(".debug_info".to_string(), SHT_PROGBITS)));
+        assert!(debug.contains(&(".text".to_string(), SHT_NOBITS)));
+
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(&split.stripped).unwrap();
+        let debuglink = elf
+            .section_header_by_name(".gnu_debuglink")
+            .unwrap()
+            .unwrap();
+        let (debuglink, _) = elf.section_data(&debuglink).unwrap();
+        let mut crc = flate2::Crc::new();
+        crc.update(&split.debug);
+        assert_eq!(&debuglink[..12], b"hello.debug\0");
+        assert_eq!(debuglink[12..], crc.sum().to_ne_bytes());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::write(&binary, &split.stripped).unwrap();
+            std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+            let output = Command::new(&binary).output().unwrap();
+            assert_eq!(output.stdout, b"hello\n");
+        }
+
+        // nothing to be stripped
+        assert!(
+            split_debuginfo(&split.stripped, "/usr/bin/hello")
+                .unwrap()
+                .is_none()
+        );
+    }
+}
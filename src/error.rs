@@ -32,6 +32,8 @@ pub enum ConfigError {
     AssetFileUndefined(usize, &'static str),
     #[error("{1} of {0}-th asset must be {2}")]
     AssetFileWrongType(usize, &'static str, &'static str),
+    #[error("Invalid placeholder `{1}' in dest of {0}-th asset")]
+    AssetDestTemplateInvalid(usize, String),
     #[error("Asset file not found: {0}")]
     AssetFileNotFound(PathBuf),
     #[error("Invalid dependency version specified for {0}")]
@@ -40,6 +42,14 @@ pub enum ConfigError {
     WrongBranchPathOfToml(String, #[source] DottedBareKeyLexError),
     #[error("Branch `{0}' not found")]
     BranchPathNotFoundInToml(String),
+    #[error("Invalid regular expression in {0}")]
+    InvalidRegex(String, #[source] regex::Error),
+    #[error("Invalid {0} `{1}': only alphanumerics and `._+~^' are allowed")]
+    InvalidVersion(String, String),
+    #[error("Invalid license expression `{0}': {1}")]
+    InvalidLicense(String, String),
+    #[error("Too deep recursion in macro `%{{{1}}}' in {0}")]
+    RecursiveMacro(String, String),
     #[error("Field {1} for file {0} has the following error: {2}")]
     AssetFileRpm(usize, &'static str, #[source] std::rc::Rc<rpm::Error>),
 }
@@ -60,10 +70,60 @@ impl<E: StdError + Display> Display for FileAnnotatedError<E> {
 pub enum AutoReqError {
     #[error("Failed to execute `{file}`: {1}", file = .0.clone().into_string().unwrap_or_default())]
     ProcessError(OsString, #[source] IoError),
+    #[error("Failed to parse ELF file `{file}`: {1}", file = .0.display())]
+    ElfParse(PathBuf, #[source] elf::ParseError),
     #[error(transparent)]
     Io(#[from] IoError),
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum SrpmError {
+    #[error("Failed to execute `cargo vendor`: {0}")]
+    VendorProcess(#[source] IoError),
+    #[error("`cargo vendor` failed: {0}")]
+    VendorFailed(String),
+    #[error("Failed to execute `git ls-files`: {0}")]
+    GitProcess(#[source] IoError),
+    #[error("`git ls-files` failed: {0}")]
+    GitFailed(String),
+    #[error("Asset `{file}` is not located in the source tree", file = .0.display())]
+    SourceOutsideTree(PathBuf),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BuildError {
+    #[error("Failed to execute `cargo build`: {0}")]
+    BuildProcess(#[source] IoError),
+    #[error("`cargo build` failed: {0}")]
+    BuildFailed(String),
+    #[error("Asset `{file}` is older than the output of `cargo build`", file = .0.display())]
+    StaleAsset(PathBuf),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CargoMetadataError {
+    #[error("Failed to execute `cargo metadata`: {0}")]
+    Process(#[source] IoError),
+    #[error("`cargo metadata` failed: {0}")]
+    Failed(String),
+    #[error("Failed to parse the output of `cargo metadata`: {0}")]
+    Parse(#[source] serde_json::Error),
+    #[error("Missing field in the output of `cargo metadata`: {0}")]
+    Missing(String),
+    #[error("Package `{0}' not found in the workspace")]
+    PackageNotFound(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ChangelogError {
+    #[error("Invalid date `{0}' in changelog: YYYY-MM-DD is expected")]
+    InvalidDate(String),
+    #[error("Failed to execute `git`: {0}")]
+    GitProcess(#[source] IoError),
+    #[error("`git {0}` failed: {1}")]
+    GitFailed(String, String),
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Cargo.toml: {0}")]
@@ -80,7 +140,26 @@ pub enum Error {
     #[error(transparent)]
     AutoReq(#[from] AutoReqError),
     #[error(transparent)]
+    Changelog(#[from] ChangelogError),
+    #[error(transparent)]
+    Srpm(#[from] SrpmError),
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    #[error(transparent)]
+    CargoMetadata(#[from] CargoMetadataError),
+    #[error(transparent)]
     Rpm(#[from] rpm::Error),
+    #[error("Failed to extract the debug info of `{file}`: {1}", file = .0.display())]
+    Debuginfo(PathBuf, #[source] elf::ParseError),
+    #[error(
+        "`--output` must be a directory to generate multiple packages: {}",
+        .0.display()
+    )]
+    OutputNotDir(PathBuf),
+    #[error("Failed to generate {0} of {1} packages")]
+    PackagesFailed(usize, usize),
+    #[error("Unexpected layout of the RPM header")]
+    InvalidRpmHeader,
     #[error("{1}: {0}")]
     FileIo(PathBuf, #[source] IoError),
     #[error(transparent)]
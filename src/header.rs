@@ -0,0 +1,379 @@
+//! Low-level access to the RPM header for what the `rpm` crate provides no API for.
+//!
+//! The package is serialized and its header is parsed into the entries, which are modified and
+//! re-serialized in the same layout as the `rpm` crate does.
+
+use rpm::{FileFlags, FileVerifyFlags, Package, PackageMetadata};
+use std::path::Path;
+
+use crate::error::Error;
+
+const LEAD_SIZE: usize = 96;
+const INDEX_HEADER_SIZE: usize = 16;
+const INDEX_ENTRY_SIZE: usize = 16;
+const HEADER_MAGIC: [u8; 8] = [0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+
+const RPMTAG_HEADERIMMUTABLE: u32 = 63;
+pub(crate) const RPMTAG_FILEFLAGS: u32 = 1037;
+const RPMTAG_FILEVERIFYFLAGS: u32 = 1045;
+pub(crate) const RPMTAG_DISTRIBUTION: u32 = 1010;
+pub(crate) const RPMTAG_PACKAGER: u32 = 1015;
+pub(crate) const RPMTAG_GROUP: u32 = 1016;
+pub(crate) const RPMTAG_SOURCERPM: u32 = 1044;
+pub(crate) const RPMTAG_SOURCEPACKAGE: u32 = 1106;
+pub(crate) const RPMTAG_DIRINDEXES: u32 = 1116;
+pub(crate) const RPMTAG_BASENAMES: u32 = 1117;
+pub(crate) const RPMTAG_DIRNAMES: u32 = 1118;
+pub(crate) const RPMTAG_BUGURL: u32 = 5012;
+
+const RPM_INT16_TYPE: u32 = 3;
+pub(crate) const RPM_INT32_TYPE: u32 = 4;
+const RPM_INT64_TYPE: u32 = 5;
+const RPM_STRING_TYPE: u32 = 6;
+const RPM_BIN_TYPE: u32 = 7;
+const RPM_STRING_ARRAY_TYPE: u32 = 8;
+const RPM_I18NSTRING_TYPE: u32 = 9;
+
+#[derive(Debug)]
+pub(crate) struct Entry {
+    pub(crate) tag: u32,
+    pub(crate) kind: u32,
+    pub(crate) count: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Entry {
+    pub(crate) fn strings(&self) -> Vec<String> {
+        self.data
+            .split(|v| *v == 0)
+            .take(self.count as usize)
+            .map(|v| String::from_utf8_lossy(v).to_string())
+            .collect()
+    }
+
+    pub(crate) fn set_strings(&mut self, values: &[String]) {
+        self.count = values.len() as u32;
+        self.data = values
+            .iter()
+            .flat_map(|v| v.bytes().chain(std::iter::once(0)))
+            .collect();
+    }
+
+    pub(crate) fn u32s(&self) -> Vec<u32> {
+        self.data
+            .chunks_exact(4)
+            .map(|v| u32::from_be_bytes(v.try_into().unwrap()))
+            .collect()
+    }
+
+    pub(crate) fn set_u32s(&mut self, values: &[u32]) {
+        self.count = values.len() as u32;
+        self.data = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+    }
+
+    fn alignment(&self) -> usize {
+        match self.kind {
+            RPM_INT16_TYPE => 2,
+            RPM_INT32_TYPE => 4,
+            RPM_INT64_TYPE => 8,
+            _ => 1,
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> Result<u32, Error> {
+    bytes
+        .get(pos..pos + 4)
+        .map(|v| u32::from_be_bytes(v.try_into().unwrap()))
+        .ok_or(Error::InvalidRpmHeader)
+}
+
+/// Returns the length of the data of the entry at the beginning of `store`.
+fn data_len(kind: u32, count: usize, store: &[u8]) -> Result<usize, Error> {
+    let len = match kind {
+        RPM_INT16_TYPE => count * 2,
+        RPM_INT32_TYPE => count * 4,
+        RPM_INT64_TYPE => count * 8,
+        RPM_STRING_TYPE | RPM_STRING_ARRAY_TYPE | RPM_I18NSTRING_TYPE => {
+            let mut len = 0;
+            for _ in 0..count {
+                len += store[len..]
+                    .iter()
+                    .position(|v| *v == 0)
+                    .ok_or(Error::InvalidRpmHeader)?
+                    + 1;
+            }
+            len
+        }
+        _ => count,
+    };
+    if len > store.len() {
+        return Err(Error::InvalidRpmHeader);
+    }
+    Ok(len)
+}
+
+/// Parses the header located at the beginning of `bytes`, excluding the region tag.
+fn parse_header(bytes: &[u8]) -> Result<Vec<Entry>, Error> {
+    if bytes.get(..HEADER_MAGIC.len()) != Some(HEADER_MAGIC.as_slice()) {
+        return Err(Error::InvalidRpmHeader);
+    }
+    let num_entries = read_u32(bytes, 8)? as usize;
+    let store_size = read_u32(bytes, 12)? as usize;
+    let store_offset = INDEX_HEADER_SIZE + num_entries * INDEX_ENTRY_SIZE;
+    let store = bytes
+        .get(store_offset..store_offset + store_size)
+        .ok_or(Error::InvalidRpmHeader)?;
+
+    let mut entries = Vec::with_capacity(num_entries);
+    for i in 0..num_entries {
+        let pos = INDEX_HEADER_SIZE + i * INDEX_ENTRY_SIZE;
+        let tag = read_u32(bytes, pos)?;
+        let kind = read_u32(bytes, pos + 4)?;
+        let offset = read_u32(bytes, pos + 8)? as usize;
+        let count = read_u32(bytes, pos + 12)?;
+        if tag == RPMTAG_HEADERIMMUTABLE {
+            continue;
+        }
+        let store = store.get(offset..).ok_or(Error::InvalidRpmHeader)?;
+        let len = data_len(kind, count as usize, store)?;
+        entries.push(Entry {
+            tag,
+            kind,
+            count,
+            data: store[..len].to_vec(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Serializes the header in the same layout as the `rpm` crate does.
+fn write_header(entries: &mut [Entry], out: &mut Vec<u8>) {
+    entries.sort_by_key(|v| v.tag);
+
+    let mut index = Vec::new();
+    let mut store = Vec::new();
+    for entry in entries.iter() {
+        let alignment = entry.alignment();
+        store.resize(store.len().next_multiple_of(alignment), 0);
+        index.extend(entry.tag.to_be_bytes());
+        index.extend(entry.kind.to_be_bytes());
+        index.extend((store.len() as u32).to_be_bytes());
+        index.extend(entry.count.to_be_bytes());
+        store.extend(&entry.data);
+    }
+
+    // region tag, whose data (trailer) points the beginning of the index
+    let region_offset = store.len() as u32;
+    store.extend(RPMTAG_HEADERIMMUTABLE.to_be_bytes());
+    store.extend(RPM_BIN_TYPE.to_be_bytes());
+    store.extend((-(((entries.len() + 1) * INDEX_ENTRY_SIZE) as i32)).to_be_bytes());
+    store.extend(16u32.to_be_bytes());
+
+    out.extend(HEADER_MAGIC);
+    out.extend(((entries.len() + 1) as u32).to_be_bytes());
+    out.extend((store.len() as u32).to_be_bytes());
+    out.extend(RPMTAG_HEADERIMMUTABLE.to_be_bytes());
+    out.extend(RPM_BIN_TYPE.to_be_bytes());
+    out.extend(region_offset.to_be_bytes());
+    out.extend(16u32.to_be_bytes());
+    out.extend(index);
+    out.extend(store);
+}
+
+/// Serializes `metadata` and returns the bytes, the offset of the header and its entries.
+fn read_header(metadata: &PackageMetadata) -> Result<(Vec<u8>, usize, Vec<Entry>), Error> {
+    let mut bytes = Vec::new();
+    metadata.write(&mut bytes)?;
+
+    let signature_size = {
+        let num_entries = read_u32(&bytes, LEAD_SIZE + 8)? as usize;
+        let store_size = read_u32(&bytes, LEAD_SIZE + 12)? as usize;
+        (INDEX_HEADER_SIZE + num_entries * INDEX_ENTRY_SIZE + store_size).next_multiple_of(8)
+    };
+    let header_offset = LEAD_SIZE + signature_size;
+    let entries = parse_header(bytes.get(header_offset..).unwrap_or_default())?;
+    Ok((bytes, header_offset, entries))
+}
+
+/// Rewrites the lead and the header entries of `pkg` with `f`.
+///
+/// The signatures are cleared since they no longer match; sign the returned package if needed.
+pub(crate) fn rewrite_package<F>(pkg: Package, f: F) -> Result<Package, Error>
+where
+    F: FnOnce(&mut [u8], &mut Vec<Entry>),
+{
+    let (mut bytes, header_offset, mut entries) = read_header(&pkg.metadata)?;
+    f(&mut bytes[..LEAD_SIZE], &mut entries);
+
+    bytes.truncate(header_offset);
+    write_header(&mut entries, &mut bytes);
+    bytes.extend(&pkg.content);
+
+    let mut pkg = Package::parse(&mut bytes.as_slice())?;
+    pkg.clear_signatures()?;
+    Ok(pkg)
+}
+
+/// Returns the full paths of the files in the order of the file tags.
+fn file_paths(entries: &[Entry]) -> Vec<String> {
+    let find = |tag| entries.iter().find(|v| v.tag == tag);
+    let (Some(base_names), Some(dir_indexes), Some(dir_names)) = (
+        find(RPMTAG_BASENAMES),
+        find(RPMTAG_DIRINDEXES),
+        find(RPMTAG_DIRNAMES),
+    ) else {
+        return Vec::new();
+    };
+    let dir_names = dir_names.strings();
+    base_names
+        .strings()
+        .into_iter()
+        .zip(dir_indexes.u32s())
+        .map(|(base_name, idx)| {
+            let dir_name = dir_names.get(idx as usize).map(String::as_str);
+            format!("{}{base_name}", dir_name.unwrap_or_default())
+        })
+        .collect()
+}
+
+/// Adds `flags` to the file flags of the files at the paths, which the `rpm` crate cannot set
+/// such as `FileFlags::MISSINGOK`.
+pub(crate) fn add_file_flags(
+    pkg: Package,
+    flags: &[(String, FileFlags)],
+) -> Result<Package, Error> {
+    rewrite_package(pkg, |_, entries| {
+        let paths = file_paths(entries);
+        if let Some(entry) = entries.iter_mut().find(|v| v.tag == RPMTAG_FILEFLAGS) {
+            let mut values = entry.u32s();
+            for (value, path) in values.iter_mut().zip(&paths) {
+                for (dest, flag) in flags {
+                    if Path::new(dest) == Path::new(path) {
+                        *value |= flag.bits();
+                    }
+                }
+            }
+            entry.set_u32s(&values);
+        }
+    })
+}
+
+/// Sets the string `tags` such as `RPMTAG_DISTRIBUTION`, which the `rpm` crate cannot set.
+///
+/// `RPMTAG_PACKAGER` and `RPMTAG_GROUP` are included since the `rpm` crate ignores the values
+/// given to `PackageBuilder::packager` and `PackageBuilder::group`.
+pub(crate) fn set_string_tags(pkg: Package, tags: &[(u32, String)]) -> Result<Package, Error> {
+    rewrite_package(pkg, |_, entries| {
+        for (tag, value) in tags {
+            entries.retain(|v| v.tag != *tag);
+            entries.push(Entry {
+                tag: *tag,
+                kind: match *tag {
+                    RPMTAG_GROUP => RPM_I18NSTRING_TYPE,
+                    _ => RPM_STRING_TYPE,
+                },
+                count: 1,
+                data: value.bytes().chain(std::iter::once(0)).collect(),
+            });
+        }
+    })
+}
+
+/// Returns the string tag such as `RPMTAG_DISTRIBUTION`, which the `rpm` crate provides no
+/// accessor for.
+pub(crate) fn string_tag(metadata: &PackageMetadata, tag: u32) -> Result<Option<String>, Error> {
+    let (_, _, entries) = read_header(metadata)?;
+    Ok(entries
+        .iter()
+        .find(|v| v.tag == tag && matches!(v.kind, RPM_STRING_TYPE | RPM_I18NSTRING_TYPE))
+        .and_then(|v| v.strings().into_iter().next()))
+}
+
+/// Returns the verify flags of the files in the order of `PackageMetadata::get_file_entries`,
+/// which the `rpm` crate provides no accessor for.
+pub(crate) fn file_verify_flags(metadata: &PackageMetadata) -> Result<Vec<FileVerifyFlags>, Error> {
+    let (_, _, entries) = read_header(metadata)?;
+    Ok(entries
+        .iter()
+        .find(|v| v.tag == RPMTAG_FILEVERIFYFLAGS)
+        .map(Entry::u32s)
+        .unwrap_or_default()
+        .into_iter()
+        .map(FileVerifyFlags::from_bits_retain)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rpm::{FileOptions, PackageBuilder};
+
+    #[test]
+    fn test_add_file_flags() {
+        let pkg = PackageBuilder::new("foo", "1.2.0", "MIT", "x86_64", "foo")
+            .with_file_contents("", FileOptions::new("/etc/foo.conf").is_config())
+            .unwrap()
+            .with_file_contents(
+                "",
+                FileOptions::new("/var/log/foo.log")
+                    .verify(FileVerifyFlags::all() - FileVerifyFlags::FILESIZE),
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let flags = [
+            ("/etc/foo.conf".to_string(), FileFlags::MISSINGOK),
+            ("/var/log/foo.log".to_string(), FileFlags::ARTIFACT),
+        ];
+        let pkg = add_file_flags(pkg, &flags).unwrap();
+        pkg.verify_digests().unwrap();
+
+        let files = pkg.metadata.get_file_entries().unwrap();
+        assert_eq!(files[0].path, Path::new("/etc/foo.conf"));
+        assert_eq!(files[0].flags, FileFlags::CONFIG | FileFlags::MISSINGOK);
+        assert_eq!(files[1].path, Path::new("/var/log/foo.log"));
+        assert_eq!(files[1].flags, FileFlags::ARTIFACT);
+        assert_eq!(
+            file_verify_flags(&pkg.metadata).unwrap(),
+            vec![
+                FileVerifyFlags::all(),
+                FileVerifyFlags::all() - FileVerifyFlags::FILESIZE
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_string_tags() {
+        let pkg = PackageBuilder::new("foo", "1.2.0", "MIT", "x86_64", "foo")
+            .with_file_contents("", FileOptions::new("/etc/foo.conf"))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(string_tag(&pkg.metadata, RPMTAG_BUGURL).unwrap(), None);
+
+        let tags = [
+            (RPMTAG_DISTRIBUTION, "Foo Linux".to_string()),
+            (RPMTAG_PACKAGER, "Foo <foo@example.com>".to_string()),
+            (RPMTAG_GROUP, "Applications/System".to_string()),
+            (RPMTAG_BUGURL, "https://example.com/issues".to_string()),
+        ];
+        let pkg = set_string_tags(pkg, &tags).unwrap();
+        pkg.verify_digests().unwrap();
+        assert_eq!(
+            string_tag(&pkg.metadata, RPMTAG_DISTRIBUTION).unwrap(),
+            Some("Foo Linux".to_string())
+        );
+        assert_eq!(
+            string_tag(&pkg.metadata, RPMTAG_BUGURL).unwrap(),
+            Some("https://example.com/issues".to_string())
+        );
+        assert_eq!(
+            pkg.metadata.get_packager().unwrap(),
+            "Foo <foo@example.com>"
+        );
+        assert_eq!(pkg.metadata.get_group().unwrap(), "Applications/System");
+        assert_eq!(pkg.metadata.get_name().unwrap(), "foo");
+        assert_eq!(pkg.metadata.get_file_paths().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,488 @@
+use rpm::{Dependency, DependencyFlags, FileEntry, FileFlags, Package, PackageMetadata, Scriptlet};
+use serde_json::{Map, Value, json};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+pub(crate) type DependencyGetter = fn(&PackageMetadata) -> Result<Vec<Dependency>, rpm::Error>;
+pub(crate) type ScriptletGetter = fn(&PackageMetadata) -> Result<Scriptlet, rpm::Error>;
+
+pub(crate) const DEPENDENCY_KINDS: [(&str, DependencyGetter); 8] = [
+    ("requires", PackageMetadata::get_requires),
+    ("provides", PackageMetadata::get_provides),
+    ("conflicts", PackageMetadata::get_conflicts),
+    ("obsoletes", PackageMetadata::get_obsoletes),
+    ("recommends", PackageMetadata::get_recommends),
+    ("suggests", PackageMetadata::get_suggests),
+    ("enhances", PackageMetadata::get_enhances),
+    ("supplements", PackageMetadata::get_supplements),
+];
+
+pub(crate) const SCRIPTLET_KINDS: [(&str, ScriptletGetter); 8] = [
+    ("pre_install", PackageMetadata::get_pre_install_script),
+    ("post_install", PackageMetadata::get_post_install_script),
+    ("pre_uninstall", PackageMetadata::get_pre_uninstall_script),
+    ("post_uninstall", PackageMetadata::get_post_uninstall_script),
+    ("pre_trans", PackageMetadata::get_pre_trans_script),
+    ("post_trans", PackageMetadata::get_post_trans_script),
+    ("pre_untrans", PackageMetadata::get_pre_untrans_script),
+    ("post_untrans", PackageMetadata::get_post_untrans_script),
+];
+
+const FILE_FLAG_NAMES: [(FileFlags, &str); 10] = [
+    (FileFlags::CONFIG, "config"),
+    (FileFlags::NOREPLACE, "noreplace"),
+    (FileFlags::MISSINGOK, "missingok"),
+    (FileFlags::DOC, "doc"),
+    (FileFlags::LICENSE, "license"),
+    (FileFlags::README, "readme"),
+    (FileFlags::GHOST, "ghost"),
+    (FileFlags::ARTIFACT, "artifact"),
+    (FileFlags::SPECFILE, "specfile"),
+    (FileFlags::PUBKEY, "pubkey"),
+];
+
+/// Information of a package to be generated, which is not recorded in the package itself
+///
+/// The files of the package are not read for the plan, hence their sizes, timestamps and digests
+/// are not shown.
+#[derive(Debug)]
+pub struct PackagePlan<'a> {
+    /// Pairs of the source path and the destination of the packaged files
+    pub sources: &'a [(PathBuf, String)],
+    /// Path to the RPM package file to be written
+    pub output: &'a Path,
+}
+
+impl PackagePlan<'_> {
+    fn source_of(&self, dest: &Path) -> Option<&Path> {
+        self.sources
+            .iter()
+            .find(|(_, d)| Path::new(d) == dest)
+            .map(|(s, _)| s.as_path())
+    }
+}
+
+/// Print the content of the RPM package file located at `path`.
+pub fn inspect<P: AsRef<Path>, W: Write>(path: P, json: bool, out: &mut W) -> Result<(), Error> {
+    let pkg = Package::open(path)?;
+    print_metadata(&pkg.metadata, None, json, out)
+}
+
+/// Print the content of the packages to be generated along with their plans, as a JSON array
+/// if `json`.
+pub fn print_plans<W: Write>(
+    plans: &[(&PackageMetadata, PackagePlan)],
+    json: bool,
+    out: &mut W,
+) -> Result<(), Error> {
+    if json {
+        let values = plans
+            .iter()
+            .map(|(metadata, plan)| to_json(metadata, Some(plan)))
+            .collect::<Result<Vec<_>, _>>()?;
+        serde_json::to_writer_pretty(&mut *out, &values).map_err(std::io::Error::from)?;
+        writeln!(out)?;
+    } else {
+        for (idx, (metadata, plan)) in plans.iter().enumerate() {
+            if idx > 0 {
+                writeln!(out)?;
+            }
+            write_text(metadata, Some(plan), out)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_metadata<W: Write>(
+    metadata: &PackageMetadata,
+    plan: Option<&PackagePlan>,
+    json: bool,
+    out: &mut W,
+) -> Result<(), Error> {
+    if json {
+        serde_json::to_writer_pretty(&mut *out, &to_json(metadata, plan)?)
+            .map_err(std::io::Error::from)?;
+        writeln!(out)?;
+    } else {
+        write_text(metadata, plan, out)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn format_dependency(dependency: &Dependency) -> String {
+    let op = dependency_operator(dependency.flags);
+    if op.is_empty() {
+        dependency.name.clone()
+    } else {
+        format!("{} {} {}", dependency.name, op, dependency.version)
+    }
+}
+
+fn dependency_operator(flags: DependencyFlags) -> &'static str {
+    let flags = flags & (DependencyFlags::LESS | DependencyFlags::GREATER | DependencyFlags::EQUAL);
+    match flags {
+        DependencyFlags::LESS => "<",
+        DependencyFlags::LE => "<=",
+        DependencyFlags::EQUAL => "=",
+        DependencyFlags::GE => ">=",
+        DependencyFlags::GREATER => ">",
+        _ => "",
+    }
+}
+
+/// Returns the mode as `ls -l` style string, e.g. `-rwxr-xr-x`.
+fn format_mode(mode: u16) -> String {
+    let file_type = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o100000 => '-',
+        _ => '?',
+    };
+    let mut s = String::from(file_type);
+    for (shift, special, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        s.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        s.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+fn file_flag_names(flags: FileFlags) -> Vec<&'static str> {
+    FILE_FLAG_NAMES
+        .iter()
+        .filter(|(flag, _)| flags.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+fn write_text<W: Write>(
+    metadata: &PackageMetadata,
+    plan: Option<&PackagePlan>,
+    out: &mut W,
+) -> Result<(), Error> {
+    let tags: [(&str, Option<String>); 18] = [
+        ("Name", metadata.get_name().ok().map(String::from)),
+        ("Epoch", metadata.get_epoch().ok().map(|v| v.to_string())),
+        ("Version", metadata.get_version().ok().map(String::from)),
+        ("Release", metadata.get_release().ok().map(String::from)),
+        ("Architecture", metadata.get_arch().ok().map(String::from)),
+        ("Group", metadata.get_group().ok().map(String::from)),
+        (
+            "Size",
+            metadata
+                .get_installed_size()
+                .ok()
+                .filter(|_| plan.is_none())
+                .map(|v| v.to_string()),
+        ),
+        ("License", metadata.get_license().ok().map(String::from)),
+        (
+            "Source RPM",
+            metadata.get_source_rpm().ok().map(String::from),
+        ),
+        (
+            "Build Date",
+            metadata.get_build_time().ok().map(|v| v.to_string()),
+        ),
+        (
+            "Build Host",
+            metadata.get_build_host().ok().map(String::from),
+        ),
+        ("Packager", metadata.get_packager().ok().map(String::from)),
+        ("Vendor", metadata.get_vendor().ok().map(String::from)),
+        ("URL", metadata.get_url().ok().map(String::from)),
+        ("VCS", metadata.get_vcs().ok().map(String::from)),
+        (
+            "Compressor",
+            metadata
+                .get_payload_compressor()
+                .ok()
+                .map(|v| format!("{v:?}")),
+        ),
+        ("Summary", metadata.get_summary().ok().map(String::from)),
+        (
+            "Description",
+            metadata.get_description().ok().map(String::from),
+        ),
+    ];
+    for (name, value) in tags {
+        if let Some(value) = value {
+            writeln!(out, "{name:<12}: {value}")?;
+        }
+    }
+
+    for (kind, getter) in DEPENDENCY_KINDS {
+        let dependencies = getter(metadata).unwrap_or_default();
+        if dependencies.is_empty() {
+            continue;
+        }
+        writeln!(out, "\n{kind}:")?;
+        for dependency in dependencies {
+            writeln!(out, "  {}", format_dependency(&dependency))?;
+        }
+    }
+
+    for (kind, getter) in SCRIPTLET_KINDS {
+        if let Ok(scriptlet) = getter(metadata) {
+            let program = scriptlet
+                .program
+                .map(|v| v.join(" "))
+                .unwrap_or("/bin/sh".to_string());
+            writeln!(out, "\n{kind} scriptlet (using {program}):")?;
+            writeln!(out, "{}", scriptlet.script.trim_end())?;
+        }
+    }
+
+    let files = metadata.get_file_entries().unwrap_or_default();
+    if !files.is_empty() {
+        writeln!(out, "\nfiles:")?;
+    }
+    for file in files {
+        write!(
+            out,
+            "{} {:<8} {:<8} {:>10} {}",
+            format_mode(file.mode.raw_mode()),
+            file.ownership.user,
+            file.ownership.group,
+            match plan {
+                Some(_) => "-".to_string(),
+                None => file.size.to_string(),
+            },
+            file.path.display(),
+        )?;
+        if !file.linkto.is_empty() {
+            write!(out, " -> {}", file.linkto)?;
+        }
+        let flags = file_flag_names(file.flags);
+        if !flags.is_empty() {
+            write!(out, " [{}]", flags.join(","))?;
+        }
+        if let Some(caps) = file.caps.as_ref().filter(|v| !v.is_empty()) {
+            write!(out, " caps={caps}")?;
+        }
+        if let Some(source) = plan.and_then(|v| v.source_of(&file.path)) {
+            write!(out, " <- {}", source.display())?;
+        }
+        writeln!(out)?;
+    }
+
+    if let Some(plan) = plan {
+        writeln!(out, "\noutput: {}", plan.output.display())?;
+    }
+
+    Ok(())
+}
+
+fn file_entry_to_json(file: &FileEntry, plan: Option<&PackagePlan>) -> Value {
+    json!({
+        "path": file.path,
+        "source": plan.and_then(|v| v.source_of(&file.path)),
+        "mode": format!("{:o}", file.mode.raw_mode()),
+        "user": file.ownership.user,
+        "group": file.ownership.group,
+        "size": Some(file.size).filter(|_| plan.is_none()),
+        "modified_at": Some(u32::from(file.modified_at)).filter(|_| plan.is_none()),
+        "flags": file_flag_names(file.flags),
+        "caps": file.caps.as_ref().filter(|v| !v.is_empty()),
+        "linkto": Some(&file.linkto).filter(|v| !v.is_empty()),
+        "digest": file.digest.as_ref().filter(|_| plan.is_none()).map(|v| v.to_string()),
+    })
+}
+
+fn to_json(metadata: &PackageMetadata, plan: Option<&PackagePlan>) -> Result<Value, Error> {
+    let mut dependencies = Map::new();
+    for (kind, getter) in DEPENDENCY_KINDS {
+        let dependencies_of_kind = getter(metadata)
+            .unwrap_or_default()
+            .iter()
+            .map(|v| {
+                json!({
+                    "name": v.name,
+                    "operator": Some(dependency_operator(v.flags)).filter(|v| !v.is_empty()),
+                    "version": Some(&v.version).filter(|v| !v.is_empty()),
+                })
+            })
+            .collect();
+        dependencies.insert(kind.to_string(), Value::Array(dependencies_of_kind));
+    }
+
+    let mut scriptlets = Map::new();
+    for (kind, getter) in SCRIPTLET_KINDS {
+        if let Ok(scriptlet) = getter(metadata) {
+            scriptlets.insert(
+                kind.to_string(),
+                json!({
+                    "script": scriptlet.script,
+                    "flags": scriptlet.flags.map(|v| v.bits()),
+                    "program": scriptlet.program,
+                }),
+            );
+        }
+    }
+
+    let files = metadata
+        .get_file_entries()
+        .unwrap_or_default()
+        .iter()
+        .map(|v| file_entry_to_json(v, plan))
+        .collect::<Vec<_>>();
+
+    let mut value = json!({
+        "name": metadata.get_name()?,
+        "epoch": metadata.get_epoch().ok(),
+        "version": metadata.get_version()?,
+        "release": metadata.get_release().ok(),
+        "arch": metadata.get_arch().ok(),
+        "group": metadata.get_group().ok(),
+        "installed_size": metadata.get_installed_size().ok().filter(|_| plan.is_none()),
+        "license": metadata.get_license().ok(),
+        "source_rpm": metadata.get_source_rpm().ok(),
+        "build_time": metadata.get_build_time().ok(),
+        "build_host": metadata.get_build_host().ok(),
+        "packager": metadata.get_packager().ok(),
+        "vendor": metadata.get_vendor().ok(),
+        "url": metadata.get_url().ok(),
+        "vcs": metadata.get_vcs().ok(),
+        "summary": metadata.get_summary().ok(),
+        "description": metadata.get_description().ok(),
+        "dependencies": dependencies,
+        "scriptlets": scriptlets,
+        "files": files,
+    });
+    if let Some(plan) = plan {
+        value["output"] = json!(plan.output);
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_test_package(dir: &Path) -> std::path::PathBuf {
+        let pkg = rpm::PackageBuilder::new("foo", "1.2.3", "MIT", "x86_64", "a test package")
+            .release("1")
+            .requires(Dependency::greater_eq("bar", "2.0"))
+            .provides(Dependency::any("libfoo.so.1()(64bit)"))
+            .post_install_script("echo installed")
+            .with_file(
+                file!(),
+                rpm::FileOptions::new("/etc/foo.conf")
+                    .mode(0o100640)
+                    .user("foo")
+                    .is_config_noreplace(),
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let path = dir.join("foo.rpm");
+        pkg.write_file(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_format_mode() {
+        assert_eq!(format_mode(0o100755), "-rwxr-xr-x");
+        assert_eq!(format_mode(0o040750), "drwxr-x---");
+        assert_eq!(format_mode(0o104755), "-rwsr-xr-x");
+        assert_eq!(format_mode(0o041777), "drwxrwxrwt");
+        assert_eq!(format_mode(0o120777), "lrwxrwxrwx");
+    }
+
+    #[test]
+    fn test_inspect() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = create_test_package(tempdir.path());
+
+        let mut out = Vec::new();
+        inspect(&path, false, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Name        : foo\n"));
+        assert!(out.contains("Version     : 1.2.3\n"));
+        assert!(out.contains("  bar >= 2.0\n"));
+        assert!(out.contains("  libfoo.so.1()(64bit)\n"));
+        assert!(out.contains("post_install scriptlet (using /bin/sh):\necho installed\n"));
+        assert!(out.contains("-rw-r----- foo"));
+        assert!(out.contains("/etc/foo.conf [config,noreplace]"));
+
+        let mut out = Vec::new();
+        inspect(&path, true, &mut out).unwrap();
+        let value: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value["name"], "foo");
+        assert_eq!(value["release"], "1");
+        assert_eq!(
+            value["dependencies"]["requires"][0],
+            json!({"name": "bar", "operator": ">=", "version": "2.0"})
+        );
+        assert_eq!(
+            value["scriptlets"]["post_install"]["script"],
+            "echo installed"
+        );
+        assert_eq!(value["files"][0]["path"], "/etc/foo.conf");
+        assert_eq!(value["files"][0]["mode"], "100640");
+        assert_eq!(value["files"][0]["flags"], json!(["config", "noreplace"]));
+
+        assert!(matches!(
+            inspect(tempdir.path().join("not-exist.rpm"), false, &mut Vec::new()),
+            Err(Error::Rpm(_))
+        ));
+    }
+
+    #[test]
+    fn test_print_plan() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let pkg = Package::open(create_test_package(tempdir.path())).unwrap();
+        let sources = [(PathBuf::from(file!()), "/etc/foo.conf".to_string())];
+        let plan = PackagePlan {
+            sources: &sources,
+            output: Path::new("target/generate-rpm/foo-1.2.3-1.x86_64.rpm"),
+        };
+
+        let plan2 = PackagePlan {
+            sources: &[],
+            output: Path::new("target/generate-rpm/foo-doc-1.2.3-1.x86_64.rpm"),
+        };
+        let plans = [(&pkg.metadata, plan), (&pkg.metadata, plan2)];
+
+        let mut out = Vec::new();
+        print_plans(&plans[..1], false, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(&format!(
+            "-rw-r----- foo      root              - /etc/foo.conf [config,noreplace] <- {}\n",
+            file!()
+        )));
+        assert!(!out.contains("Size"));
+        assert!(out.ends_with("\noutput: target/generate-rpm/foo-1.2.3-1.x86_64.rpm\n"));
+
+        let mut out = Vec::new();
+        print_plans(&plans, false, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(
+            "\noutput: target/generate-rpm/foo-1.2.3-1.x86_64.rpm\n\nName        : foo\n"
+        ));
+
+        let mut out = Vec::new();
+        print_plans(&plans, true, &mut out).unwrap();
+        let value: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 2);
+        assert_eq!(value[0]["files"][0]["source"], file!());
+        assert_eq!(value[0]["files"][0]["size"], Value::Null);
+        assert_eq!(value[0]["installed_size"], Value::Null);
+        assert_eq!(
+            value[0]["output"],
+            "target/generate-rpm/foo-1.2.3-1.x86_64.rpm"
+        );
+        assert_eq!(
+            value[1]["output"],
+            "target/generate-rpm/foo-doc-1.2.3-1.x86_64.rpm"
+        );
+    }
+}
@@ -1,42 +1,325 @@
-use crate::{build_target::BuildTarget, config::BuilderConfig};
-use cli::Cli;
+use crate::{
+    build_target::BuildTarget,
+    cargo_metadata::{CargoMetadata, MetadataPackage},
+    config::{BuilderConfig, PackageBuilder},
+};
+use cli::{AutoProvMode, AutoReqMode, Cli, Commands, ExtraMetadataSource, OutputFormat};
 use rpm::signature::pgp::Signer;
 use std::{
     fs,
     path::{Path, PathBuf},
 };
 mod auto_req;
+mod build;
 mod build_target;
+mod cargo_metadata;
 mod cli;
 mod config;
+mod debuginfo;
 mod error;
+mod header;
+mod inspect;
+mod srpm;
 
 use config::Config;
-use error::Error;
+use error::{CargoMetadataError, Error};
 
+/// Returns the path of the package file named `file_name`. `output` is taken as a directory
+/// if it is one, or if `output_is_dir` is set even before it is created.
 fn determine_output_dir(
     output: Option<&PathBuf>,
+    output_is_dir: bool,
     file_name: &str,
     build_target: BuildTarget,
 ) -> PathBuf {
     match output.as_ref().map(PathBuf::from) {
-        Some(path) if path.is_dir() => path.join(file_name),
+        Some(path) if output_is_dir || path.is_dir() => path.join(file_name),
         Some(path) => path,
         None => build_target.target_path("generate-rpm").join(file_name),
     }
 }
 
+/// Rejects `output` that is not usable as the directory of multiple packages, i.e. an existing
+/// file or a path with the `.rpm` extension.
+fn check_output_dir(output: &Path) -> Result<(), Error> {
+    let is_file_name = output.extension().is_some_and(|v| v == "rpm");
+    if (output.exists() && !output.is_dir()) || (!output.exists() && is_file_name) {
+        return Err(Error::OutputNotDir(output.to_path_buf()));
+    }
+    Ok(())
+}
+
+fn write_package(rpm_pkg: &rpm::Package, target_file_name: &Path) -> Result<(), Error> {
+    if let Some(parent_dir) = target_file_name.parent()
+        && !parent_dir.exists()
+    {
+        fs::create_dir_all(parent_dir)
+            .map_err(|err| Error::FileIo(parent_dir.to_path_buf(), err))?;
+    }
+    let mut f = fs::File::create(target_file_name)
+        .map_err(|err| Error::FileIo(target_file_name.to_path_buf(), err))?;
+
+    rpm_pkg.write(&mut f)?;
+    Ok(())
+}
+
+/// Package selected to be generated.
+struct SelectedPackage {
+    /// Name shown in the summary.
+    label: String,
+    /// Directory of the package, or `None` for the current directory.
+    dir: Option<PathBuf>,
+    /// Root of the workspace, or `None` to look it up in the parent directories.
+    workspace_root: Option<PathBuf>,
+    target_directory: Option<PathBuf>,
+}
+
+impl SelectedPackage {
+    fn new(label: &str, package: Option<&MetadataPackage>, metadata: &CargoMetadata) -> Self {
+        Self {
+            label: label.to_string(),
+            dir: package.map(|v| relative_to_current_dir(v.dir())),
+            workspace_root: Some(metadata.workspace_root.clone()),
+            target_directory: Some(relative_to_current_dir(&metadata.target_directory)),
+        }
+    }
+}
+
+/// Returns `path` relative to the current directory if it is located below, so that the
+/// paths shown and written in the spec file are the same as before.
+fn relative_to_current_dir(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .ok()
+        .and_then(|v| path.strip_prefix(v).ok())
+        .filter(|v| !v.as_os_str().is_empty())
+        .map_or(path.to_path_buf(), Path::to_path_buf)
+}
+
+/// Resolves `--workspace` and `--package`, which is either the name of a member of the
+/// workspace or the directory of a package, with `cargo metadata`.
+///
+/// The package in the current directory is selected without `cargo metadata` if cargo cannot
+/// be executed.
+fn select_packages(args: &Cli) -> Result<Vec<SelectedPackage>, Error> {
+    if args.workspace {
+        let metadata = CargoMetadata::load(None)?;
+        return Ok(metadata
+            .packages_with_metadata()
+            .into_iter()
+            .map(|v| SelectedPackage::new(&v.name, Some(v), &metadata))
+            .collect());
+    }
+    if args.package.is_empty() {
+        let package = match CargoMetadata::load(None) {
+            Ok(metadata) => SelectedPackage::new(".", None, &metadata),
+            Err(Error::CargoMetadata(CargoMetadataError::Process(_))) => SelectedPackage {
+                label: ".".to_string(),
+                dir: None,
+                workspace_root: None,
+                target_directory: None,
+            },
+            Err(err) => return Err(err),
+        };
+        return Ok(vec![package]);
+    }
+
+    let mut packages = Vec::new();
+    let mut current_metadata = None;
+    for spec in &args.package {
+        let manifest_path = Path::new(spec).join("Cargo.toml");
+        let package = if manifest_path.is_file() {
+            // the package may belong to another workspace than the current directory
+            let metadata = CargoMetadata::load(Some(&manifest_path))?;
+            SelectedPackage {
+                dir: Some(PathBuf::from(spec)),
+                ..SelectedPackage::new(spec, None, &metadata)
+            }
+        } else {
+            if current_metadata.is_none() {
+                current_metadata = Some(CargoMetadata::load(None)?);
+            }
+            let metadata = current_metadata.as_ref().unwrap();
+            SelectedPackage::new(spec, Some(metadata.package(spec)?), metadata)
+        };
+        packages.push(package);
+    }
+    Ok(packages)
+}
+
+/// Creates the builders of the main package and its subpackages, showing the warnings on them.
+fn create_builders(config: &Config, cfg: BuilderConfig) -> Result<Vec<PackageBuilder>, Error> {
+    let mut builders = vec![config.create_rpm_builder(cfg)?];
+    for name in config.subpackage_names()? {
+        builders.push(config.create_subpackage_rpm_builder(cfg, &name)?);
+    }
+    for warning in builders.iter().flat_map(|v| v.warnings()) {
+        eprintln!("warning: {warning}");
+    }
+    Ok(builders)
+}
+
+/// Package built from the plan by `--dry-run`, which is not written.
+struct PlannedPackage {
+    pkg: rpm::Package,
+    sources: Vec<(PathBuf, String)>,
+    output: PathBuf,
+}
+
+/// Generates the RPM packages of the `package` and returns the paths of the package files.
+/// `--output` is the directory of the package files if `output_is_dir` is set.
+///
+/// With `--dry-run`, nothing is built nor written, and the packages are added to `plans`.
+fn generate(
+    args: &Cli,
+    extra_metadata: &[ExtraMetadataSource],
+    signer: Option<&Signer>,
+    package: &SelectedPackage,
+    output_is_dir: bool,
+    plans: &mut Vec<PlannedPackage>,
+) -> Result<Vec<PathBuf>, Error> {
+    let package_dir = package.dir.as_deref().unwrap_or(Path::new(""));
+    let config = Config::new(
+        package_dir,
+        package.workspace_root.as_deref(),
+        extra_metadata,
+    )?;
+    let mut build_target = BuildTarget::new(args, package_dir)?;
+    if let Some(target_directory) = &package.target_directory {
+        build_target = build_target.with_default_target_dir(target_directory.clone());
+    }
+    let build_target = &build_target;
+    let package = package.dir.as_deref();
+
+    if args.build && args.dry_run.is_none() {
+        let build_output = build::cargo_build(args, build_target, package)?;
+        let mut sources = config.expand_assets(build_target, None)?;
+        for name in config.subpackage_names()? {
+            sources.extend(config.expand_assets(build_target, Some(&name))?);
+        }
+        build_output.check_sources(&sources, &build_target.build_target_path())?;
+    }
+
+    if args.srpm {
+        // The dependencies discovered automatically are left to rpmbuild.
+        let srpm_args = Cli {
+            auto_req: AutoReqMode::Disabled,
+            auto_prov: AutoProvMode::Disabled,
+            ..args.clone()
+        };
+        let cfg = BuilderConfig::new(build_target, &srpm_args).plan();
+        let builders = create_builders(&config, cfg)?;
+        let mut rpm_pkg = srpm::build_source_package(builders, build_target, args, package)?;
+        if let Some(signer) = signer {
+            match config::source_date(args)? {
+                Some(t) => rpm_pkg.sign_with_timestamp(signer.clone(), t)?,
+                None => rpm_pkg.sign(signer.clone())?,
+            }
+        }
+        let file_name = format!(
+            "{}-{}-{}.src.rpm",
+            rpm_pkg.metadata.get_name()?,
+            rpm_pkg.metadata.get_version()?,
+            rpm_pkg.metadata.get_release()?,
+        );
+        let target_file_name = determine_output_dir(
+            args.output.as_ref(),
+            output_is_dir,
+            &file_name,
+            build_target.clone(),
+        );
+        write_package(&rpm_pkg, &target_file_name)?;
+        return Ok(vec![target_file_name]);
+    }
+
+    let cfg = match args.dry_run {
+        Some(_) => BuilderConfig::new(build_target, args).plan(),
+        None => BuilderConfig::new(build_target, args),
+    };
+    let mut rpm_builders = create_builders(&config, cfg)?;
+    let debuginfo_builders = rpm_builders
+        .iter_mut()
+        .filter_map(|v| v.take_debuginfo())
+        .collect::<Vec<_>>();
+    rpm_builders.extend(debuginfo_builders);
+
+    let mut outputs = Vec::<PathBuf>::new();
+    for rpm_builder in rpm_builders {
+        let sources = rpm_builder.sources().to_vec();
+        let rpm_pkg = match signer {
+            Some(signer) if args.dry_run.is_none() => rpm_builder.build_and_sign(signer.clone()),
+            _ => rpm_builder.build(),
+        }?;
+
+        let pkg_name = rpm_pkg.metadata.get_name()?;
+        let pkg_version = rpm_pkg.metadata.get_version()?;
+        let pkg_release = rpm_pkg
+            .metadata
+            .get_release()
+            .map(|v| format!("-{}", v))
+            .unwrap_or_default();
+        let pkg_arch = rpm_pkg
+            .metadata
+            .get_arch()
+            .map(|v| format!(".{}", v))
+            .unwrap_or_default();
+        let file_name = format!("{pkg_name}-{pkg_version}{pkg_release}{pkg_arch}.rpm");
+
+        // the subpackages are placed next to the main package
+        let target_file_name = match outputs.first() {
+            Some(main_file_name) => main_file_name.with_file_name(&file_name),
+            None => determine_output_dir(
+                args.output.as_ref(),
+                output_is_dir,
+                &file_name,
+                build_target.clone(),
+            ),
+        };
+
+        if args.dry_run.is_some() {
+            plans.push(PlannedPackage {
+                pkg: rpm_pkg,
+                sources,
+                output: target_file_name.clone(),
+            });
+        } else {
+            write_package(&rpm_pkg, &target_file_name)?;
+        }
+        outputs.push(target_file_name);
+    }
+
+    Ok(outputs)
+}
+
+/// Prints the `plans` of all the packages at once with `--dry-run`.
+fn print_plans(args: &Cli, plans: &[PlannedPackage]) -> Result<(), Error> {
+    let Some(format) = args.dry_run else {
+        return Ok(());
+    };
+    let plans = plans
+        .iter()
+        .map(|v| {
+            let plan = inspect::PackagePlan {
+                sources: &v.sources,
+                output: &v.output,
+            };
+            (&v.pkg.metadata, plan)
+        })
+        .collect::<Vec<_>>();
+    inspect::print_plans(&plans, format == OutputFormat::Json, &mut std::io::stdout())
+}
+
 fn run() -> Result<(), Error> {
     let (args, matches) = Cli::get_matches_and_try_parse().unwrap_or_else(|e| e.exit());
 
-    let build_target = BuildTarget::new(&args);
-    let extra_metadata = args.extra_metadata(&matches);
+    if let Some(Commands::Inspect(inspect_args)) = &args.command {
+        return inspect::inspect(
+            &inspect_args.file,
+            inspect_args.json,
+            &mut std::io::stdout(),
+        );
+    }
 
-    let config = if let Some(p) = &args.package {
-        Config::new(Path::new(p), Some(Path::new("")), &extra_metadata)?
-    } else {
-        Config::new(Path::new(""), None, &extra_metadata)?
-    };
+    let extra_metadata = args.extra_metadata(&matches);
 
     let signer = if let Some(keyfile_path) = &args.signing_key {
         let key: Vec<u8> = fs::read(keyfile_path)
@@ -47,42 +330,61 @@ fn run() -> Result<(), Error> {
     }
     .transpose()?;
 
-    let rpm_builder = config.create_rpm_builder(BuilderConfig::new(&build_target, &args))?;
+    let packages = select_packages(&args)?;
+    let mut plans = Vec::new();
+    if let [package] = packages.as_slice() {
+        generate(
+            &args,
+            &extra_metadata,
+            signer.as_ref(),
+            package,
+            false,
+            &mut plans,
+        )?;
+        return print_plans(&args, &plans);
+    }
 
-    let rpm_pkg = if let Some(signer) = signer {
-        rpm_builder.build_and_sign(signer)
-    } else {
-        rpm_builder.build()
-    }?;
-
-    let pkg_name = rpm_pkg.metadata.get_name()?;
-    let pkg_version = rpm_pkg.metadata.get_version()?;
-    let pkg_release = rpm_pkg
-        .metadata
-        .get_release()
-        .map(|v| format!("-{}", v))
-        .unwrap_or_default();
-    let pkg_arch = rpm_pkg
-        .metadata
-        .get_arch()
-        .map(|v| format!(".{}", v))
-        .unwrap_or_default();
-    let file_name = format!("{pkg_name}-{pkg_version}{pkg_release}{pkg_arch}.rpm");
-
-    let target_file_name = determine_output_dir(args.output.as_ref(), &file_name, build_target);
-
-    if let Some(parent_dir) = target_file_name.parent() {
-        if !parent_dir.exists() {
-            fs::create_dir_all(parent_dir)
-                .map_err(|err| Error::FileIo(parent_dir.to_path_buf(), err))?;
+    // the packages of the members are placed in the same directory
+    if let Some(output) = &args.output {
+        check_output_dir(output)?;
+        if args.dry_run.is_none() {
+            fs::create_dir_all(output).map_err(|err| Error::FileIo(output.clone(), err))?;
         }
     }
-    let mut f = fs::File::create(&target_file_name)
-        .map_err(|err| Error::FileIo(target_file_name.to_path_buf(), err))?;
-
-    rpm_pkg.write(&mut f)?;
+    let mut failures = 0;
+    let mut summary = Vec::new();
+    for package in &packages {
+        let name = &package.label;
+        match generate(
+            &args,
+            &extra_metadata,
+            signer.as_ref(),
+            package,
+            true,
+            &mut plans,
+        ) {
+            Ok(outputs) => {
+                for output in outputs {
+                    summary.push(format!("{name}: {}", output.display()));
+                }
+            }
+            Err(err) => {
+                failures += 1;
+                summary.push(format!("{name}: error: {err}"));
+            }
+        }
+    }
+    print_plans(&args, &plans)?;
+    eprintln!("Summary:");
+    for line in summary {
+        eprintln!("  {line}");
+    }
 
-    Ok(())
+    if failures > 0 {
+        Err(Error::PackagesFailed(failures, packages.len()))
+    } else {
+        Ok(())
+    }
 }
 
 fn main() {
@@ -106,9 +408,9 @@ mod tests {
 
         let output = Some(pathbufbinding);
         let file_name = "test.rpm";
-        let build_target = BuildTarget::new(&crate::cli::Cli::default());
+        let build_target = BuildTarget::from_args(&crate::cli::Cli::default());
 
-        let target_file_name = determine_output_dir(output, file_name, build_target);
+        let target_file_name = determine_output_dir(output, false, file_name, build_target);
         assert_eq!(target_file_name, tempdir.path().join("test.rpm"));
     }
     #[test]
@@ -119,22 +421,46 @@ mod tests {
 
         let output = Some(&temppath);
         let file_name = "test.rpm";
-        let build_target = BuildTarget::new(&crate::cli::Cli::default());
+        let build_target = BuildTarget::from_args(&crate::cli::Cli::default());
 
-        let target_file_name = determine_output_dir(output, file_name, build_target);
+        let target_file_name = determine_output_dir(output, false, file_name, build_target.clone());
         assert_eq!(target_file_name, temppath);
+
+        // the directory of multiple packages, which is not created yet with `--dry-run`
+        let target_file_name = determine_output_dir(output, true, file_name, build_target);
+        assert_eq!(target_file_name, temppath.join("test.rpm"));
     }
 
     #[test]
     fn test_no_output_specified() {
         let output = None;
         let file_name = "test.rpm";
-        let build_target = BuildTarget::new(&crate::cli::Cli::default());
+        let build_target = BuildTarget::from_args(&crate::cli::Cli::default());
 
-        let target_file_name = determine_output_dir(output, file_name, build_target);
+        let target_file_name = determine_output_dir(output, false, file_name, build_target);
         assert_eq!(
             target_file_name,
             PathBuf::from("target/generate-rpm/test.rpm")
         );
     }
+
+    #[test]
+    fn test_check_output_dir() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file = tempdir.path().join("foo.txt");
+        fs::write(&file, "").unwrap();
+        fs::create_dir(tempdir.path().join("bar.rpm")).unwrap();
+
+        assert!(check_output_dir(tempdir.path()).is_ok());
+        assert!(check_output_dir(&tempdir.path().join("bar.rpm")).is_ok());
+        assert!(check_output_dir(&tempdir.path().join("baz")).is_ok());
+        assert!(matches!(
+            check_output_dir(&file),
+            Err(Error::OutputNotDir(v)) if v == file
+        ));
+        assert!(matches!(
+            check_output_dir(&tempdir.path().join("foo.rpm")),
+            Err(Error::OutputNotDir(_))
+        ));
+    }
 }
@@ -0,0 +1,145 @@
+//! Conversion of a package built by the `rpm` crate into a source package.
+//!
+//! The `rpm` crate always marks its packages as binary ones with the `SOURCERPM` tag.
+//! Hence the header is re-serialized replacing it with the `SOURCEPACKAGE` tag.
+
+use rpm::{FileFlags, Package};
+
+use crate::error::Error;
+use crate::header::{
+    Entry, RPM_INT32_TYPE, RPMTAG_BASENAMES, RPMTAG_DIRINDEXES, RPMTAG_DIRNAMES, RPMTAG_FILEFLAGS,
+    RPMTAG_SOURCEPACKAGE, RPMTAG_SOURCERPM, rewrite_package,
+};
+
+/// Converts `pkg` into a source package.
+///
+/// The files are placed without directories as rpm does, and the file named `spec_file` is
+/// flagged as the spec file. The returned package is not signed.
+///
+/// The payload written by the `rpm` crate names the files such as `./foo.spec`, which is kept
+/// as it is: rpm looks up the payload entries in the header ignoring the leading `./` and `/`
+/// (`rpmfnFindFN`), which is how it reads the source packages of rpmbuild naming `foo.spec`.
+pub(super) fn into_source_package(pkg: Package, spec_file: &str) -> Result<Package, Error> {
+    rewrite_package(pkg, |lead, entries| {
+        // package type in the lead: source
+        lead[6..8].copy_from_slice(&1u16.to_be_bytes());
+
+        entries.retain(|v| v.tag != RPMTAG_SOURCERPM);
+        entries.push(Entry {
+            tag: RPMTAG_SOURCEPACKAGE,
+            kind: RPM_INT32_TYPE,
+            count: 1,
+            data: 1u32.to_be_bytes().to_vec(),
+        });
+
+        let base_names = entries
+            .iter()
+            .find(|v| v.tag == RPMTAG_BASENAMES)
+            .map(Entry::strings)
+            .unwrap_or_default();
+        for entry in entries.iter_mut() {
+            match entry.tag {
+                // all the files are placed in the only one directory
+                RPMTAG_DIRNAMES => entry.set_strings(&[String::new()]),
+                RPMTAG_DIRINDEXES => entry.data.fill(0),
+                RPMTAG_FILEFLAGS => {
+                    let flags = entry
+                        .u32s()
+                        .into_iter()
+                        .zip(&base_names)
+                        .map(|(flags, name)| match name == spec_file {
+                            true => flags | FileFlags::SPECFILE.bits(),
+                            false => flags,
+                        })
+                        .collect::<Vec<_>>();
+                    entry.set_u32s(&flags);
+                }
+                _ => {}
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rpm::{BuildConfig, CompressionType, FileOptions, PackageBuilder};
+
+    /// Returns the names of the entries in the uncompressed cpio (newc) payload.
+    fn payload_names(payload: &[u8]) -> Vec<String> {
+        let field = |offset: usize, idx: usize| {
+            let start = offset + 6 + idx * 8;
+            let hex = std::str::from_utf8(&payload[start..start + 8]).unwrap();
+            usize::from_str_radix(hex, 16).unwrap()
+        };
+        let align = |v: usize| v.div_ceil(4) * 4;
+        let mut names = Vec::new();
+        let mut offset = 0;
+        loop {
+            let (file_size, name_size) = (field(offset, 6), field(offset, 11));
+            let name = &payload[offset + 110..offset + 110 + name_size - 1];
+            let name = String::from_utf8(name.to_vec()).unwrap();
+            if name == "TRAILER!!!" {
+                return names;
+            }
+            names.push(name);
+            offset = align(align(offset + 110 + name_size) + file_size);
+        }
+    }
+
+    #[test]
+    fn test_into_source_package() {
+        let pkg = PackageBuilder::new("foo", "1.2.0", "MIT", "x86_64", "foo")
+            .using_config(BuildConfig::default().compression(CompressionType::None))
+            .with_file_contents("Name: foo", FileOptions::new("/foo.spec"))
+            .unwrap()
+            .with_file_contents("", FileOptions::new("/foo-1.2.0.tar.gz"))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(!pkg.metadata.is_source_package());
+
+        let pkg = into_source_package(pkg, "foo.spec").unwrap();
+        assert!(pkg.metadata.is_source_package());
+        assert!(pkg.metadata.get_source_rpm().is_err());
+        assert_eq!(pkg.metadata.get_name().unwrap(), "foo");
+        assert_eq!(pkg.metadata.get_version().unwrap(), "1.2.0");
+        pkg.verify_digests().unwrap();
+
+        let files = pkg.metadata.get_file_entries().unwrap();
+        assert_eq!(files.len(), 2);
+        let spec = files
+            .iter()
+            .find(|v| v.path.file_name().unwrap() == "foo.spec")
+            .unwrap();
+        assert_eq!(spec.path, std::path::Path::new("foo.spec"));
+        assert!(spec.flags.contains(FileFlags::SPECFILE));
+        let tarball = files
+            .iter()
+            .find(|v| v.path.file_name().unwrap() == "foo-1.2.0.tar.gz")
+            .unwrap();
+        assert!(!tarball.flags.contains(FileFlags::SPECFILE));
+
+        // the same paths as the header once the leading `./` is ignored as rpm does
+        let names = payload_names(&pkg.content);
+        let mut paths = files
+            .iter()
+            .map(|v| v.path.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(names, ["./foo-1.2.0.tar.gz", "./foo.spec"]);
+        assert_eq!(
+            names
+                .iter()
+                .map(|v| v.trim_start_matches("./"))
+                .collect::<Vec<_>>(),
+            paths
+        );
+
+        let mut bytes = Vec::new();
+        pkg.write(&mut bytes).unwrap();
+        let pkg = Package::parse(&mut bytes.as_slice()).unwrap();
+        assert!(pkg.metadata.is_source_package());
+        assert_eq!(pkg.files().unwrap().count(), 2);
+    }
+}
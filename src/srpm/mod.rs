@@ -0,0 +1,251 @@
+use rpm::{Dependency, FileOptions};
+use std::path::{Path, PathBuf};
+
+use crate::build_target::BuildTarget;
+use crate::cli::Cli;
+use crate::config::{PackageBuilder, source_date};
+use crate::error::{Error, SrpmError};
+use crate::header::{
+    RPMTAG_BUGURL, RPMTAG_DISTRIBUTION, RPMTAG_GROUP, RPMTAG_PACKAGER, set_string_tags, string_tag,
+};
+use header::into_source_package;
+use spec::{SpecPackage, write_spec};
+use tarball::create_tarball;
+
+mod header;
+mod spec;
+mod tarball;
+
+/// Builds the source package, from which `rpmbuild --rebuild` reproduces the binary packages
+/// planned by `builders`, the main package followed by its subpackages.
+///
+/// The source package contains the spec file and the tarball of the files tracked by git
+/// including the dependencies vendored by `cargo vendor`.
+pub fn build_source_package(
+    builders: Vec<PackageBuilder>,
+    build_target: &BuildTarget,
+    args: &Cli,
+    package: Option<&Path>,
+) -> Result<rpm::Package, Error> {
+    let base = std::env::current_dir()?.canonicalize()?;
+    let target_dir = build_target.build_target_path();
+
+    let mut debuginfo = false;
+    let mut packages = Vec::with_capacity(builders.len());
+    for builder in builders {
+        debuginfo |= builder.debuginfo_enabled();
+        let sources = relative_sources(builder.sources().to_vec(), &base, &target_dir)?;
+        packages.push((builder.build()?, sources));
+    }
+
+    let main = &packages[0].0.metadata;
+    let name = main.get_name()?;
+    let version = main.get_version()?;
+    let source = format!("{name}-{version}.tar.gz");
+    let spec_file = format!("{name}.spec");
+    let source_date = source_date(args)?;
+
+    let spec_packages = packages
+        .iter()
+        .map(|(pkg, sources)| SpecPackage {
+            metadata: &pkg.metadata,
+            sources,
+        })
+        .collect::<Vec<_>>();
+    let mut spec = Vec::new();
+    write_spec(
+        &spec_packages,
+        &source,
+        &build_command(args, build_target, package),
+        debuginfo,
+        &mut spec,
+    )?;
+
+    let mtime = match source_date {
+        Some(t) => t as u64,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|v| v.as_secs())
+            .unwrap_or_default(),
+    };
+    let tarball = create_tarball(&format!("{name}-{version}"), &base, build_target, mtime)?;
+
+    let build_config = rpm::BuildConfig::default().compression(args.payload_compress);
+    let mut builder = rpm::PackageBuilder::new(
+        name,
+        version,
+        main.get_license()?,
+        main.get_arch()?,
+        main.get_summary()?,
+    )
+    .using_config(build_config)
+    .epoch(main.get_epoch().unwrap_or_default())
+    .release(main.get_release()?)
+    .description(main.get_description()?)
+    .requires(Dependency::any("cargo"))
+    .with_file_contents(
+        spec,
+        FileOptions::new(format!("/{spec_file}")).mode(0o100644),
+    )?
+    .with_file_contents(
+        tarball,
+        FileOptions::new(format!("/{source}")).mode(0o100644),
+    )?;
+    if let Ok(url) = main.get_url()
+        && !url.is_empty()
+    {
+        builder = builder.url(url);
+    }
+    if let Ok(vendor) = main.get_vendor()
+        && !vendor.is_empty()
+    {
+        builder = builder.vendor(vendor);
+    }
+    if let Ok(vcs) = main.get_vcs() {
+        builder = builder.vcs(vcs);
+    }
+    if let Ok(build_host) = main.get_build_host() {
+        builder = builder.build_host(build_host);
+    }
+    if let Some(t) = source_date {
+        builder = builder.source_date(t);
+    }
+
+    let mut string_tags = Vec::new();
+    for tag in [
+        RPMTAG_GROUP,
+        RPMTAG_PACKAGER,
+        RPMTAG_DISTRIBUTION,
+        RPMTAG_BUGURL,
+    ] {
+        if let Some(value) = string_tag(main, tag)? {
+            string_tags.push((tag, value));
+        }
+    }
+    let pkg = set_string_tags(builder.build()?, &string_tags)?;
+    into_source_package(pkg, &spec_file)
+}
+
+/// Converts the source paths into the ones relative to `base`, the top of the source tree.
+///
+/// The outputs of the build in `target_dir` are moved to `target` of the source tree, where
+/// the spec file builds them.
+fn relative_sources(
+    sources: Vec<(PathBuf, String)>,
+    base: &Path,
+    target_dir: &Path,
+) -> Result<Vec<(PathBuf, String)>, SrpmError> {
+    sources
+        .into_iter()
+        .map(|(src, dst)| {
+            let relative = if let Ok(v) = src.strip_prefix(target_dir) {
+                Some(Path::new("target").join(v))
+            } else if src.is_absolute() {
+                src.strip_prefix(base).ok().map(Path::to_path_buf)
+            } else {
+                Some(src.clone())
+            };
+            match relative {
+                Some(v) if !v.starts_with("..") => Ok((v, dst)),
+                _ => Err(SrpmError::SourceOutsideTree(src)),
+            }
+        })
+        .collect()
+}
+
+/// Returns the command line to build the binaries of the `package` in the `%build` section.
+fn build_command(args: &Cli, build_target: &BuildTarget, package: Option<&Path>) -> String {
+    let mut command = vec!["cargo build --offline --locked".to_string()];
+    match build_target.profile() {
+        "release" => command.push("--release".to_string()),
+        profile => command.push(format!("--profile {profile}")),
+    }
+    if let Some(target) = build_target.target() {
+        command.push(format!("--target {target}"));
+    }
+    command.push("--target-dir target".to_string());
+    if let Some(package) = package {
+        let manifest_path = package.join("Cargo.toml");
+        command.push(format!("--manifest-path {}", manifest_path.display()));
+    }
+    command.extend(args.feature_args());
+    command.join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_relative_sources() {
+        let base = Path::new("/path/to/project");
+        let target_dir = Path::new("target");
+        let sources = vec![
+            (
+                PathBuf::from("target/release/foo"),
+                "/usr/bin/foo".to_string(),
+            ),
+            (
+                PathBuf::from("/path/to/project/README.md"),
+                "/usr/share/doc/foo/README.md".to_string(),
+            ),
+        ];
+        assert_eq!(
+            relative_sources(sources, base, target_dir).unwrap(),
+            vec![
+                (
+                    PathBuf::from("target/release/foo"),
+                    "/usr/bin/foo".to_string()
+                ),
+                (
+                    PathBuf::from("README.md"),
+                    "/usr/share/doc/foo/README.md".to_string()
+                ),
+            ]
+        );
+
+        let sources = vec![(PathBuf::from("/etc/foo"), "/etc/foo".to_string())];
+        assert!(matches!(
+            relative_sources(sources, base, target_dir),
+            Err(SrpmError::SourceOutsideTree(v)) if v == Path::new("/etc/foo")
+        ));
+        let sources = vec![(PathBuf::from("../foo"), "/etc/foo".to_string())];
+        assert!(relative_sources(sources, base, target_dir).is_err());
+
+        let sources = vec![(
+            PathBuf::from("/tmp/target/release/foo"),
+            "/usr/bin/foo".to_string(),
+        )];
+        assert_eq!(
+            relative_sources(sources, base, Path::new("/tmp/target")).unwrap(),
+            vec![(
+                PathBuf::from("target/release/foo"),
+                "/usr/bin/foo".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_build_command() {
+        let args = Cli::default();
+        assert_eq!(
+            build_command(&args, &BuildTarget::from_args(&args), None),
+            "cargo build --offline --locked --release --target-dir target"
+        );
+        let args = Cli {
+            profile: "dev".to_string(),
+            target: Some("aarch64-unknown-linux-gnu".to_string()),
+            target_dir: Some("/tmp/target".to_string()),
+            features: vec!["bar".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            build_command(
+                &args,
+                &BuildTarget::from_args(&args),
+                Some(Path::new("foo"))
+            ),
+            "cargo build --offline --locked --profile dev --target aarch64-unknown-linux-gnu --target-dir target --manifest-path foo/Cargo.toml --features bar"
+        );
+    }
+}
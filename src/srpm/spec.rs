@@ -0,0 +1,547 @@
+use rpm::{
+    DependencyFlags, FileEntry, FileFlags, FileVerifyFlags, PackageMetadata, ScriptletFlags,
+};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::format_date;
+use crate::error::Error;
+use crate::header::{
+    RPMTAG_BUGURL, RPMTAG_DISTRIBUTION, RPMTAG_GROUP, RPMTAG_PACKAGER, file_verify_flags,
+    string_tag,
+};
+use crate::inspect::{DEPENDENCY_KINDS, SCRIPTLET_KINDS, format_dependency};
+
+const DEPENDENCY_TAGS: [(&str, &str); 8] = [
+    ("requires", "Requires"),
+    ("provides", "Provides"),
+    ("conflicts", "Conflicts"),
+    ("obsoletes", "Obsoletes"),
+    ("recommends", "Recommends"),
+    ("suggests", "Suggests"),
+    ("enhances", "Enhances"),
+    ("supplements", "Supplements"),
+];
+
+const VERIFY_ATTRIBUTES: [(FileVerifyFlags, &str); 9] = [
+    (FileVerifyFlags::FILEDIGEST, "md5"),
+    (FileVerifyFlags::FILESIZE, "size"),
+    (FileVerifyFlags::LINKTO, "link"),
+    (FileVerifyFlags::USER, "user"),
+    (FileVerifyFlags::GROUP, "group"),
+    (FileVerifyFlags::MTIME, "mtime"),
+    (FileVerifyFlags::MODE, "mode"),
+    (FileVerifyFlags::RDEV, "rdev"),
+    (FileVerifyFlags::CAPS, "caps"),
+];
+
+const SCRIPTLET_SECTIONS: [(&str, &str); 8] = [
+    ("pre_install", "%pre"),
+    ("post_install", "%post"),
+    ("pre_uninstall", "%preun"),
+    ("post_uninstall", "%postun"),
+    ("pre_trans", "%pretrans"),
+    ("post_trans", "%posttrans"),
+    ("pre_untrans", "%preuntrans"),
+    ("post_untrans", "%postuntrans"),
+];
+
+/// A binary package to be described in the spec file
+#[derive(Debug)]
+pub(super) struct SpecPackage<'a> {
+    pub(super) metadata: &'a PackageMetadata,
+    /// Pairs of the source path relative to the top directory of the source tarball and the
+    /// destination of the packaged files
+    pub(super) sources: &'a [(PathBuf, String)],
+}
+
+impl SpecPackage<'_> {
+    fn source_of(&self, dest: &Path) -> Option<&Path> {
+        self.sources
+            .iter()
+            .find(|(_, d)| Path::new(d) == dest)
+            .map(|(s, _)| s.as_path())
+    }
+}
+
+/// Write the spec file that rebuilds `packages` from the tarball `source`.
+///
+/// The first one of `packages` is the main package and the others are its subpackages.
+/// rpmbuild extracts the debug info into the `-debuginfo` packages if `debuginfo`.
+pub(super) fn write_spec<W: Write>(
+    packages: &[SpecPackage],
+    source: &str,
+    build_command: &str,
+    debuginfo: bool,
+    out: &mut W,
+) -> Result<(), Error> {
+    let main = packages[0].metadata;
+    let main_name = main.get_name()?;
+
+    let mut globals = Vec::new();
+    if !debuginfo {
+        globals.push("debug_package %{nil}".to_string());
+    }
+    if let Ok(build_host) = main.get_build_host()
+        && !build_host.is_empty()
+    {
+        globals.push(format!("_buildhost {build_host}"));
+    }
+    for global in &globals {
+        writeln!(out, "%global {global}")?;
+    }
+    if !globals.is_empty() {
+        writeln!(out)?;
+    }
+    writeln!(out, "Name:           {main_name}")?;
+    writeln!(out, "Version:        {}", main.get_version()?)?;
+    writeln!(out, "Release:        {}", main.get_release()?)?;
+    if let Ok(epoch) = main.get_epoch()
+        && epoch != 0
+    {
+        writeln!(out, "Epoch:          {epoch}")?;
+    }
+    writeln!(out, "Summary:        {}", main.get_summary()?)?;
+    writeln!(out, "License:        {}", main.get_license()?)?;
+    if let Ok(url) = main.get_url()
+        && !url.is_empty()
+    {
+        writeln!(out, "URL:            {url}")?;
+    }
+    if let Ok(vendor) = main.get_vendor()
+        && !vendor.is_empty()
+    {
+        writeln!(out, "Vendor:         {vendor}")?;
+    }
+    for (tag, value) in [
+        // the `rpm` crate sets `Unspecified` unless given
+        (
+            "Group",
+            string_tag(main, RPMTAG_GROUP)?.filter(|v| v != "Unspecified"),
+        ),
+        ("Packager", string_tag(main, RPMTAG_PACKAGER)?),
+        ("Distribution", string_tag(main, RPMTAG_DISTRIBUTION)?),
+        ("BugURL", string_tag(main, RPMTAG_BUGURL)?),
+        ("VCS", main.get_vcs().ok().map(str::to_string)),
+    ] {
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            writeln!(out, "{:<15} {value}", format!("{tag}:"))?;
+        }
+    }
+    writeln!(out, "Source0:        {source}")?;
+    writeln!(out, "BuildRequires:  cargo")?;
+    if main.get_arch()? == "noarch" {
+        writeln!(out, "BuildArch:      noarch")?;
+    }
+    write_dependencies(main, main_name, out)?;
+    writeln!(out)?;
+    writeln!(out, "%description")?;
+    writeln!(out, "{}", escape_macros(main.get_description()?.trim_end()))?;
+
+    for package in &packages[1..] {
+        let metadata = package.metadata;
+        let suffix = section_suffix(metadata.get_name()?, main_name);
+        writeln!(out)?;
+        writeln!(out, "%package{suffix}")?;
+        writeln!(out, "Summary:        {}", metadata.get_summary()?)?;
+        if metadata.get_license()? != main.get_license()? {
+            writeln!(out, "License:        {}", metadata.get_license()?)?;
+        }
+        if metadata.get_arch()? == "noarch" {
+            writeln!(out, "BuildArch:      noarch")?;
+        }
+        write_dependencies(metadata, main_name, out)?;
+        writeln!(out)?;
+        writeln!(out, "%description{suffix}")?;
+        writeln!(
+            out,
+            "{}",
+            escape_macros(metadata.get_description()?.trim_end())
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "%prep")?;
+    writeln!(out, "%setup -q")?;
+    writeln!(out)?;
+    writeln!(out, "%build")?;
+    writeln!(out, "{build_command}")?;
+    writeln!(out)?;
+    writeln!(out, "%install")?;
+    for package in packages {
+        for file in package.metadata.get_file_entries()? {
+            write_install_command(&file, package.source_of(&file.path), out)?;
+        }
+    }
+
+    for package in packages {
+        let suffix = section_suffix(package.metadata.get_name()?, main_name);
+        for (kind, getter) in SCRIPTLET_KINDS {
+            let Ok(scriptlet) = getter(package.metadata) else {
+                continue;
+            };
+            let section = lookup(&SCRIPTLET_SECTIONS, kind);
+            write!(out, "\n{section}{suffix}")?;
+            if let Some(flags) = scriptlet.flags {
+                if flags.contains(ScriptletFlags::EXPAND) {
+                    write!(out, " -e")?;
+                }
+                if flags.contains(ScriptletFlags::QFORMAT) {
+                    write!(out, " -q")?;
+                }
+            }
+            match scriptlet.program.as_deref() {
+                Some([program]) => write!(out, " -p {program}")?,
+                Some(program) if !program.is_empty() => {
+                    write!(out, " -p \"{}\"", program.join(" "))?
+                }
+                _ => {}
+            }
+            writeln!(out)?;
+            writeln!(out, "{}", escape_macros(scriptlet.script.trim_end()))?;
+        }
+    }
+
+    for package in packages {
+        let suffix = section_suffix(package.metadata.get_name()?, main_name);
+        writeln!(out)?;
+        writeln!(out, "%files{suffix}")?;
+        let verify_flags = file_verify_flags(package.metadata)?;
+        for (idx, file) in package.metadata.get_file_entries()?.iter().enumerate() {
+            let verify = verify_flags
+                .get(idx)
+                .copied()
+                .unwrap_or(FileVerifyFlags::all());
+            writeln!(out, "{}", file_directives(file, verify))?;
+        }
+    }
+
+    let changelog = main.get_changelog_entries()?;
+    if !changelog.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "%changelog")?;
+        for (idx, entry) in changelog.iter().enumerate() {
+            if idx > 0 {
+                writeln!(out)?;
+            }
+            writeln!(out, "* {} {}", format_date(entry.timestamp), entry.name)?;
+            writeln!(out, "{}", escape_macros(&entry.description))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Escapes `%` in the text of the sections such as `%description`, `%post` and `%changelog`,
+/// which rpmbuild expands the macros in.
+fn escape_macros(text: &str) -> String {
+    text.replace('%', "%%")
+}
+
+fn lookup(table: &[(&str, &'static str)], key: &str) -> &'static str {
+    table
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| *v)
+        .unwrap()
+}
+
+/// Returns the suffix of the section headers such as `%files` for the package `name`.
+fn section_suffix(name: &str, main_name: &str) -> String {
+    if name == main_name {
+        String::new()
+    } else if let Some(suffix) = name.strip_prefix(&format!("{main_name}-")) {
+        format!(" {suffix}")
+    } else {
+        format!(" -n {name}")
+    }
+}
+
+fn write_dependencies<W: Write>(
+    metadata: &PackageMetadata,
+    main_name: &str,
+    out: &mut W,
+) -> Result<(), Error> {
+    let name = metadata.get_name()?;
+    let arch_name = format!("{name}({})", metadata.get_arch()?);
+    for (kind, getter) in DEPENDENCY_KINDS {
+        let tag = lookup(&DEPENDENCY_TAGS, kind);
+        for dependency in getter(metadata).unwrap_or_default() {
+            let is_generated = match kind {
+                "requires" => dependency.name.starts_with("rpmlib("),
+                "provides" => dependency.name == name || dependency.name == arch_name,
+                _ => false,
+            };
+            if is_generated {
+                continue;
+            }
+            if kind == "requires"
+                && name != main_name
+                && dependency.name == main_name
+                && dependency.flags & DependencyFlags::EQUAL == DependencyFlags::EQUAL
+            {
+                // the requirement of a subpackage on the main package
+                writeln!(
+                    out,
+                    "{:<16}%{{name}} = %{{?epoch:%{{epoch}}:}}%{{version}}-%{{release}}",
+                    format!("{tag}:")
+                )?;
+                continue;
+            }
+            writeln!(
+                out,
+                "{:<16}{}",
+                format!("{tag}:"),
+                format_dependency(&dependency)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn write_install_command<W: Write>(
+    file: &FileEntry,
+    source: Option<&Path>,
+    out: &mut W,
+) -> Result<(), Error> {
+    let dest = quote(&format!("%{{buildroot}}{}", file.path.display()));
+    let mode = file.mode.raw_mode() & 0o7777;
+    match (file.mode.raw_mode() & 0o170000, source) {
+        _ if file.flags.contains(FileFlags::GHOST) => {}
+        (0o040000, _) => writeln!(out, "install -d -m {mode:04o} {dest}")?,
+        (0o120000, _) => {
+            let parent = file.path.parent().unwrap_or(Path::new("/"));
+            writeln!(
+                out,
+                "install -d {}",
+                quote(&format!("%{{buildroot}}{}", parent.display()))
+            )?;
+            writeln!(out, "ln -s {} {dest}", quote(&file.linkto))?;
+        }
+        (_, Some(source)) => writeln!(
+            out,
+            "install -D -p -m {mode:04o} {} {dest}",
+            quote(&source.to_string_lossy())
+        )?,
+        (_, None) => {}
+    }
+    Ok(())
+}
+
+/// Returns the line of the `%files` section for `file`.
+fn file_directives(file: &FileEntry, verify: FileVerifyFlags) -> String {
+    let mut line = String::new();
+    let file_type = file.mode.raw_mode() & 0o170000;
+    let mode = if file_type == 0o120000 {
+        "-".to_string()
+    } else {
+        format!("{:04o}", file.mode.raw_mode() & 0o7777)
+    };
+    line.push_str(&format!(
+        "%attr({mode}, {}, {}) ",
+        file.ownership.user, file.ownership.group
+    ));
+    if file_type == 0o040000 {
+        line.push_str("%dir ");
+    }
+    if file.flags.contains(FileFlags::CONFIG) {
+        let mut options = vec![];
+        if file.flags.contains(FileFlags::NOREPLACE) {
+            options.push("noreplace");
+        }
+        if file.flags.contains(FileFlags::MISSINGOK) {
+            options.push("missingok");
+        }
+        if options.is_empty() {
+            line.push_str("%config ");
+        } else {
+            line.push_str(&format!("%config({}) ", options.join(",")));
+        }
+    }
+    for (flag, directive) in [
+        (FileFlags::DOC, "%doc "),
+        (FileFlags::LICENSE, "%license "),
+        (FileFlags::README, "%readme "),
+        (FileFlags::GHOST, "%ghost "),
+        (FileFlags::ARTIFACT, "%artifact "),
+    ] {
+        if file.flags.contains(flag) {
+            line.push_str(directive);
+        }
+    }
+    if let Some(caps) = file.caps.as_ref().filter(|v| !v.is_empty()) {
+        line.push_str(&format!("%caps({caps}) "));
+    }
+    let not_verified = VERIFY_ATTRIBUTES
+        .iter()
+        .filter(|(flag, _)| !verify.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>();
+    if !not_verified.is_empty() {
+        line.push_str(&format!("%verify(not {}) ", not_verified.join(" ")));
+    }
+    line.push_str(&quote(&file.path.to_string_lossy()));
+    line
+}
+
+/// Quotes `value` if it contains white spaces.
+fn quote(value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("\"{value}\"")
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rpm::{Dependency, FileOptions, PackageBuilder, Scriptlet};
+
+    #[test]
+    fn test_section_suffix() {
+        assert_eq!(section_suffix("foo", "foo"), "");
+        assert_eq!(section_suffix("foo-devel", "foo"), " devel");
+        assert_eq!(section_suffix("bar", "foo"), " -n bar");
+    }
+
+    #[test]
+    fn test_write_spec() {
+        let main = PackageBuilder::new("foo", "1.2.0", "MIT", "x86_64", "foo summary")
+            .description("foo description, 100% Rust")
+            .epoch(1)
+            .release("2")
+            .url("https://example.com/foo")
+            .build_host("build.example.com")
+            .requires(Dependency::any("/bin/sh"))
+            .requires(Dependency::greater_eq("bar", "1.0"))
+            .provides(Dependency::any("baz"))
+            .post_install_script(
+                Scriptlet::new("printf '%s\\n' post")
+                    .prog(vec!["/bin/bash", "-e"])
+                    .flags(ScriptletFlags::EXPAND),
+            )
+            .add_changelog_entry(
+                "Foo <foo@example.com> - 1.2.0",
+                "- 100% rewritten",
+                1704196800,
+            )
+            .add_changelog_entry(
+                "Foo <foo@example.com> - 1.1.0",
+                "- Initial release",
+                1701432000,
+            )
+            .with_file_contents("", FileOptions::new("/usr/bin/foo").mode(0o100755))
+            .unwrap()
+            .with_file_contents(
+                "",
+                FileOptions::new("/etc/foo.conf")
+                    .mode(0o100644)
+                    .is_config_noreplace()
+                    .verify(
+                        FileVerifyFlags::all()
+                            - FileVerifyFlags::FILEDIGEST
+                            - FileVerifyFlags::MTIME,
+                    ),
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let tags = [
+            (RPMTAG_PACKAGER, "Foo <foo@example.com>".to_string()),
+            (RPMTAG_BUGURL, "https://example.com/foo/issues".to_string()),
+        ];
+        let main = crate::header::set_string_tags(main, &tags).unwrap();
+        let devel = PackageBuilder::new("foo-devel", "1.2.0", "MIT", "noarch", "devel summary")
+            .description("devel summary")
+            .epoch(1)
+            .release("2")
+            .requires(Dependency::eq("foo", "1:1.2.0-2"))
+            .with_file_contents("", FileOptions::new("/usr/include/foo.h").mode(0o100644))
+            .unwrap()
+            .build()
+            .unwrap();
+        let main_sources = [
+            (
+                PathBuf::from("target/release/foo"),
+                "/usr/bin/foo".to_string(),
+            ),
+            (PathBuf::from("foo.conf"), "/etc/foo.conf".to_string()),
+        ];
+        let devel_sources = [(
+            PathBuf::from("include/foo.h"),
+            "/usr/include/foo.h".to_string(),
+        )];
+        let packages = [
+            SpecPackage {
+                metadata: &main.metadata,
+                sources: &main_sources,
+            },
+            SpecPackage {
+                metadata: &devel.metadata,
+                sources: &devel_sources,
+            },
+        ];
+
+        let mut out = Vec::new();
+        write_spec(
+            &packages,
+            "foo-1.2.0.tar.gz",
+            "cargo build --release",
+            false,
+            &mut out,
+        )
+        .unwrap();
+        let spec = String::from_utf8(out).unwrap();
+
+        assert!(
+            spec.starts_with(
+                "%global debug_package %{nil}\n%global _buildhost build.example.com\n"
+            )
+        );
+        for line in [
+            "Name:           foo",
+            "Version:        1.2.0",
+            "Release:        2",
+            "Epoch:          1",
+            "Summary:        foo summary",
+            "License:        MIT",
+            "URL:            https://example.com/foo",
+            "Packager:       Foo <foo@example.com>",
+            "BugURL:         https://example.com/foo/issues",
+            "Source0:        foo-1.2.0.tar.gz",
+            "Requires:       /bin/sh",
+            "Requires:       bar >= 1.0",
+            "Provides:       baz",
+            "%description\nfoo description, 100%% Rust\n",
+            "%package devel\nSummary:        devel summary\nBuildArch:      noarch\n",
+            "Requires:       %{name} = %{?epoch:%{epoch}:}%{version}-%{release}",
+            "%build\ncargo build --release\n",
+            "install -D -p -m 0755 target/release/foo %{buildroot}/usr/bin/foo\n",
+            "install -D -p -m 0644 include/foo.h %{buildroot}/usr/include/foo.h\n",
+            "%post -e -p \"/bin/bash -e\"\nprintf '%%s\\n' post\n",
+            "%files\n",
+            "%attr(0644, root, root) %config(noreplace) %verify(not md5 mtime) /etc/foo.conf\n",
+            "%attr(0755, root, root) /usr/bin/foo\n",
+            "%files devel\n%attr(0644, root, root) /usr/include/foo.h\n",
+            "%changelog\n* Tue Jan 02 2024 Foo <foo@example.com> - 1.2.0\n- 100%% rewritten\n\n* Fri Dec 01 2023 Foo <foo@example.com> - 1.1.0\n- Initial release\n",
+        ] {
+            assert!(spec.contains(line), "{line:?} not in\n{spec}");
+        }
+        assert!(!spec.contains("rpmlib("));
+        assert!(!spec.contains("Group:"));
+        assert!(!spec.contains("Provides:       foo"));
+
+        let mut out = Vec::new();
+        write_spec(
+            &packages,
+            "foo-1.2.0.tar.gz",
+            "cargo build --release",
+            true,
+            &mut out,
+        )
+        .unwrap();
+        let spec = String::from_utf8(out).unwrap();
+        assert!(spec.starts_with("%global _buildhost build.example.com\n"));
+    }
+}
@@ -0,0 +1,253 @@
+use flate2::write::GzEncoder;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tar::{Builder, Header, HeaderMode};
+
+use crate::build_target::BuildTarget;
+use crate::error::{Error, SrpmError};
+
+/// Creates the gzipped tarball of the files tracked by git in `base` and the dependencies
+/// vendored by `cargo vendor`, whose top directory is `top_dir` and whose entries are
+/// timestamped with `mtime`.
+///
+/// The compressed archive is returned as a whole since the `rpm` crate takes the contents of
+/// the files in memory.
+pub(super) fn create_tarball(
+    top_dir: &str,
+    base: &Path,
+    build_target: &BuildTarget,
+    mtime: u64,
+) -> Result<Vec<u8>, Error> {
+    let vendor_dir = base
+        .join(build_target.target_path("generate-rpm"))
+        .join("vendor");
+    let cargo = std::env::var_os("CARGO").unwrap_or("cargo".into());
+    let output = Command::new(cargo)
+        .arg("vendor")
+        .arg("--versioned-dirs")
+        .arg(&vendor_dir)
+        .current_dir(base)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(SrpmError::VendorProcess)?;
+    if !output.status.success() {
+        Err(SrpmError::VendorFailed(output.status.to_string()))?;
+    }
+    // `cargo vendor` prints the configuration to use the vendored sources.
+    let vendor_config = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip_while(|v| !v.starts_with('['))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .replace(vendor_dir.to_string_lossy().as_ref(), "vendor");
+
+    let mut cargo_config =
+        fs::read_to_string(base.join(".cargo").join("config.toml")).unwrap_or_default();
+    cargo_config.push('\n');
+    cargo_config.push_str(&vendor_config);
+    cargo_config.push('\n');
+
+    let mut files = tracked_files(base)?;
+    // `--locked` requires `Cargo.lock`, which may be left untracked
+    if !files.iter().any(|v| v == Path::new("Cargo.lock")) && base.join("Cargo.lock").is_file() {
+        files.push(PathBuf::from("Cargo.lock"));
+    }
+
+    let top_dir = Path::new(top_dir);
+    let encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    let mut builder = Builder::new(encoder);
+    append_files(&mut builder, top_dir, base, &files, mtime)?;
+    // `cargo vendor` creates no directory if there is no dependency.
+    if vendor_dir.is_dir() {
+        append_dir_all(&mut builder, &top_dir.join("vendor"), &vendor_dir, mtime)?;
+    }
+    let mut header = new_header(mtime);
+    header.set_mode(0o644);
+    header.set_size(cargo_config.len() as u64);
+    builder.append_data(
+        &mut header,
+        top_dir.join(".cargo").join("config.toml"),
+        cargo_config.as_bytes(),
+    )?;
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// Returns the paths of the files tracked by git in `base`, relative to `base`.
+fn tracked_files(base: &Path) -> Result<Vec<PathBuf>, SrpmError> {
+    let output = Command::new("git")
+        .args(["ls-files", "-z", "--recurse-submodules"])
+        .current_dir(base)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(SrpmError::GitProcess)?;
+    if !output.status.success() {
+        Err(SrpmError::GitFailed(output.status.to_string()))?;
+    }
+    Ok(output
+        .stdout
+        .split(|v| *v == 0)
+        .filter(|v| !v.is_empty())
+        .map(|v| PathBuf::from(String::from_utf8_lossy(v).as_ref()))
+        .collect())
+}
+
+/// Appends the `files` in `base` under `top_dir`, except the ones replaced by the vendored
+/// dependencies and the configuration to use them.
+fn append_files<W: Write>(
+    builder: &mut Builder<W>,
+    top_dir: &Path,
+    base: &Path,
+    files: &[PathBuf],
+    mtime: u64,
+) -> io::Result<()> {
+    let cargo_config = Path::new(".cargo").join("config.toml");
+    for file in files {
+        if file.starts_with("vendor") || *file == cargo_config {
+            continue;
+        }
+        let path = base.join(file);
+        // the files deleted but still tracked
+        if fs::symlink_metadata(&path).is_err() {
+            continue;
+        }
+        append_path(builder, &top_dir.join(file), &path, mtime)?;
+    }
+    Ok(())
+}
+
+/// Appends the contents of the directory `dir` recursively as `name`.
+fn append_dir_all<W: Write>(
+    builder: &mut Builder<W>,
+    name: &Path,
+    dir: &Path,
+    mtime: u64,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|v| v.file_name());
+    for entry in entries {
+        let name = name.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            append_dir_all(builder, &name, &entry.path(), mtime)?;
+        } else {
+            append_path(builder, &name, &entry.path(), mtime)?;
+        }
+    }
+    Ok(())
+}
+
+/// Appends the regular file or the symbolic link at `path` as `name`.
+fn append_path<W: Write>(
+    builder: &mut Builder<W>,
+    name: &Path,
+    path: &Path,
+    mtime: u64,
+) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    let mut header = new_header(mtime);
+    // the owner and the permissions other than the executable bits are not kept
+    header.set_metadata_in_mode(&metadata, HeaderMode::Deterministic);
+    header.set_mtime(mtime);
+    if metadata.file_type().is_symlink() {
+        builder.append_link(&mut header, name, fs::read_link(path)?)
+    } else if metadata.is_file() {
+        builder.append_data(&mut header, name, fs::File::open(path)?)
+    } else {
+        Ok(())
+    }
+}
+
+fn new_header(mtime: u64) -> Header {
+    let mut header = Header::new_gnu();
+    header.set_mtime(mtime);
+    header.set_username("root").unwrap();
+    header.set_groupname("root").unwrap();
+    header
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tar::{Archive, EntryType};
+
+    #[test]
+    fn test_append_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let base = tempdir.path();
+        fs::create_dir_all(base.join("src")).unwrap();
+        fs::create_dir_all(base.join("vendor/bar")).unwrap();
+        fs::create_dir_all(base.join(".cargo")).unwrap();
+        fs::write(base.join("Cargo.toml"), "[package]").unwrap();
+        fs::write(base.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(base.join("vendor/bar/lib.rs"), "").unwrap();
+        fs::write(base.join(".cargo/config.toml"), "").unwrap();
+        let long_name = format!("src/{}.rs", "x".repeat(120));
+        fs::write(base.join(&long_name), "").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("main.rs", base.join("src/link.rs")).unwrap();
+
+        let files = [
+            "Cargo.toml",
+            "src/main.rs",
+            long_name.as_str(),
+            "src/link.rs",
+            "src/deleted.rs",
+            "vendor/bar/lib.rs",
+            ".cargo/config.toml",
+        ]
+        .map(PathBuf::from);
+        let mut builder = Builder::new(Vec::new());
+        append_files(
+            &mut builder,
+            Path::new("foo-1.0"),
+            base,
+            &files,
+            1_600_000_000,
+        )
+        .unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let mut archive = Archive::new(archive.as_slice());
+        let entries = archive
+            .entries()
+            .unwrap()
+            .map(|v| {
+                let v = v.unwrap();
+                let header = v.header();
+                (
+                    v.path().unwrap().to_string_lossy().to_string(),
+                    header.entry_type(),
+                    header.mtime().unwrap(),
+                    header.username().unwrap().map(str::to_string),
+                    v.link_name()
+                        .unwrap()
+                        .map(|v| v.to_string_lossy().to_string()),
+                )
+            })
+            .collect::<Vec<_>>();
+        let file = |name: &str| {
+            (
+                name.to_string(),
+                EntryType::Regular,
+                1_600_000_000,
+                Some("root".to_string()),
+                None,
+            )
+        };
+        let mut expected = vec![
+            file("foo-1.0/Cargo.toml"),
+            file("foo-1.0/src/main.rs"),
+            file(&format!("foo-1.0/{long_name}")),
+        ];
+        #[cfg(unix)]
+        expected.push((
+            "foo-1.0/src/link.rs".to_string(),
+            EntryType::Symlink,
+            1_600_000_000,
+            Some("root".to_string()),
+            Some("main.rs".to_string()),
+        ));
+        assert_eq!(entries, expected);
+    }
+}